@@ -0,0 +1,492 @@
+use crate::character::Message;
+use crate::core::ability_score::{Ability, AbilityScore, AbilityScores};
+use iced::{button, pick_list, Align, Button, Column, Length, PickList, Row, Text};
+use rand::Rng;
+use serde::export::Formatter;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+pub const POINT_BUY_BUDGET: isize = 27;
+pub const STANDARD_ARRAY: [isize; 6] = [15, 14, 13, 12, 10, 8];
+
+/// How the base ability scores were generated, kept so they can be checked later. Racial and
+/// other bonuses are applied as effects, so the base scores are exactly what was generated.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(tag = "method")]
+pub enum AbilityGeneration {
+    PointBuy,
+    StandardArray,
+    /// Every set of 4d6 rolled, in order. The lowest die of each set is dropped.
+    Rolled {
+        rolls: Vec<Vec<isize>>,
+        /// The sets of each earlier roll, oldest first, kept when the scores were rerolled.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        rerolled: Vec<Vec<Vec<isize>>>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Method {
+    PointBuy,
+    StandardArray,
+    Rolled,
+}
+
+#[derive(Debug, Clone)]
+pub enum AbilityGenerationMessage {
+    Open,
+    SelectMethod(Method),
+    Increase(Ability),
+    Decrease(Ability),
+    Assign(Ability, PoolValue),
+    Roll,
+    Apply,
+    Cancel,
+}
+
+/// A value from the standard array or the rolled totals, told apart by position so equal
+/// values can each be assigned once.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct PoolValue {
+    index: usize,
+    value: isize,
+}
+
+impl Display for PoolValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AbilityGenerationState {
+    generation: Option<AbilityGeneration>,
+    generator: Option<Generator>,
+    open_button: button::State,
+}
+
+#[derive(Debug, Clone)]
+struct Generator {
+    method: Method,
+    point_buy: Vec<isize>,
+    assigned: Vec<Option<PoolValue>>,
+    rolls: Vec<Vec<isize>>,
+    rerolled: Vec<Vec<Vec<isize>>>,
+    /// Why the scores couldn't be applied.
+    error: Option<String>,
+    method_buttons: Vec<button::State>,
+    score_buttons: Vec<(button::State, button::State)>,
+    pool_lists: Vec<pick_list::State<PoolValue>>,
+    roll_button: button::State,
+    apply_button: button::State,
+    cancel_button: button::State,
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        Generator {
+            method: Method::PointBuy,
+            point_buy: vec![8; 6],
+            assigned: vec![None; 6],
+            rolls: vec![],
+            rerolled: vec![],
+            error: None,
+            method_buttons: vec![button::State::default(); 3],
+            score_buttons: vec![Default::default(); 6],
+            pool_lists: vec![Default::default(); 6],
+            roll_button: button::State::default(),
+            apply_button: button::State::default(),
+            cancel_button: button::State::default(),
+        }
+    }
+}
+
+/// The points a score costs in point buy, or `None` if it can't be bought.
+pub fn point_buy_cost(score: isize) -> Option<isize> {
+    match score {
+        8..=13 => Some(score - 8),
+        14 => Some(7),
+        15 => Some(9),
+        _ => None,
+    }
+}
+
+/// The points spent on `scores`, or why they aren't a valid point buy.
+pub fn point_buy_spent(scores: &[isize]) -> Result<isize, String> {
+    let mut spent = 0;
+    for score in scores {
+        spent += point_buy_cost(*score)
+            .ok_or_else(|| format!("{} can't be bought; scores range from 8 to 15", score))?;
+    }
+    if spent > POINT_BUY_BUDGET {
+        Err(format!("{} points spent of {}", spent, POINT_BUY_BUDGET))
+    } else {
+        Ok(spent)
+    }
+}
+
+/// Rolls 4d6, keeping every die so the roll can be checked.
+pub fn roll_4d6<R: Rng>(rng: &mut R) -> Vec<isize> {
+    (0..4).map(|_| rng.gen_range(1, 7)).collect()
+}
+
+/// The total of the highest three dice.
+pub fn keep_highest_three(dice: &[isize]) -> isize {
+    let lowest = dice.iter().min().cloned().unwrap_or(0);
+    dice.iter().sum::<isize>() - lowest
+}
+
+/// Scores from assigning a pool value to each ability, or the ability still to be assigned.
+fn assign(assigned: &[Option<PoolValue>]) -> Result<Vec<isize>, String> {
+    let mut scores = vec![];
+    for (ability, value) in Ability::all().into_iter().zip(assigned.iter()) {
+        match value {
+            Some(value) => scores.push(value.value),
+            None => return Err(format!("Assign a score to {}", ability)),
+        }
+    }
+    Ok(scores)
+}
+
+fn ability_scores(scores: &[isize]) -> AbilityScores {
+    Ability::all().into_iter().zip(scores.iter()).fold(
+        AbilityScores::default(),
+        |ability_scores, (ability, score)| ability_scores.with(ability, AbilityScore::of(*score)),
+    )
+}
+
+impl Generator {
+    fn pool(&self) -> Vec<PoolValue> {
+        let values = match self.method {
+            Method::PointBuy => vec![],
+            Method::StandardArray => STANDARD_ARRAY.to_vec(),
+            Method::Rolled => self
+                .rolls
+                .iter()
+                .map(|dice| keep_highest_three(dice))
+                .collect(),
+        };
+        values
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| PoolValue { index, value })
+            .collect()
+    }
+
+    fn result(&self) -> Result<(AbilityScores, AbilityGeneration), String> {
+        match self.method {
+            Method::PointBuy => {
+                point_buy_spent(&self.point_buy)?;
+                Ok((ability_scores(&self.point_buy), AbilityGeneration::PointBuy))
+            }
+            Method::StandardArray => Ok((
+                ability_scores(&assign(&self.assigned)?),
+                AbilityGeneration::StandardArray,
+            )),
+            Method::Rolled if self.rolls.is_empty() => Err("Roll the scores first".to_string()),
+            Method::Rolled => Ok((
+                ability_scores(&assign(&self.assigned)?),
+                AbilityGeneration::Rolled {
+                    rolls: self.rolls.clone(),
+                    rerolled: self.rerolled.clone(),
+                },
+            )),
+        }
+    }
+}
+
+impl AbilityGenerationState {
+    pub fn from(generation: Option<AbilityGeneration>) -> AbilityGenerationState {
+        AbilityGenerationState {
+            generation,
+            ..AbilityGenerationState::default()
+        }
+    }
+
+    pub fn persistable(&self) -> Option<AbilityGeneration> {
+        self.generation.clone()
+    }
+
+    /// Returns the generated base scores once they are applied.
+    pub fn update(&mut self, message: AbilityGenerationMessage) -> Option<AbilityScores> {
+        if let AbilityGenerationMessage::Open = message {
+            self.generator = Some(Generator::default());
+            return None;
+        }
+        let generator = self.generator.as_mut()?;
+        generator.error = None;
+        match message {
+            AbilityGenerationMessage::SelectMethod(method) => {
+                generator.method = method;
+                generator.assigned = vec![None; 6];
+            }
+            AbilityGenerationMessage::Increase(ability) => {
+                let index = ability_index(&ability);
+                generator.point_buy[index] = std::cmp::min(15, generator.point_buy[index] + 1);
+            }
+            AbilityGenerationMessage::Decrease(ability) => {
+                let index = ability_index(&ability);
+                generator.point_buy[index] = std::cmp::max(8, generator.point_buy[index] - 1);
+            }
+            AbilityGenerationMessage::Assign(ability, value) => {
+                // A value can only go to one ability, so it moves from wherever it was.
+                for assigned in generator.assigned.iter_mut() {
+                    if *assigned == Some(value) {
+                        *assigned = None;
+                    }
+                }
+                generator.assigned[ability_index(&ability)] = Some(value);
+            }
+            AbilityGenerationMessage::Roll => {
+                let mut rng = rand::thread_rng();
+                let rolls = (0..6).map(|_| roll_4d6(&mut rng)).collect();
+                let earlier = std::mem::replace(&mut generator.rolls, rolls);
+                if !earlier.is_empty() {
+                    generator.rerolled.push(earlier);
+                }
+                generator.assigned = vec![None; 6];
+            }
+            AbilityGenerationMessage::Apply => match generator.result() {
+                Ok((ability_scores, generation)) => {
+                    self.generation = Some(generation);
+                    self.generator = None;
+                    return Some(ability_scores);
+                }
+                Err(e) => generator.error = Some(format!("Cannot apply ability scores: {}", e)),
+            },
+            AbilityGenerationMessage::Cancel => self.generator = None,
+            AbilityGenerationMessage::Open => {}
+        }
+        None
+    }
+
+    pub fn view(&mut self) -> Column<Message> {
+        let AbilityGenerationState {
+            generation,
+            generator,
+            open_button,
+        } = self;
+        let message = |message| Message::AbilityGeneration(message);
+
+        let generator = match generator {
+            Some(generator) => generator,
+            None => {
+                let generated = match generation {
+                    Some(AbilityGeneration::PointBuy) => "Generated by point buy".to_string(),
+                    Some(AbilityGeneration::StandardArray) => {
+                        "Generated from the standard array".to_string()
+                    }
+                    Some(AbilityGeneration::Rolled { rolls, rerolled }) => format!(
+                        "Rolled {}{}",
+                        rolls
+                            .iter()
+                            .map(|dice| format!("{:?}", dice))
+                            .collect::<Vec<String>>()
+                            .join(" "),
+                        rerolled_times(rerolled.len())
+                    ),
+                    None => "".to_string(),
+                };
+                return Column::new().push(
+                    Row::new()
+                        .spacing(20)
+                        .align_items(Align::Center)
+                        .push(Text::new(generated).size(12).width(Length::Fill))
+                        .push(
+                            Button::new(open_button, Text::new("Generate").size(16))
+                                .on_press(message(AbilityGenerationMessage::Open))
+                                .padding(8),
+                        ),
+                );
+            }
+        };
+
+        let pool = generator.pool();
+        let result = generator.result();
+        let Generator {
+            method,
+            point_buy,
+            assigned,
+            rolls,
+            rerolled,
+            error,
+            method_buttons,
+            score_buttons,
+            pool_lists,
+            roll_button,
+            apply_button,
+            cancel_button,
+        } = generator;
+
+        let mut methods = Row::new().spacing(8);
+        for (button, (option, label)) in method_buttons.iter_mut().zip(vec![
+            (Method::PointBuy, "Point Buy"),
+            (Method::StandardArray, "Standard Array"),
+            (Method::Rolled, "4d6 Drop Lowest"),
+        ]) {
+            let mut button = Button::new(button, Text::new(label).size(16)).padding(8);
+            if *method != option {
+                button = button.on_press(message(AbilityGenerationMessage::SelectMethod(option)));
+            }
+            methods = methods.push(button);
+        }
+        let mut column = Column::new().spacing(4).push(methods);
+
+        if *method == Method::Rolled {
+            let rolled = rolls
+                .iter()
+                .map(|dice| format!("{:?} = {}", dice, keep_highest_three(dice)))
+                .collect::<Vec<String>>()
+                .join(", ")
+                + &rerolled_times(rerolled.len());
+            column = column.push(
+                Row::new()
+                    .spacing(20)
+                    .align_items(Align::Center)
+                    .push(
+                        Button::new(roll_button, Text::new("Roll").size(16))
+                            .on_press(message(AbilityGenerationMessage::Roll))
+                            .padding(8),
+                    )
+                    .push(Text::new(rolled).size(12)),
+            );
+        }
+
+        for (((ability, score), (increase, decrease)), (list, value)) in Ability::all()
+            .into_iter()
+            .zip(point_buy.iter())
+            .zip(score_buttons.iter_mut())
+            .zip(pool_lists.iter_mut().zip(assigned.iter()))
+        {
+            let mut row = Row::new().spacing(8).align_items(Align::Center).push(
+                Text::new(ability.abbreviation())
+                    .size(16)
+                    .width(Length::Units(40)),
+            );
+            if *method == Method::PointBuy {
+                row = row
+                    .push(
+                        Button::new(decrease, Text::new("-").size(16))
+                            .on_press(message(AbilityGenerationMessage::Decrease(ability.clone())))
+                            .padding(4),
+                    )
+                    .push(Text::new(score.to_string()).size(16))
+                    .push(
+                        Button::new(increase, Text::new("+").size(16))
+                            .on_press(message(AbilityGenerationMessage::Increase(ability.clone())))
+                            .padding(4),
+                    )
+                    .push(
+                        Text::new(format!(
+                            "({} points)",
+                            point_buy_cost(*score).unwrap_or_default()
+                        ))
+                        .size(12),
+                    );
+            } else if !pool.is_empty() {
+                row = row.push(PickList::new(list, pool.clone(), *value, move |value| {
+                    message(AbilityGenerationMessage::Assign(ability.clone(), value))
+                }));
+            }
+            column = column.push(row);
+        }
+
+        if *method == Method::PointBuy {
+            let spent = point_buy
+                .iter()
+                .filter_map(|score| point_buy_cost(*score))
+                .sum::<isize>();
+            column = column
+                .push(Text::new(format!("{} / {} points spent", spent, POINT_BUY_BUDGET)).size(16));
+        }
+
+        let mut apply = Button::new(apply_button, Text::new("Apply").size(16)).padding(8);
+        match result {
+            Ok(_) => apply = apply.on_press(message(AbilityGenerationMessage::Apply)),
+            Err(e) => column = column.push(Text::new(e).size(12)),
+        }
+        if let Some(error) = error {
+            column = column.push(Text::new(error.as_str()).size(12));
+        }
+        column.push(
+            Row::new().spacing(20).push(apply).push(
+                Button::new(cancel_button, Text::new("Cancel").size(16))
+                    .on_press(message(AbilityGenerationMessage::Cancel))
+                    .padding(8),
+            ),
+        )
+    }
+}
+
+fn rerolled_times(times: usize) -> String {
+    match times {
+        0 => "".to_string(),
+        1 => ", rerolled once".to_string(),
+        times => format!(", rerolled {} times", times),
+    }
+}
+
+fn ability_index(ability: &Ability) -> usize {
+    Ability::all()
+        .iter()
+        .position(|a| a == ability)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn point_buy_stays_within_budget() {
+        assert_eq!(point_buy_spent(&[15, 15, 15, 8, 8, 8]), Ok(27));
+        assert_eq!(point_buy_spent(&[13, 13, 13, 12, 12, 12]), Ok(27));
+        assert!(point_buy_spent(&[15, 15, 15, 9, 8, 8]).is_err());
+        assert!(point_buy_spent(&[16, 8, 8, 8, 8, 8]).is_err());
+    }
+
+    #[test]
+    fn rolled_scores_drop_the_lowest_die() {
+        assert_eq!(keep_highest_three(&[6, 1, 4, 3]), 13);
+        assert_eq!(keep_highest_three(&[2, 2, 2, 2]), 6);
+
+        let mut generator = Generator {
+            method: Method::Rolled,
+            rolls: vec![vec![6, 6, 6, 1]; 6],
+            ..Generator::default()
+        };
+        assert!(generator.result().is_err());
+        generator.assigned = generator.pool().into_iter().map(Some).collect();
+        let (scores, generation) = generator.result().unwrap();
+        assert_eq!(scores.get(Ability::Charisma).value(), 18);
+        assert_eq!(
+            generation,
+            AbilityGeneration::Rolled {
+                rolls: vec![vec![6, 6, 6, 1]; 6],
+                rerolled: vec![]
+            }
+        );
+    }
+
+    #[test]
+    fn rerolling_keeps_the_earlier_rolls() {
+        let mut state = AbilityGenerationState::default();
+        state.update(AbilityGenerationMessage::Open);
+        state.update(AbilityGenerationMessage::SelectMethod(Method::Rolled));
+        state.update(AbilityGenerationMessage::Roll);
+        let first = state.generator.as_ref().unwrap().rolls.clone();
+        state.update(AbilityGenerationMessage::Roll);
+        state.update(AbilityGenerationMessage::Roll);
+
+        let generator = state.generator.as_ref().unwrap();
+        assert_eq!(generator.rerolled.len(), 2);
+        assert_eq!(generator.rerolled[0], first);
+        assert_eq!(generator.rolls.len(), 6);
+
+        state.update(AbilityGenerationMessage::Apply);
+        assert_eq!(
+            state.generator.unwrap().error,
+            Some("Cannot apply ability scores: Assign a score to Strength".to_string())
+        );
+    }
+}
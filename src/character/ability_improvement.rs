@@ -0,0 +1,78 @@
+use crate::core::ability_score::{Ability, IMPROVEMENTS};
+use crate::core::effect::{ActiveEffect, Effect};
+use crate::core::feature_path::FeaturePath;
+use crate::core::stat::ValueBonus;
+use serde::{Deserialize, Serialize};
+
+/// An ability score improvement taken at a class level, as ability increases or a feat.
+/// Increases apply as effects, so the base scores keep what the character started with.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct AbilityImprovement {
+    class: String,
+    level: isize,
+    /// Each listed ability is raised by 1; one listed twice is raised by 2.
+    #[serde(default)]
+    abilities: Vec<Ability>,
+    #[serde(default)]
+    feat: Option<String>,
+}
+
+impl AbilityImprovement {
+    pub fn abilities(class: String, level: isize, abilities: Vec<Ability>) -> AbilityImprovement {
+        AbilityImprovement {
+            class,
+            level,
+            abilities,
+            feat: None,
+        }
+    }
+
+    pub fn feat(class: String, level: isize, feat: String) -> AbilityImprovement {
+        AbilityImprovement {
+            class,
+            level,
+            abilities: vec![],
+            feat: Some(feat),
+        }
+    }
+
+    pub fn taken_feat(&self) -> Option<&String> {
+        self.feat.as_ref()
+    }
+
+    /// An ability modifier for each improved ability, from "Ability Score Improvement › Class N".
+    pub fn effects(&self) -> Vec<ActiveEffect> {
+        let source = FeaturePath::of(vec![
+            IMPROVEMENTS.to_string(),
+            format!("{} {}", self.class, self.level),
+        ]);
+        Ability::all()
+            .into_iter()
+            .filter_map(|ability| {
+                let modifier = self.abilities.iter().filter(|a| **a == ability).count() as isize;
+                if modifier == 0 {
+                    return None;
+                }
+                Some(ActiveEffect::new(
+                    Effect::Ability {
+                        bonus: ValueBonus::Modifier { modifier },
+                        ability,
+                        name: None,
+                    },
+                    source.clone(),
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Feats taken directly together with those taken in place of an improvement.
+pub fn taken_feats(feats: &[String], improvements: &[AbilityImprovement]) -> Vec<String> {
+    let mut taken = feats.to_vec();
+    for feat in improvements.iter().filter_map(|i| i.taken_feat()) {
+        if !taken.contains(feat) {
+            taken.push(feat.clone());
+        }
+    }
+    taken
+}
@@ -1,4 +1,5 @@
 use crate::character::Message;
+use crate::core::ability_score::{Ability, ModifiedAbilityScores};
 use crate::util::format_modifier;
 use iced::{Column, HorizontalAlignment, Length, Row, Text, VerticalAlignment};
 use serde::{Deserialize, Serialize};
@@ -8,6 +9,15 @@ pub struct Classes {
     classes: Vec<Class>,
 }
 
+/// How a class that casts spells readies them.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Preparation {
+    pub class: String,
+    /// How many spells it can prepare each day from its list, or `None` when it casts the
+    /// spells it knows.
+    pub limit: Option<isize>,
+}
+
 impl Classes {
     pub fn from(classes: Vec<Class>) -> Classes {
         Classes { classes }
@@ -63,10 +73,89 @@ impl Classes {
         column.push(proficiency_row)
     }
 
-    fn total_level(&self) -> isize {
+    /// The spellcasting ability of the first class that casts spells.
+    pub fn spellcasting_ability(&self) -> Option<Ability> {
+        self.classes
+            .iter()
+            .filter_map(|class| class.spellcasting_ability())
+            .next()
+    }
+
+    /// Each class that casts spells, by name, with its spellcasting ability.
+    pub fn spellcasters(&self) -> Vec<(String, Ability)> {
+        self.classes
+            .iter()
+            .filter_map(|class| Some((class.name.clone(), class.spellcasting_ability()?)))
+            .collect()
+    }
+
+    pub fn total_level(&self) -> isize {
         self.classes.clone().into_iter().map(|c| c.level).sum()
     }
 
+    /// How each class that casts spells readies them, in class order.
+    pub fn preparation(&self, ability_scores: &ModifiedAbilityScores) -> Vec<Preparation> {
+        self.classes
+            .iter()
+            .filter_map(|class| {
+                let modifier = ability_scores
+                    .get(class.spellcasting_ability()?)
+                    .score()
+                    .modifier();
+                let level = match class.name.as_str() {
+                    "Cleric" | "Druid" | "Wizard" => Some(class.level),
+                    "Artificer" | "Paladin" => Some(class.level / 2),
+                    _ => None,
+                };
+                Some(Preparation {
+                    class: class.name.clone(),
+                    limit: level.map(|level| std::cmp::max(1, level + modifier)),
+                })
+            })
+            .collect()
+    }
+
+    /// The caster level used to look up spell slots. A single half or third caster rounds up,
+    /// as its own class table does, but multiclassed casters round each class down.
+    pub fn caster_level(&self) -> isize {
+        let casters = self
+            .classes
+            .iter()
+            .filter(|c| c.caster.is_some() && c.caster != Some(CasterType::Pact))
+            .collect::<Vec<&Class>>();
+        let single_class = casters.len() == 1;
+
+        casters
+            .into_iter()
+            .map(|class| match (&class.caster, single_class) {
+                (Some(CasterType::Half), true) if class.level < 2 => 0,
+                (Some(CasterType::Half), true) => (class.level + 1) / 2,
+                (Some(CasterType::Half), false) => class.level / 2,
+                (Some(CasterType::Third), true) if class.level < 3 => 0,
+                (Some(CasterType::Third), true) => (class.level + 2) / 3,
+                (Some(CasterType::Third), false) => class.level / 3,
+                _ => class.level,
+            })
+            .sum()
+    }
+
+    pub fn pact_level(&self) -> isize {
+        self.classes
+            .iter()
+            .filter(|c| c.caster == Some(CasterType::Pact))
+            .map(|c| c.level)
+            .sum()
+    }
+
+    /// The levels taken in the named class, or 0 if the character has none.
+    pub fn level(&self, class_name: &str) -> isize {
+        self.classes
+            .iter()
+            .filter(|c| c.name.eq_ignore_ascii_case(class_name))
+            .map(|c| c.level)
+            .sum()
+    }
+
     pub fn proficiency_bonus(&self) -> isize {
         let total_level = self.total_level();
         if total_level < 5 {
@@ -89,20 +178,110 @@ impl Classes {
 pub struct Class {
     name: String,
     level: isize,
+    #[serde(default)]
+    caster: Option<CasterType>,
+    /// Classes without one use the ability their class normally casts with, if any.
+    #[serde(default)]
+    spellcasting_ability: Option<Ability>,
+    #[serde(default)]
+    subclass: Option<String>,
+}
+
+/// How a class's levels count towards spell slots.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub enum CasterType {
+    Full,
+    Half,
+    Third,
+    /// Pact magic slots are tracked separately from other spell slots.
+    Pact,
 }
 
 impl Class {
     pub fn new(name: String, level: isize) -> Class {
-        Class { name, level }
+        Class {
+            name,
+            level,
+            caster: None,
+            spellcasting_ability: None,
+            subclass: None,
+        }
+    }
+
+    pub fn with_caster(self, caster: CasterType) -> Class {
+        Class {
+            caster: Some(caster),
+            ..self
+        }
+    }
+
+    pub fn with_spellcasting_ability(self, ability: Ability) -> Class {
+        Class {
+            spellcasting_ability: Some(ability),
+            ..self
+        }
+    }
+
+    pub fn with_subclass(self, subclass: Option<String>) -> Class {
+        Class { subclass, ..self }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn level(&self) -> isize {
+        self.level
+    }
+
+    pub fn subclass(&self) -> Option<&str> {
+        self.subclass.as_deref()
+    }
+
+    pub fn level_up(&mut self) {
+        self.level += 1;
+    }
+
+    fn spellcasting_ability(&self) -> Option<Ability> {
+        if let Some(ability) = &self.spellcasting_ability {
+            return Some(ability.clone());
+        }
+        match self.name.as_str() {
+            "Bard" | "Paladin" | "Sorcerer" | "Warlock" => Some(Ability::Charisma),
+            "Artificer" | "Wizard" => Some(Ability::Intelligence),
+            "Cleric" | "Druid" | "Ranger" => Some(Ability::Wisdom),
+            _ => None,
+        }
     }
 
     fn view(&mut self) -> Row<Message> {
-        Row::new().push(Text::new(format!("Level {} {}", self.level, self.name)).size(24))
+        let text = match &self.subclass {
+            Some(subclass) => format!("Level {} {} ({})", self.level, self.name, subclass),
+            None => format!("Level {} {}", self.level, self.name),
+        };
+        Row::new().push(Text::new(text).size(24))
     }
 }
 #[cfg(test)]
 mod test {
     use crate::character::class::{Class, Classes};
+    use crate::core::ability_score::Ability;
+
+    #[test]
+    fn declared_spellcasting_abilities_win_over_class_defaults() {
+        let classes = Classes::from(vec![
+            Class::new("Sorcerer".to_string(), 3),
+            Class::new("Fighter".to_string(), 3),
+            Class::new("Psion".to_string(), 1).with_spellcasting_ability(Ability::Intelligence),
+        ]);
+        assert_eq!(
+            classes.spellcasters(),
+            vec![
+                ("Sorcerer".to_string(), Ability::Charisma),
+                ("Psion".to_string(), Ability::Intelligence),
+            ]
+        );
+    }
 
     fn assert_proficiency(v: (Vec<isize>, isize)) {
         let (levels, proficiency) = v;
@@ -112,6 +291,9 @@ mod test {
                 .map(|l| Class {
                     name: "test".to_string(),
                     level: l,
+                    caster: None,
+                    spellcasting_ability: None,
+                    subclass: None,
                 })
                 .collect(),
         };
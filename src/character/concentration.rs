@@ -0,0 +1,116 @@
+use crate::character::Message;
+use crate::core::feature_path::FeaturePath;
+use iced::{button, Align, Button, Row, Text};
+use serde::{Deserialize, Serialize};
+
+pub const SAVING_THROWS_FEATURE: &str = "Saving Throws";
+pub const CONSTITUTION_SAVE_ROLL: &str = "Constitution";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Concentration {
+    feature: Option<FeaturePath>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ConcentrationMessage {
+    Start(FeaturePath),
+    End,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ConcentrationState {
+    concentration: Concentration,
+    end_button: button::State,
+}
+
+type IsDirty = bool;
+
+/// The DC of the constitution save made to keep concentrating after taking damage.
+pub fn save_dc(damage: isize) -> isize {
+    std::cmp::max(10, damage / 2)
+}
+
+impl Concentration {
+    pub fn to_state(self) -> ConcentrationState {
+        ConcentrationState {
+            concentration: self,
+            ..ConcentrationState::default()
+        }
+    }
+}
+
+impl ConcentrationState {
+    pub fn persistable(&self) -> Concentration {
+        self.concentration.clone()
+    }
+
+    pub fn concentrating_on(&self) -> Option<&FeaturePath> {
+        self.concentration.feature.as_ref()
+    }
+
+    pub fn update(&mut self, message: ConcentrationMessage) -> IsDirty {
+        match message {
+            ConcentrationMessage::Start(path) => {
+                if self.concentration.feature.as_ref() == Some(&path) {
+                    false
+                } else {
+                    self.concentration.feature = Some(path);
+                    true
+                }
+            }
+            ConcentrationMessage::End => self.concentration.feature.take().is_some(),
+        }
+    }
+
+    pub fn view(&mut self, feature_name: Option<String>) -> Row<Message> {
+        let ConcentrationState {
+            concentration,
+            end_button,
+        } = self;
+
+        match (&concentration.feature, feature_name) {
+            (Some(path), name) => Row::new()
+                .spacing(20)
+                .align_items(Align::Center)
+                .push(
+                    Text::new(format!(
+                        "Concentrating on {}",
+                        name.unwrap_or_else(|| path.to_string())
+                    ))
+                    .size(24),
+                )
+                .push(
+                    Button::new(end_button, Text::new("End").size(16))
+                        .on_press(Message::Concentration(ConcentrationMessage::End))
+                        .padding(8),
+                ),
+            (None, _) => Row::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn save_dc_is_half_damage_with_minimum_of_ten() {
+        assert_eq!(save_dc(1), 10);
+        assert_eq!(save_dc(21), 10);
+        assert_eq!(save_dc(22), 11);
+        assert_eq!(save_dc(45), 22);
+    }
+
+    #[test]
+    fn only_one_feature_is_concentrated_on() {
+        let mut state = Concentration::default().to_state();
+        let haste = FeaturePath::of(vec!["Haste".to_string()]);
+        let fly = FeaturePath::of(vec!["Fly".to_string()]);
+
+        assert!(state.update(ConcentrationMessage::Start(haste)));
+        assert!(state.update(ConcentrationMessage::Start(fly.clone())));
+        assert_eq!(state.concentrating_on(), Some(&fly));
+        assert!(state.update(ConcentrationMessage::End));
+        assert_eq!(state.concentrating_on(), None);
+    }
+}
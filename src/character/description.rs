@@ -7,6 +7,8 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Description {
     race: String,
+    #[serde(default)]
+    background: Option<String>,
     size: Size,
     alignment: Alignment,
     speed: isize,
@@ -23,7 +25,7 @@ pub struct Height {
     inches: isize,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 pub enum Size {
     Tiny,
     Small,
@@ -71,9 +73,45 @@ impl Default for MoralAlignment {
 }
 
 impl Description {
+    pub fn new(race: String, background: Option<String>) -> Description {
+        Description {
+            race,
+            background,
+            ..Description::default()
+        }
+    }
+
+    pub fn speed(&self) -> isize {
+        self.speed
+    }
+
+    /// The race template, if a race is set.
+    pub fn race(&self) -> Option<&str> {
+        Some(self.race.as_str()).filter(|race| !race.is_empty())
+    }
+
+    pub fn background(&self) -> Option<&str> {
+        self.background.as_deref()
+    }
+
+    /// Takes the speed and size a template grants.
+    pub fn derive(&mut self, speed: Option<isize>, size: Option<Size>) -> bool {
+        let mut changed = false;
+        if let Some(speed) = speed.filter(|speed| *speed != self.speed) {
+            self.speed = speed;
+            changed = true;
+        }
+        if let Some(size) = size.filter(|size| *size != self.size) {
+            self.size = size;
+            changed = true;
+        }
+        changed
+    }
+
     pub fn view(&mut self) -> Column<Message> {
         let Description {
             race,
+            background,
             size,
             alignment,
             speed,
@@ -85,6 +123,10 @@ impl Description {
         } = self;
         let mut column_1 = Column::new()
             .push(two_column_row(Text::new("Race"), Text::new(race.clone())))
+            .push(two_column_row(
+                Text::new("Background"),
+                Text::new(background.clone().unwrap_or_default()),
+            ))
             .push(two_column_row(
                 Text::new("Size"),
                 Text::new(format!("{:?}", size)),
@@ -0,0 +1,228 @@
+use crate::character::Message;
+use iced::{button, text_input, Align, Button, Column, Length, Row, Text, TextInput};
+use serde::{Deserialize, Serialize};
+
+/// Experience needed to reach each level, starting at level 1.
+const THRESHOLDS: [isize; 20] = [
+    0, 300, 900, 2700, 6500, 14000, 23000, 34000, 48000, 64000, 85000, 100000, 120000, 140000,
+    165000, 195000, 225000, 265000, 305000, 355000,
+];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Experience {
+    #[serde(default)]
+    points: isize,
+    /// Groups that level by milestone instead of experience points.
+    #[serde(default)]
+    milestones: bool,
+    /// Whether the next milestone has been reached, until the character levels up.
+    #[serde(default)]
+    milestone_reached: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExperienceMessage {
+    ChangeAward(String),
+    ChangePartySize(String),
+    /// Award experience split evenly across the party, rounding down.
+    Award,
+    ToggleMilestones,
+    ReachMilestone,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExperienceState {
+    experience: Experience,
+    /// The award as typed, parsed when it is awarded.
+    award: String,
+    party_size: Option<isize>,
+    award_input: text_input::State,
+    party_size_input: text_input::State,
+    award_button: button::State,
+    milestones_button: button::State,
+    milestone_button: button::State,
+}
+
+type IsDirty = bool;
+
+/// The experience needed to reach the level after `level`, or `None` at the highest level.
+pub fn next_threshold(level: isize) -> Option<isize> {
+    if level < 1 {
+        return Some(0);
+    }
+    THRESHOLDS.get(level as usize).cloned()
+}
+
+impl Experience {
+    pub fn to_state(self) -> ExperienceState {
+        ExperienceState {
+            experience: self,
+            ..ExperienceState::default()
+        }
+    }
+
+    pub fn ready_to_level_up(&self, level: isize) -> bool {
+        if self.milestones {
+            self.milestone_reached
+        } else {
+            next_threshold(level)
+                .map(|threshold| self.points >= threshold)
+                .unwrap_or(false)
+        }
+    }
+
+    pub fn points(&self) -> isize {
+        self.points
+    }
+
+    pub fn milestone_reached(&self) -> bool {
+        self.milestone_reached
+    }
+
+    /// Leveling up uses up the milestone reached.
+    pub fn level_up(&mut self) {
+        self.milestone_reached = false;
+    }
+}
+
+impl ExperienceState {
+    pub fn persistable(&self) -> Experience {
+        self.experience.clone()
+    }
+
+    pub fn update(&mut self, message: ExperienceMessage) -> IsDirty {
+        match message {
+            ExperienceMessage::ChangeAward(award) => {
+                self.award = award;
+                false
+            }
+            ExperienceMessage::ChangePartySize(party_size) => {
+                self.party_size = party_size.parse::<isize>().ok().filter(|size| *size > 0);
+                false
+            }
+            ExperienceMessage::Award => {
+                let award = match self.award.trim().parse::<isize>() {
+                    Ok(award) => award,
+                    Err(_) => return false,
+                };
+                let share = award / self.party_size.unwrap_or(1);
+                self.award.clear();
+                self.experience.points += share;
+                share != 0
+            }
+            ExperienceMessage::ToggleMilestones => {
+                self.experience.milestones = !self.experience.milestones;
+                true
+            }
+            ExperienceMessage::ReachMilestone => {
+                let reached = self.experience.milestone_reached;
+                self.experience.milestone_reached = true;
+                !reached
+            }
+        }
+    }
+
+    pub fn view(&mut self, level: isize) -> Column<Message> {
+        let ready = self.experience.ready_to_level_up(level);
+        let ExperienceState {
+            experience,
+            award,
+            party_size,
+            award_input,
+            party_size_input,
+            award_button,
+            milestones_button,
+            milestone_button,
+        } = self;
+
+        let mut column = Column::new().spacing(4);
+        let (progress, mode) = if experience.milestones {
+            ("Milestone leveling".to_string(), "Use XP")
+        } else {
+            let progress = match next_threshold(level) {
+                Some(threshold) => format!("{} / {} XP", experience.points, threshold),
+                None => format!("{} XP", experience.points),
+            };
+            (progress, "Use milestones")
+        };
+        let mut header = Row::new()
+            .spacing(20)
+            .align_items(Align::Center)
+            .push(Text::new(progress).size(24).width(Length::FillPortion(2)));
+        if ready {
+            header = header.push(Text::new("Ready to level up").size(16));
+        }
+        column = column.push(
+            header.push(
+                Button::new(milestones_button, Text::new(mode).size(12))
+                    .on_press(Message::Experience(ExperienceMessage::ToggleMilestones))
+                    .padding(4),
+            ),
+        );
+
+        let controls = if experience.milestones {
+            let mut milestone =
+                Button::new(milestone_button, Text::new("Milestone").size(16)).padding(8);
+            if !experience.milestone_reached {
+                milestone =
+                    milestone.on_press(Message::Experience(ExperienceMessage::ReachMilestone));
+            }
+            Row::new().push(milestone)
+        } else {
+            let party_size = party_size.map(|size| size.to_string()).unwrap_or_default();
+            Row::new()
+                .spacing(8)
+                .align_items(Align::Center)
+                .push(
+                    TextInput::new(award_input, "XP", award.as_str(), |v| {
+                        Message::Experience(ExperienceMessage::ChangeAward(v))
+                    })
+                    .padding(8)
+                    .size(16)
+                    .on_submit(Message::Experience(ExperienceMessage::Award)),
+                )
+                .push(
+                    TextInput::new(
+                        party_size_input,
+                        "Split between",
+                        party_size.as_str(),
+                        |v| Message::Experience(ExperienceMessage::ChangePartySize(v)),
+                    )
+                    .padding(8)
+                    .size(16),
+                )
+                .push(
+                    Button::new(award_button, Text::new("Award").size(16))
+                        .on_press(Message::Experience(ExperienceMessage::Award))
+                        .padding(8),
+                )
+        };
+
+        column.push(controls)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn awards_split_across_the_party() {
+        let mut state = Experience::default().to_state();
+        state.update(ExperienceMessage::ChangeAward("1000".to_string()));
+        state.update(ExperienceMessage::ChangePartySize("3".to_string()));
+        assert!(state.update(ExperienceMessage::Award));
+        assert_eq!(state.persistable().points, 333);
+        assert!(state.persistable().ready_to_level_up(1));
+        assert!(!state.persistable().ready_to_level_up(2));
+        assert!(!state.persistable().ready_to_level_up(20));
+
+        state.update(ExperienceMessage::ChangeAward("-".to_string()));
+        assert!(!state.update(ExperienceMessage::Award));
+        assert_eq!(state.award, "-");
+        state.update(ExperienceMessage::ChangeAward("-33".to_string()));
+        assert!(state.update(ExperienceMessage::Award));
+        assert_eq!(state.persistable().points, 322);
+        assert_eq!(state.award, "");
+    }
+}
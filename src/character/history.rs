@@ -0,0 +1,395 @@
+use crate::character::concentration::ConcentrationMessage;
+use crate::character::experience::ExperienceMessage;
+use crate::character::hitpoints::HitPointMessage;
+use crate::character::persistence::CharacterPersistence;
+use crate::character::spellbook::SpellbookMessage;
+use crate::character::{Message, State};
+use crate::core::feature::FeatureMessage;
+use crate::core::feature_path::FeaturePath;
+use crate::core::roll::log::RollLogMessage;
+use crate::core::slot::{Recovery, SlotCommand};
+use iced::{button, Align, Button, Row, Subscription, Text};
+use iced_native::event::Status;
+use iced_native::keyboard::{self, KeyCode};
+use std::collections::VecDeque;
+
+/// How many changes can be undone.
+pub const LIMIT: usize = 50;
+
+#[derive(Debug, Clone)]
+pub enum HistoryMessage {
+    Undo,
+    Redo,
+}
+
+/// A change, with the character as it was on the other side of it.
+#[derive(Debug, Clone)]
+struct Entry {
+    label: String,
+    character: CharacterPersistence,
+}
+
+/// Changes to the character that can be undone and redone.
+/// The whole character is kept on each side of a change, so undoing damage also brings back
+/// the concentration it broke.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    undo: VecDeque<Entry>,
+    redo: Vec<Entry>,
+    undo_button: button::State,
+    redo_button: button::State,
+}
+
+impl History {
+    pub fn record(&mut self, label: String, before: CharacterPersistence) {
+        self.undo.push_back(Entry {
+            label,
+            character: before,
+        });
+        if self.undo.len() > LIMIT {
+            self.undo.pop_front();
+        }
+        self.redo.clear();
+    }
+
+    /// The label of the change undo would take back.
+    pub fn next_undo(&self) -> Option<&String> {
+        self.undo.back().map(|entry| &entry.label)
+    }
+
+    /// The label of the change redo would make again.
+    pub fn next_redo(&self) -> Option<&String> {
+        self.redo.last().map(|entry| &entry.label)
+    }
+
+    /// The character before the last change, keeping `current` to redo it.
+    pub fn undo(&mut self, current: CharacterPersistence) -> Option<CharacterPersistence> {
+        let entry = self.undo.pop_back()?;
+        self.redo.push(Entry {
+            label: entry.label,
+            character: current,
+        });
+        Some(entry.character)
+    }
+
+    /// The character after the last undone change, keeping `current` to undo it again.
+    pub fn redo(&mut self, current: CharacterPersistence) -> Option<CharacterPersistence> {
+        let entry = self.redo.pop()?;
+        self.undo.push_back(Entry {
+            label: entry.label,
+            character: current,
+        });
+        Some(entry.character)
+    }
+
+    pub fn view(&mut self) -> Row<'_, Message> {
+        let History {
+            undo,
+            redo,
+            undo_button,
+            redo_button,
+        } = self;
+
+        let mut undo_button = Button::new(
+            undo_button,
+            Text::new(match undo.back() {
+                Some(entry) => format!("Undo {}", entry.label),
+                None => "Undo".to_string(),
+            })
+            .size(16),
+        )
+        .padding(8);
+        if !undo.is_empty() {
+            undo_button = undo_button.on_press(Message::History(HistoryMessage::Undo));
+        }
+        let mut redo_button = Button::new(
+            redo_button,
+            Text::new(match redo.last() {
+                Some(entry) => format!("Redo {}", entry.label),
+                None => "Redo".to_string(),
+            })
+            .size(16),
+        )
+        .padding(8);
+        if !redo.is_empty() {
+            redo_button = redo_button.on_press(Message::History(HistoryMessage::Redo));
+        }
+
+        Row::new()
+            .spacing(20)
+            .align_items(Align::Center)
+            .push(undo_button)
+            .push(redo_button)
+    }
+}
+
+/// Ctrl+Z undoes, and Ctrl+Shift+Z or Ctrl+Y redoes; Cmd instead of Ctrl on macOS.
+pub fn shortcuts() -> Subscription<Message> {
+    iced_native::subscription::events_with(|event, status| match (event, status) {
+        (
+            iced_native::Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code,
+                modifiers,
+            }),
+            Status::Ignored,
+        ) if modifiers.is_command_pressed() => match key_code {
+            KeyCode::Z if modifiers.shift => Some(Message::History(HistoryMessage::Redo)),
+            KeyCode::Z => Some(Message::History(HistoryMessage::Undo)),
+            KeyCode::Y => Some(Message::History(HistoryMessage::Redo)),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// Whether a change made by `message` is play at the table, labelled by `label` and kept in
+/// the journal. Any other change is labelled by `describe`.
+pub fn tracks(message: &Message) -> bool {
+    matches!(
+        message,
+        Message::HitPoint(HitPointMessage::Heal)
+            | Message::HitPoint(HitPointMessage::Damage)
+            | Message::HitPoint(HitPointMessage::FullHealth)
+            | Message::Feature(FeatureMessage::Slot(_, _))
+            | Message::Feature(FeatureMessage::Activate(_))
+            | Message::Feature(FeatureMessage::Concentrate(_))
+            | Message::Feature(FeatureMessage::Convert(_))
+            | Message::Feature(FeatureMessage::RollTable(_, _))
+            | Message::Rest(_)
+            | Message::Concentration(ConcentrationMessage::End)
+            | Message::Spellbook(SpellbookMessage::Cast(_, Some(_)))
+    )
+}
+
+/// The number `message` changes, read before and after it to label the change.
+pub fn reading(state: &State, message: &Message) -> Option<isize> {
+    match message {
+        Message::HitPoint(_) => Some(state.hit_points.current_hit_points()),
+        Message::Feature(FeatureMessage::Slot(path, _)) => state
+            .features
+            .find_slot(path.segments())
+            .map(|slot| slot.current()),
+        Message::Spellbook(SpellbookMessage::Cast(_, Some(slot))) => state
+            .features
+            .find_slot(&slot.path())
+            .map(|slot| slot.current()),
+        _ => None,
+    }
+}
+
+/// Names the change `message` made, such as "Used Sorcery Points (5 → 4)", or None if it left
+/// the number it changes where it was.
+pub fn label(state: &State, message: &Message, before: Option<isize>) -> Option<String> {
+    let after = reading(state, message);
+    if before.is_some() && before == after {
+        return None;
+    }
+    let name = |path: &FeaturePath| {
+        state
+            .features
+            .feature_name(path)
+            .unwrap_or_else(|| path.breadcrumb())
+    };
+    let label = match (message, before, after) {
+        (Message::HitPoint(HitPointMessage::Damage), Some(before), Some(after)) => {
+            format!("Took {} damage ({} → {} HP)", before - after, before, after)
+        }
+        (Message::HitPoint(HitPointMessage::Heal), Some(before), Some(after)) => {
+            format!("Healed {} ({} → {} HP)", after - before, before, after)
+        }
+        (Message::HitPoint(HitPointMessage::FullHealth), Some(before), Some(after)) => {
+            format!("Healed to full ({} → {} HP)", before, after)
+        }
+        (Message::Feature(FeatureMessage::Slot(path, command)), Some(before), Some(after)) => {
+            let verb = match command {
+                SlotCommand::Use => "Used",
+                SlotCommand::Reset => "Reset",
+            };
+            format!("{} {} ({} → {})", verb, name(path), before, after)
+        }
+        (
+            Message::Spellbook(SpellbookMessage::Cast(name, Some(slot))),
+            Some(before),
+            Some(after),
+        ) => {
+            format!(
+                "Cast {} with a {} slot ({} → {})",
+                name, slot, before, after
+            )
+        }
+        (Message::Feature(FeatureMessage::Activate(path)), _, _) => {
+            let name = name(path);
+            if state.features.active().contains(&name) {
+                format!("Activated {}", name)
+            } else {
+                format!("Deactivated {}", name)
+            }
+        }
+        (Message::Feature(FeatureMessage::Concentrate(path)), _, _) => {
+            format!("Concentrated on {}", name(path))
+        }
+        (Message::Feature(FeatureMessage::Convert(conversion)), _, _) => {
+            conversion.name().to_string()
+        }
+        (Message::Feature(FeatureMessage::RollTable(_, table)), _, _) => {
+            format!("Rolled on {}", table)
+        }
+        (Message::Rest(Recovery::ShortRest), _, _) => "Short Rest".to_string(),
+        (Message::Rest(Recovery::LongRest), _, _) => "Long Rest".to_string(),
+        (Message::Concentration(ConcentrationMessage::End), _, _) => {
+            "Ended concentration".to_string()
+        }
+        _ => return None,
+    };
+    Some(label)
+}
+
+/// Names a change `message` made to the character sheet, such as "Prepared Shield" or "Leveled
+/// up to level 4", from the character `before` and `after` it.
+pub fn describe(
+    state: &State,
+    message: &Message,
+    before: &CharacterPersistence,
+    after: &CharacterPersistence,
+) -> String {
+    match message {
+        Message::RollLog(RollLogMessage::Amend { modifier, .. }) => format!(
+            "Used {} on a roll",
+            state
+                .features
+                .feature_name(modifier)
+                .unwrap_or_else(|| modifier.breadcrumb())
+        ),
+        Message::Spellbook(SpellbookMessage::Learn) => {
+            match after
+                .spells()
+                .known()
+                .iter()
+                .find(|spell| !before.spells().knows(spell))
+            {
+                Some(spell) => format!("Learned {}", spell),
+                None => "Learned a spell".to_string(),
+            }
+        }
+        Message::Spellbook(SpellbookMessage::Forget(spell)) => format!("Forgot {}", spell),
+        Message::Spellbook(SpellbookMessage::TogglePrepared(spell)) => {
+            if after.spells().prepares(spell) {
+                format!("Prepared {}", spell)
+            } else {
+                format!("Unprepared {}", spell)
+            }
+        }
+        Message::Experience(ExperienceMessage::Award) => {
+            let (points_before, points_after) =
+                (before.experience().points(), after.experience().points());
+            format!(
+                "Awarded {} XP ({} → {})",
+                points_after - points_before,
+                points_before,
+                points_after
+            )
+        }
+        Message::Experience(ExperienceMessage::ReachMilestone) => "Reached a milestone".to_string(),
+        Message::Experience(ExperienceMessage::ToggleMilestones) => {
+            "Changed how the character levels".to_string()
+        }
+        Message::AbilityGeneration(_) => "Generated ability scores".to_string(),
+        Message::LevelUp(_) => format!("Leveled up to level {}", after.total_level()),
+        Message::TimedEffect(_) => "Ended a timed effect".to_string(),
+        Message::Concentration(_) => "Changed concentration".to_string(),
+        _ => "Changed the character".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::character::persistence::LoadData;
+    use crate::character::Character;
+    use crate::core::slot::Slot;
+    use crate::resources::Resources;
+    use iced::Application;
+
+    fn state(sheet: &Character) -> &State {
+        match sheet {
+            Character::Loaded(state) => state,
+            _ => panic!("The sheet should be loaded"),
+        }
+    }
+
+    #[test]
+    fn changes_are_undone_and_redone_with_labels() {
+        let mut character = serde_json::to_value(CharacterPersistence::default()).unwrap();
+        character["hit_points"] =
+            serde_json::json!({ "current_hit_points": 20, "max_hit_points": 20 });
+        character["features"] = serde_json::json!([{
+            "name": "Sorcery Points",
+            "slot": serde_json::to_value(Slot::new(5, Some(5))).unwrap(),
+        }]);
+        let character: CharacterPersistence = serde_json::from_value(character).unwrap();
+        let mut sheet =
+            Character::Loaded(LoadData::from(Resources::default(), character).to_state());
+        let points = FeaturePath::of(vec!["Sorcery Points".to_string()]);
+        let remaining = |sheet: &Character| {
+            let state = state(sheet);
+            (
+                state
+                    .features
+                    .find_slot(points.segments())
+                    .unwrap()
+                    .current(),
+                state.hit_points.current_hit_points(),
+            )
+        };
+
+        sheet.update(Message::Feature(FeatureMessage::Slot(
+            points.clone(),
+            SlotCommand::Use,
+        )));
+        sheet.update(Message::HitPoint(HitPointMessage::ChangeHealthDelta(
+            "7".to_string(),
+        )));
+        sheet.update(Message::HitPoint(HitPointMessage::Damage));
+        let labels: Vec<&String> = state(&sheet)
+            .history
+            .undo
+            .iter()
+            .map(|e| &e.label)
+            .collect();
+        assert_eq!(
+            labels,
+            vec!["Used Sorcery Points (5 → 4)", "Took 7 damage (20 → 13 HP)"]
+        );
+
+        sheet.update(Message::History(HistoryMessage::Undo));
+        sheet.update(Message::History(HistoryMessage::Undo));
+        assert_eq!(remaining(&sheet), (5, 20));
+        sheet.update(Message::History(HistoryMessage::Redo));
+        assert_eq!(remaining(&sheet), (4, 20));
+        assert_eq!(
+            state(&sheet).history.redo.last().map(|e| e.label.as_str()),
+            Some("Took 7 damage (20 → 13 HP)")
+        );
+
+        sheet.update(Message::Rest(Recovery::LongRest));
+        assert!(state(&sheet).history.redo.is_empty());
+        assert_eq!(remaining(&sheet), (5, 20));
+        sheet.update(Message::History(HistoryMessage::Undo));
+        assert_eq!(remaining(&sheet), (4, 20));
+
+        sheet.update(Message::Experience(ExperienceMessage::ChangeAward(
+            "300".to_string(),
+        )));
+        sheet.update(Message::Experience(ExperienceMessage::Award));
+        assert_eq!(
+            state(&sheet)
+                .history
+                .next_undo()
+                .map(|label| label.as_str()),
+            Some("Awarded 300 XP (0 → 300)")
+        );
+        sheet.update(Message::History(HistoryMessage::Undo));
+        sheet.update(Message::History(HistoryMessage::Undo));
+        assert_eq!(remaining(&sheet), (5, 20));
+    }
+}
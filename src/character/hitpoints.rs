@@ -34,6 +34,7 @@ pub enum HitPointMessage {
 #[derive(Debug, Clone, Default)]
 pub struct HitPointState {
     hit_points: HitPoints,
+    modified_max_hit_points: Option<isize>,
     hp_delta: isize,
     change_hp: iced::text_input::State,
     hp_controls: crate::character::hitpoints::HitPointControls,
@@ -48,6 +49,14 @@ impl HitPoints {
     }
 }
 
+impl HitPoints {
+    /// Raises the maximum, and current, hit points by `hit_points` as when gaining a level.
+    pub fn gain(&mut self, hit_points: isize) {
+        self.max_hit_points += hit_points;
+        self.current_hit_points += hit_points;
+    }
+}
+
 type IsDirty = bool;
 
 impl HitPointState {
@@ -55,6 +64,28 @@ impl HitPointState {
         self.hit_points.clone()
     }
 
+    pub fn current_hit_points(&self) -> isize {
+        self.hit_points.current_hit_points
+    }
+
+    pub fn max_hit_points(&self) -> isize {
+        self.hit_points.max_hit_points
+    }
+
+    /// The maximum after effects, which is what healing to full restores.
+    pub fn set_modified_max_hit_points(&mut self, max_hit_points: isize) {
+        self.modified_max_hit_points = Some(max_hit_points);
+    }
+
+    fn effective_max_hit_points(&self) -> isize {
+        self.modified_max_hit_points
+            .unwrap_or(self.hit_points.max_hit_points)
+    }
+
+    pub fn delta(&self) -> isize {
+        self.hp_delta
+    }
+
     pub fn update(&mut self, message: HitPointMessage) -> IsDirty {
         match message {
             HitPointMessage::IncrementHealthDelta => {
@@ -89,23 +120,25 @@ impl HitPointState {
                 true
             }
             HitPointMessage::FullHealth => {
-                self.hit_points.current_hit_points = self.hit_points.max_hit_points;
+                self.hit_points.current_hit_points = self.effective_max_hit_points();
                 true
             }
         }
     }
 
     pub fn view(&mut self) -> Column<Message> {
+        let max_hit_points = self.effective_max_hit_points();
         let HitPointState {
             hit_points,
             hp_delta,
             change_hp,
             hp_controls,
+            ..
         } = self;
 
         let hp = Text::new(format!(
             "{} / {} HP",
-            hit_points.current_hit_points, hit_points.max_hit_points
+            hit_points.current_hit_points, max_hit_points
         ))
         .width(Length::Fill)
         .size(60)
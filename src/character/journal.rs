@@ -0,0 +1,572 @@
+use crate::character::concentration::ConcentrationMessage;
+use crate::character::persistence::{CharacterPersistence, CharacterPersistenceConfig};
+use crate::character::Message;
+use crate::core::feature::FeatureMessage;
+use crate::core::feature_path::FeaturePath;
+use crate::store::{Store, StoreError};
+use iced::{button, pick_list, Align, Button, Column, Command, PickList, Row, Text};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+pub enum EventKind {
+    HitPoints,
+    Slot,
+    Feature,
+    Rest,
+    Roll,
+    Experience,
+    Gained,
+    Lost,
+    Undo,
+}
+
+impl Display for EventKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let kind = match self {
+            EventKind::HitPoints => "HP",
+            EventKind::Slot => "Slot",
+            EventKind::Feature => "Feature",
+            EventKind::Rest => "Rest",
+            EventKind::Roll => "Roll",
+            EventKind::Experience => "XP",
+            EventKind::Gained => "Gained",
+            EventKind::Lost => "Lost",
+            EventKind::Undo => "Undo",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+/// One line of a character's journal.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct JournalEvent {
+    /// Milliseconds since the epoch.
+    at: u64,
+    /// When the sheet was opened, which groups the events of a play session.
+    session: u64,
+    kind: EventKind,
+    text: String,
+}
+
+impl Display for JournalEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}  {}  {}", timestamp(self.at), self.kind, self.text)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum SessionFilter {
+    All,
+    Session(u64),
+}
+
+impl Display for SessionFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SessionFilter::All => write!(f, "All sessions"),
+            SessionFilter::Session(session) => write!(f, "Session of {}", timestamp(*session)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum DateFilter {
+    All,
+    Date(String),
+}
+
+impl Display for DateFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DateFilter::All => write!(f, "All dates"),
+            DateFilter::Date(date) => write!(f, "{}", date),
+        }
+    }
+}
+
+/// The events read from the journal, with the numbers of the lines that couldn't be read.
+#[derive(Debug, Clone, Default)]
+pub struct JournalRead {
+    events: Vec<JournalEvent>,
+    skipped: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub enum JournalMessage {
+    Show,
+    Loaded(Result<JournalRead, String>),
+    FilterSession(SessionFilter),
+    FilterDate(DateFilter),
+    Export,
+    Exported(Result<String, String>),
+    Written(Result<(), String>),
+    Hide,
+}
+
+/// The character's timeline of changes and rolls, kept as JSON lines under `journal/` and
+/// only ever added to.
+#[derive(Debug, Clone, Default)]
+pub struct JournalState {
+    session: u64,
+    unwritten: Vec<JournalEvent>,
+    writing: bool,
+    open: bool,
+    /// Every event, once the journal has been read.
+    events: Option<Vec<JournalEvent>>,
+    session_filter: SessionFilter,
+    date_filter: DateFilter,
+    notice: Option<String>,
+    session_list: pick_list::State<SessionFilter>,
+    date_list: pick_list::State<DateFilter>,
+    show_button: button::State,
+    export_button: button::State,
+    hide_button: button::State,
+}
+
+impl Default for SessionFilter {
+    fn default() -> SessionFilter {
+        SessionFilter::All
+    }
+}
+
+impl Default for DateFilter {
+    fn default() -> DateFilter {
+        DateFilter::All
+    }
+}
+
+impl JournalState {
+    /// Events recorded from now on belong to a new session.
+    pub fn start_session(&mut self) {
+        self.session = now();
+    }
+
+    pub fn record(&mut self, kind: EventKind, text: String) {
+        let event = JournalEvent {
+            at: now(),
+            session: self.session,
+            kind,
+            text,
+        };
+        if let Some(events) = self.events.as_mut() {
+            events.push(event.clone());
+        }
+        self.unwritten.push(event);
+    }
+
+    /// Whether everything recorded has been written, or failed to be.
+    pub fn is_written(&self) -> bool {
+        !self.writing && self.unwritten.is_empty()
+    }
+
+    /// Appends what was recorded since the last write. Only one write runs at a time, so the
+    /// lines stay in the order they happened.
+    pub fn write(&mut self, config: &CharacterPersistenceConfig) -> Command<Message> {
+        if self.writing || self.unwritten.is_empty() {
+            return Command::none();
+        }
+        self.writing = true;
+        let events = std::mem::take(&mut self.unwritten);
+        Command::perform(append(config.clone(), events), |written| {
+            Message::Journal(JournalMessage::Written(written))
+        })
+    }
+
+    pub fn update(
+        &mut self,
+        message: JournalMessage,
+        config: &CharacterPersistenceConfig,
+    ) -> Command<Message> {
+        match message {
+            JournalMessage::Show => {
+                self.open = true;
+                return Command::perform(load(config.clone()), |events| {
+                    Message::Journal(JournalMessage::Loaded(events))
+                });
+            }
+            JournalMessage::Loaded(Ok(read)) => {
+                let JournalRead {
+                    mut events,
+                    skipped,
+                } = read;
+                for event in self.unwritten.iter() {
+                    if !events.contains(event) {
+                        events.push(event.clone());
+                    }
+                }
+                self.events = Some(events);
+                self.notice = match skipped.as_slice() {
+                    [] => None,
+                    [line] => Some(format!(
+                        "Skipped line {} of the journal, which can't be read",
+                        line
+                    )),
+                    lines => Some(format!(
+                        "Skipped lines {} of the journal, which can't be read",
+                        lines
+                            .iter()
+                            .map(|line| line.to_string())
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    )),
+                };
+            }
+            JournalMessage::Loaded(Err(e)) => {
+                self.notice = Some(format!("Cannot read the journal: {}", e));
+            }
+            JournalMessage::FilterSession(filter) => self.session_filter = filter,
+            JournalMessage::FilterDate(filter) => self.date_filter = filter,
+            JournalMessage::Export => {
+                let recap = self
+                    .filtered()
+                    .iter()
+                    .map(|event| format!("{}\n", event))
+                    .collect::<String>();
+                return Command::perform(export(config.clone(), recap), |exported| {
+                    Message::Journal(JournalMessage::Exported(exported))
+                });
+            }
+            JournalMessage::Exported(Ok(path)) => {
+                self.notice = Some(format!("Exported to {}", path));
+            }
+            JournalMessage::Exported(Err(e)) => {
+                self.notice = Some(format!("Cannot export the journal {}", e));
+            }
+            JournalMessage::Written(written) => {
+                if let Err(e) = written {
+                    self.notice = Some(format!("Cannot write to the journal: {}", e));
+                }
+                self.writing = false;
+                return self.write(config);
+            }
+            JournalMessage::Hide => self.open = false,
+        }
+        Command::none()
+    }
+
+    fn filtered(&self) -> Vec<&JournalEvent> {
+        self.events
+            .iter()
+            .flatten()
+            .filter(|event| match &self.session_filter {
+                SessionFilter::All => true,
+                SessionFilter::Session(session) => event.session == *session,
+            })
+            .filter(|event| match &self.date_filter {
+                DateFilter::All => true,
+                DateFilter::Date(day) => date(event.at) == *day,
+            })
+            .collect()
+    }
+
+    pub fn view(&mut self) -> Column<'_, Message> {
+        if !self.open {
+            let mut column = Column::new().push(
+                Button::new(&mut self.show_button, Text::new("Journal").size(16))
+                    .on_press(Message::Journal(JournalMessage::Show))
+                    .padding(8),
+            );
+            if let Some(notice) = &self.notice {
+                column = column.push(Text::new(notice.clone()).size(16));
+            }
+            return column;
+        }
+
+        let lines: Vec<String> = self
+            .filtered()
+            .iter()
+            .map(|event| event.to_string())
+            .collect();
+        let mut sessions = vec![SessionFilter::All];
+        let mut dates = vec![DateFilter::All];
+        for event in self.events.iter().flatten() {
+            let session = SessionFilter::Session(event.session);
+            if !sessions.contains(&session) {
+                sessions.push(session);
+            }
+            let day = DateFilter::Date(date(event.at));
+            if !dates.contains(&day) {
+                dates.push(day);
+            }
+        }
+
+        let JournalState {
+            session_filter,
+            date_filter,
+            notice,
+            session_list,
+            date_list,
+            export_button,
+            hide_button,
+            ..
+        } = self;
+
+        let controls = Row::new()
+            .spacing(20)
+            .align_items(Align::Center)
+            .push(PickList::new(
+                session_list,
+                sessions,
+                Some(session_filter.clone()),
+                |filter| Message::Journal(JournalMessage::FilterSession(filter)),
+            ))
+            .push(PickList::new(
+                date_list,
+                dates,
+                Some(date_filter.clone()),
+                |filter| Message::Journal(JournalMessage::FilterDate(filter)),
+            ))
+            .push(
+                Button::new(export_button, Text::new("Export").size(16))
+                    .on_press(Message::Journal(JournalMessage::Export))
+                    .padding(8),
+            )
+            .push(
+                Button::new(hide_button, Text::new("Hide").size(16))
+                    .on_press(Message::Journal(JournalMessage::Hide))
+                    .padding(8),
+            );
+
+        let mut column = Column::new()
+            .spacing(2)
+            .push(Text::new("Journal").size(24))
+            .push(controls);
+        if let Some(notice) = notice {
+            column = column.push(Text::new(notice.clone()).size(16));
+        }
+        if lines.is_empty() {
+            column = column.push(Text::new("Nothing recorded").size(16));
+        }
+        for line in lines {
+            column = column.push(Text::new(line).size(16));
+        }
+        column
+    }
+}
+
+/// The kind of event an undoable change made by `message` is journaled as. Table rolls are
+/// journaled as the roll itself.
+pub fn kind(message: &Message) -> Option<EventKind> {
+    match message {
+        Message::HitPoint(_) => Some(EventKind::HitPoints),
+        Message::Feature(FeatureMessage::Slot(_, _)) => Some(EventKind::Slot),
+        Message::Feature(FeatureMessage::RollTable(_, _)) => None,
+        Message::Feature(_) | Message::Concentration(ConcentrationMessage::End) => {
+            Some(EventKind::Feature)
+        }
+        Message::Rest(_) => Some(EventKind::Rest),
+        Message::Spellbook(_) => Some(EventKind::Slot),
+        _ => None,
+    }
+}
+
+/// Experience awarded, and features or items gained and lost, from `before` to `after`.
+pub fn changes(
+    before: &CharacterPersistence,
+    after: &CharacterPersistence,
+) -> Vec<(EventKind, String)> {
+    let mut changes = vec![];
+    let (points_before, points_after) = (before.experience().points(), after.experience().points());
+    if points_after != points_before {
+        changes.push((
+            EventKind::Experience,
+            format!(
+                "Gained {} XP ({} → {})",
+                points_after - points_before,
+                points_before,
+                points_after
+            ),
+        ));
+    }
+    if after.experience().milestone_reached() && !before.experience().milestone_reached() {
+        changes.push((EventKind::Experience, "Reached a milestone".to_string()));
+    }
+
+    let (paths_before, paths_after) = (before.feature_paths(), after.feature_paths());
+    for path in outermost(&paths_after, &paths_before) {
+        changes.push((EventKind::Gained, path));
+    }
+    for path in outermost(&paths_before, &paths_after) {
+        changes.push((EventKind::Lost, path));
+    }
+    changes
+}
+
+/// The paths not among `others`, leaving out those under another such path.
+fn outermost(paths: &[FeaturePath], others: &[FeaturePath]) -> Vec<String> {
+    let new: Vec<&FeaturePath> = paths.iter().filter(|path| !others.contains(path)).collect();
+    new.iter()
+        .filter(|path| {
+            let segments = path.segments();
+            let parent = &segments[..segments.len().saturating_sub(1)];
+            !new.iter().any(|other| other.segments() == parent)
+        })
+        .map(|path| path.breadcrumb())
+        .collect()
+}
+
+fn key(config: &CharacterPersistenceConfig) -> String {
+    format!("journal/{}.jsonl", config.character_id())
+}
+
+/// Moves a character's journal along with it when its id changes.
+pub async fn rename(storage_root: String, from: String, to: String) -> Result<(), String> {
+    let from = CharacterPersistenceConfig::new(storage_root.clone(), from);
+    let to = CharacterPersistenceConfig::new(storage_root.clone(), to);
+    if !std::path::Path::new(&storage_root)
+        .join(key(&from))
+        .exists()
+    {
+        return Ok(());
+    }
+    store(&from)?
+        .rename(key(&from), key(&to))
+        .await
+        .map_err(|e| format!("{:?}", e))
+}
+
+fn store(config: &CharacterPersistenceConfig) -> Result<Store, String> {
+    Store::new(config.storage_root()).map_err(|e| format!("{:?}", e))
+}
+
+async fn append(
+    config: CharacterPersistenceConfig,
+    events: Vec<JournalEvent>,
+) -> Result<(), String> {
+    let mut lines = String::new();
+    for event in events {
+        lines += &serde_json::to_string(&event).map_err(|e| e.to_string())?;
+        lines += "\n";
+    }
+    store(&config)?
+        .append(key(&config), lines)
+        .await
+        .map_err(|e| format!("{:?}", e))
+}
+
+/// Every event in the journal. Lines that can't be read are skipped, so one bad line doesn't
+/// hide the rest of the timeline.
+async fn load(config: CharacterPersistenceConfig) -> Result<JournalRead, String> {
+    let content = match store(&config)?.load(key(&config)).await {
+        Ok(content) => content,
+        Err(StoreError::NotFound(_)) => return Ok(JournalRead::default()),
+        Err(e) => return Err(format!("{:?}", e)),
+    };
+    let mut read = JournalRead::default();
+    for (number, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(line) {
+            Ok(event) => read.events.push(event),
+            Err(_) => read.skipped.push(number + 1),
+        }
+    }
+    Ok(read)
+}
+
+/// Writes the recap to `exports/<character>-journal.txt`, returning where it went.
+async fn export(config: CharacterPersistenceConfig, recap: String) -> Result<String, String> {
+    let key = format!("exports/{}-journal.txt", config.character_id());
+    store(&config)?
+        .save_without_backup(key.clone(), recap)
+        .await
+        .map_err(|e| format!("{:?}", e))?;
+    Ok(format!(
+        "{}/{}",
+        config.storage_root().trim_end_matches('/'),
+        key
+    ))
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|time| time.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// The UTC day of `millis` since the epoch, as "YYYY-MM-DD".
+fn date(millis: u64) -> String {
+    // Days to a civil date, after Howard Hinnant's `civil_from_days`.
+    let days = (millis / 86_400_000) as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// `millis` since the epoch as "YYYY-MM-DD HH:MM UTC".
+fn timestamp(millis: u64) -> String {
+    let minutes = millis / 60_000 % (24 * 60);
+    format!(
+        "{} {:02}:{:02} UTC",
+        date(millis),
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::store::test::TestDirectory;
+
+    #[test]
+    fn timestamps_are_utc_dates_and_times() {
+        assert_eq!(timestamp(0), "1970-01-01 00:00 UTC");
+        assert_eq!(timestamp(1_700_000_000_000), "2023-11-14 22:13 UTC");
+        assert_eq!(date(951_782_400_000), "2000-02-29");
+    }
+
+    #[actix_rt::test]
+    async fn events_are_appended_and_filtered_by_session() {
+        let root = TestDirectory::new("journal-test");
+        let config = CharacterPersistenceConfig::new(root.0.clone(), "vynne".to_string());
+        let mut journal = JournalState::default();
+        journal.start_session();
+        journal.record(EventKind::Slot, "Used Tides of Chaos (1 → 0)".to_string());
+        let first = journal.session;
+        append(config.clone(), std::mem::take(&mut journal.unwritten))
+            .await
+            .unwrap();
+
+        journal.session = first + 1;
+        journal.record(EventKind::Rest, "Long Rest".to_string());
+        append(config.clone(), std::mem::take(&mut journal.unwritten))
+            .await
+            .unwrap();
+
+        store(&config)
+            .unwrap()
+            .append(key(&config), "{ not an event\n".to_string())
+            .await
+            .unwrap();
+
+        let read = load(config.clone()).await.unwrap();
+        assert_eq!(read.events.len(), 2);
+        assert_eq!(read.skipped, vec![3]);
+        journal.update(JournalMessage::Loaded(Ok(read)), &config);
+        assert_eq!(
+            journal.notice,
+            Some("Skipped line 3 of the journal, which can't be read".to_string())
+        );
+        journal.update(
+            JournalMessage::FilterSession(SessionFilter::Session(first)),
+            &config,
+        );
+        let texts: Vec<&String> = journal.filtered().iter().map(|e| &e.text).collect();
+        assert_eq!(texts, vec!["Used Tides of Chaos (1 → 0)"]);
+    }
+}
@@ -0,0 +1,580 @@
+use crate::character::journal;
+use crate::character::name::Name;
+use crate::character::new_character::character_id;
+use crate::character::persistence::{CharacterPersistence, CharacterPersistenceConfig};
+use crate::character::Message;
+use crate::migration::{self, ReadError};
+use crate::store::{ParseError, Store};
+use iced::{
+    button, scrollable, text_input, Align, Button, Column, Container, Element, Length, Row,
+    Scrollable, Text, TextInput,
+};
+use serde_json::Value;
+
+const CHARACTERS: &str = "characters";
+const ARCHIVE: &str = "characters/archive";
+
+/// A saved character as listed by the launcher.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CharacterSummary {
+    pub id: String,
+    pub name: String,
+    pub classes: String,
+    pub level: isize,
+    pub archived: bool,
+    /// Why the file can't be read, if it can't.
+    pub unreadable: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum LauncherMessage {
+    /// Leave the character sheet for the launcher.
+    Show,
+    Listed(Result<Vec<CharacterSummary>, String>),
+    Open(String),
+    NewCharacter,
+    Duplicate(String),
+    Rename(String),
+    ChangeName(String),
+    ConfirmRename,
+    Archive(String),
+    Unarchive(String),
+    Delete(String, bool),
+    ConfirmDelete,
+    Quarantine(String),
+    Cancel,
+    Done(Result<(), String>),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LauncherState {
+    storage_root: String,
+    characters: Vec<CharacterRow>,
+    error: Option<String>,
+    /// The id of the character being renamed, and its new name.
+    renaming: Option<(String, String)>,
+    /// The id of the character waiting for confirmation to be deleted, and whether it's archived.
+    deleting: Option<(String, bool)>,
+    rename_input: text_input::State,
+    new_button: button::State,
+    confirm_button: button::State,
+    cancel_button: button::State,
+    scroll: scrollable::State,
+}
+
+#[derive(Debug, Clone, Default)]
+struct CharacterRow {
+    summary: Option<CharacterSummary>,
+    open_button: button::State,
+    duplicate_button: button::State,
+    rename_button: button::State,
+    archive_button: button::State,
+    delete_button: button::State,
+}
+
+fn store(storage_root: &str) -> Result<Store, String> {
+    Store::new(storage_root.to_string()).map_err(|e| format!("{:?}", e))
+}
+
+fn archived_key(id: &str) -> String {
+    format!("{}/{}.json", ARCHIVE, id)
+}
+
+/// Reads the name and classes without loading the whole character, so that a character an
+/// older version saved is still listed.
+fn summarize(key: &str, content: &str, archived: bool) -> CharacterSummary {
+    let id = key
+        .rsplit('/')
+        .next()
+        .unwrap_or(key)
+        .trim_end_matches(".json")
+        .to_string();
+    let (json, unreadable) = match serde_json::from_str::<Value>(content) {
+        Ok(json) => (json, None),
+        Err(e) => (Value::Null, Some(ParseError::of(key, &e).to_string())),
+    };
+    let name = json["name"]["name"]
+        .as_str()
+        .filter(|name| !name.is_empty())
+        .unwrap_or(&id)
+        .to_string();
+    let classes = json["classes"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default()
+        .iter()
+        .map(|class| {
+            (
+                class["name"].as_str().unwrap_or_default().to_string(),
+                class["level"].as_i64().unwrap_or(0) as isize,
+            )
+        })
+        .collect::<Vec<(String, isize)>>();
+    CharacterSummary {
+        name,
+        classes: classes
+            .iter()
+            .map(|(name, level)| format!("{} {}", name, level))
+            .collect::<Vec<String>>()
+            .join(", "),
+        level: classes.iter().map(|(_, level)| level).sum(),
+        archived,
+        unreadable,
+        id,
+    }
+}
+
+/// Every saved character, followed by the archived ones.
+pub async fn list(storage_root: String) -> Result<Vec<CharacterSummary>, String> {
+    let store = store(&storage_root)?;
+    let mut characters = vec![];
+    for (prefix, archived) in vec![(CHARACTERS, false), (ARCHIVE, true)] {
+        let keys = store
+            .list(prefix.to_string())
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        for key in keys.into_iter().filter(|key| key.ends_with(".json")) {
+            let content = store.load(key.clone()).await.unwrap_or_default();
+            characters.push(summarize(&key, &content, archived));
+        }
+    }
+    Ok(characters)
+}
+
+/// Saves a copy of the character under a new id and name, leaving the original as it is.
+async fn copy(
+    storage_root: String,
+    id: String,
+    new_id: String,
+    name: String,
+) -> Result<(), String> {
+    let key = CharacterPersistence::key(id);
+    let content = store(&storage_root)?
+        .load(key.clone())
+        .await
+        .map_err(|e| format!("{:?}", e))?;
+    let read = migration::read::<CharacterPersistence>(&key, &content).map_err(|e| match e {
+        ReadError::Parse(e) => e.to_string(),
+        ReadError::Schema(e) => e.to_string(),
+    })?;
+    read.value
+        .with_identity(
+            CharacterPersistenceConfig::new(storage_root, new_id),
+            Name::of(name),
+        )
+        .save()
+        .await
+        .map_err(|e| format!("{:?}", e))
+}
+
+/// The id for a new character named `name`, numbered if another character already uses it.
+fn free_id(storage_root: &str, name: &str) -> String {
+    let id = character_id(name);
+    let taken = |id: &String| {
+        CharacterPersistenceConfig::new(storage_root.to_string(), id.clone()).exists()
+            || std::path::Path::new(storage_root)
+                .join(archived_key(id))
+                .exists()
+    };
+    let mut candidate = id.clone();
+    let mut number = 2;
+    while taken(&candidate) {
+        candidate = format!("{}-{}", id, number);
+        number += 1;
+    }
+    candidate
+}
+
+async fn duplicate(storage_root: String, id: String, name: String) -> Result<(), String> {
+    let name = format!("{} (copy)", name);
+    let new_id = free_id(&storage_root, &name);
+    copy(storage_root, id, new_id, name).await
+}
+
+/// Renames the character, moving it to the id of its new name.
+async fn rename(storage_root: String, id: String, name: String) -> Result<(), String> {
+    let name = name.trim().to_string();
+    let new_id = character_id(&name);
+    if new_id.is_empty() {
+        return Err("Name the character".to_string());
+    }
+    if new_id != id
+        && CharacterPersistenceConfig::new(storage_root.clone(), new_id.clone()).exists()
+    {
+        return Err(format!("There already is a character saved as {}", new_id));
+    }
+    copy(storage_root.clone(), id.clone(), new_id.clone(), name).await?;
+    if new_id != id {
+        store(&storage_root)?
+            .remove(CharacterPersistence::key(id.clone()))
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        journal::rename(storage_root, id, new_id).await?;
+    }
+    Ok(())
+}
+
+async fn archive(storage_root: String, id: String, archived: bool) -> Result<(), String> {
+    let (from, to) = if archived {
+        (CharacterPersistence::key(id.clone()), archived_key(&id))
+    } else {
+        (archived_key(&id), CharacterPersistence::key(id.clone()))
+    };
+    if std::path::Path::new(&storage_root).join(&to).exists() {
+        return Err(format!("There already is a character saved as {}", id));
+    }
+    store(&storage_root)?
+        .rename(from, to)
+        .await
+        .map_err(|e| format!("{:?}", e))
+}
+
+async fn quarantine(storage_root: String, id: String) -> Result<(), String> {
+    CharacterPersistence::quarantine(CharacterPersistenceConfig::new(storage_root, id))
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+async fn delete(storage_root: String, id: String, archived: bool) -> Result<(), String> {
+    let key = if archived {
+        archived_key(&id)
+    } else {
+        CharacterPersistence::key(id)
+    };
+    store(&storage_root)?
+        .remove(key)
+        .await
+        .map_err(|e| format!("{:?}", e))
+}
+
+impl LauncherState {
+    pub fn new(storage_root: String) -> LauncherState {
+        LauncherState {
+            storage_root,
+            ..LauncherState::default()
+        }
+    }
+
+    pub fn storage_root(&self) -> String {
+        self.storage_root.clone()
+    }
+
+    pub fn refresh(&self) -> iced::Command<Message> {
+        iced::Command::perform(list(self.storage_root.clone()), |characters| {
+            Message::Launcher(LauncherMessage::Listed(characters))
+        })
+    }
+
+    fn perform<F>(&self, operation: F) -> iced::Command<Message>
+    where
+        F: std::future::Future<Output = Result<(), String>> + 'static + Send,
+    {
+        iced::Command::perform(operation, |result| {
+            Message::Launcher(LauncherMessage::Done(result))
+        })
+    }
+
+    fn name_of(&self, id: &str) -> String {
+        self.characters
+            .iter()
+            .filter_map(|row| row.summary.as_ref())
+            .find(|summary| summary.id == id)
+            .map(|summary| summary.name.clone())
+            .unwrap_or_else(|| id.to_string())
+    }
+
+    /// Opening a character and creating one leave the launcher, so they're up to the caller.
+    pub fn update(&mut self, message: LauncherMessage) -> iced::Command<Message> {
+        let root = self.storage_root.clone();
+        match message {
+            LauncherMessage::Listed(Ok(characters)) => {
+                self.characters = characters
+                    .into_iter()
+                    .map(|summary| CharacterRow {
+                        summary: Some(summary),
+                        ..CharacterRow::default()
+                    })
+                    .collect();
+            }
+            LauncherMessage::Listed(Err(e)) | LauncherMessage::Done(Err(e)) => {
+                self.error = Some(e);
+            }
+            LauncherMessage::Done(Ok(())) => {
+                self.error = None;
+                return self.refresh();
+            }
+            LauncherMessage::Duplicate(id) => {
+                let name = self.name_of(&id);
+                return self.perform(duplicate(root, id, name));
+            }
+            LauncherMessage::Rename(id) => {
+                self.deleting = None;
+                self.renaming = Some((id.clone(), self.name_of(&id)));
+            }
+            LauncherMessage::ChangeName(name) => {
+                if let Some((_, new_name)) = self.renaming.as_mut() {
+                    *new_name = name;
+                }
+            }
+            LauncherMessage::ConfirmRename => {
+                if let Some((id, name)) = self.renaming.take() {
+                    return self.perform(rename(root, id, name));
+                }
+            }
+            LauncherMessage::Archive(id) => return self.perform(archive(root, id, true)),
+            LauncherMessage::Unarchive(id) => return self.perform(archive(root, id, false)),
+            LauncherMessage::Delete(id, archived) => {
+                self.renaming = None;
+                self.deleting = Some((id, archived));
+            }
+            LauncherMessage::ConfirmDelete => {
+                if let Some((id, archived)) = self.deleting.take() {
+                    return self.perform(delete(root, id, archived));
+                }
+            }
+            LauncherMessage::Quarantine(id) => return self.perform(quarantine(root, id)),
+            LauncherMessage::Cancel => {
+                self.renaming = None;
+                self.deleting = None;
+            }
+            LauncherMessage::Show | LauncherMessage::Open(_) | LauncherMessage::NewCharacter => {}
+        }
+        iced::Command::none()
+    }
+
+    pub fn view(&mut self) -> Element<'_, Message> {
+        let LauncherState {
+            storage_root: _,
+            characters,
+            error,
+            renaming,
+            deleting,
+            rename_input,
+            new_button,
+            confirm_button,
+            cancel_button,
+            scroll,
+        } = self;
+        let message = |message| Message::Launcher(message);
+        let small_button = |state, label: &str, on_press| {
+            Button::new(state, Text::new(label).size(12))
+                .on_press(message(on_press))
+                .padding(4)
+        };
+
+        let mut column = Column::new().spacing(12).push(
+            Row::new()
+                .spacing(20)
+                .align_items(Align::Center)
+                .push(Text::new("Characters").size(48).width(Length::Fill))
+                .push(
+                    Button::new(new_button, Text::new("New Character").size(16))
+                        .on_press(message(LauncherMessage::NewCharacter))
+                        .padding(8),
+                ),
+        );
+        if let Some(error) = error {
+            column = column.push(Text::new(error.clone()).size(16));
+        }
+        if characters.is_empty() {
+            column = column.push(Text::new("No saved characters").size(16));
+        }
+
+        let mut confirm = Some(confirm_button);
+        let mut cancel = Some(cancel_button);
+        let mut input = Some(rename_input);
+        let mut archived_header = false;
+        for row in characters.iter_mut() {
+            let summary = match &row.summary {
+                Some(summary) => summary.clone(),
+                None => continue,
+            };
+            if summary.archived && !archived_header {
+                archived_header = true;
+                column = column.push(Text::new("Archived").size(24));
+            }
+            let id = summary.id.clone();
+            let mut line = Row::new().spacing(12).align_items(Align::Center);
+
+            let is_renaming = renaming.as_ref().map(|(r, _)| r == &id).unwrap_or(false);
+            match (renaming.as_ref().filter(|_| is_renaming), input.take()) {
+                (Some((_, name)), Some(rename_input)) => {
+                    line = line.push(
+                        TextInput::new(rename_input, "Name", name.as_str(), move |name| {
+                            message(LauncherMessage::ChangeName(name))
+                        })
+                        .padding(4)
+                        .size(16)
+                        .width(Length::FillPortion(2))
+                        .on_submit(message(LauncherMessage::ConfirmRename)),
+                    );
+                }
+                (_, state) => {
+                    input = state;
+                    line = line.push(
+                        Text::new(summary.name.clone())
+                            .size(20)
+                            .width(Length::FillPortion(2)),
+                    );
+                }
+            }
+            line = line
+                .push(
+                    Text::new(
+                        summary
+                            .unreadable
+                            .clone()
+                            .unwrap_or(summary.classes.clone()),
+                    )
+                    .size(16)
+                    .width(Length::FillPortion(2)),
+                )
+                .push(
+                    Text::new(format!("Level {}", summary.level))
+                        .size(16)
+                        .width(Length::FillPortion(1)),
+                );
+
+            let is_deleting = deleting.as_ref().map(|(d, _)| d == &id).unwrap_or(false);
+            if is_renaming || is_deleting {
+                let (label, on_press) = if is_renaming {
+                    ("Save", LauncherMessage::ConfirmRename)
+                } else {
+                    ("Delete forever", LauncherMessage::ConfirmDelete)
+                };
+                if let (Some(confirm), Some(cancel)) = (confirm.take(), cancel.take()) {
+                    line = line
+                        .push(small_button(confirm, label, on_press))
+                        .push(small_button(cancel, "Cancel", LauncherMessage::Cancel));
+                }
+            } else if summary.unreadable.is_some() && !summary.archived {
+                line = line
+                    .push(small_button(
+                        &mut row.open_button,
+                        "Open",
+                        LauncherMessage::Open(id.clone()),
+                    ))
+                    .push(small_button(
+                        &mut row.archive_button,
+                        "Quarantine",
+                        LauncherMessage::Quarantine(id.clone()),
+                    ))
+                    .push(small_button(
+                        &mut row.delete_button,
+                        "Delete",
+                        LauncherMessage::Delete(id, false),
+                    ));
+            } else if summary.archived {
+                line = line
+                    .push(small_button(
+                        &mut row.archive_button,
+                        "Unarchive",
+                        LauncherMessage::Unarchive(id.clone()),
+                    ))
+                    .push(small_button(
+                        &mut row.delete_button,
+                        "Delete",
+                        LauncherMessage::Delete(id, true),
+                    ));
+            } else {
+                line = line
+                    .push(small_button(
+                        &mut row.open_button,
+                        "Open",
+                        LauncherMessage::Open(id.clone()),
+                    ))
+                    .push(small_button(
+                        &mut row.duplicate_button,
+                        "Duplicate",
+                        LauncherMessage::Duplicate(id.clone()),
+                    ))
+                    .push(small_button(
+                        &mut row.rename_button,
+                        "Rename",
+                        LauncherMessage::Rename(id.clone()),
+                    ))
+                    .push(small_button(
+                        &mut row.archive_button,
+                        "Archive",
+                        LauncherMessage::Archive(id.clone()),
+                    ))
+                    .push(small_button(
+                        &mut row.delete_button,
+                        "Delete",
+                        LauncherMessage::Delete(id, false),
+                    ));
+            }
+            column = column.push(line);
+        }
+
+        Scrollable::new(scroll)
+            .padding(40)
+            .push(Container::new(column).width(Length::Fill))
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::store::test::TestDirectory;
+
+    #[actix_rt::test]
+    async fn characters_are_duplicated_renamed_and_archived() {
+        let directory = TestDirectory::new("launcher-test");
+        let root = directory.0.clone();
+        let config = CharacterPersistenceConfig::new(root.clone(), "vynne".to_string());
+        CharacterPersistence::default()
+            .with_identity(config, Name::of("Vynne".to_string()))
+            .save()
+            .await
+            .unwrap();
+
+        duplicate(root.clone(), "vynne".to_string(), "Vynne".to_string())
+            .await
+            .unwrap();
+        rename(root.clone(), "vynne-copy".to_string(), "Nyx".to_string())
+            .await
+            .unwrap();
+        archive(root.clone(), "vynne".to_string(), true)
+            .await
+            .unwrap();
+
+        let listed = list(root.clone())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.id, c.name, c.archived))
+            .collect::<Vec<(String, String, bool)>>();
+        assert_eq!(
+            listed,
+            vec![
+                ("nyx".to_string(), "Nyx".to_string(), false),
+                ("vynne".to_string(), "Vynne".to_string(), true),
+            ]
+        );
+        let nyx = std::fs::read_to_string(format!("{}/characters/nyx.json", root)).unwrap();
+        assert!(nyx.contains("\"character_id\": \"nyx\""));
+    }
+
+    #[actix_rt::test]
+    async fn older_characters_are_upgraded_when_duplicated() {
+        let directory = TestDirectory::new("launcher-copy-test");
+        let root = directory.0.clone();
+        std::fs::create_dir_all(format!("{}/characters", root)).unwrap();
+        std::fs::copy(
+            "fixtures/schema-v0/characters/bashid.json",
+            format!("{}/characters/bashid.json", root),
+        )
+        .unwrap();
+
+        duplicate(root.clone(), "bashid".to_string(), "Bashid".to_string())
+            .await
+            .unwrap();
+        let copy =
+            std::fs::read_to_string(format!("{}/characters/bashid-copy.json", root)).unwrap();
+        assert!(copy.contains("show_reset_children"));
+        assert!(!copy.contains("show_reset_chidren"));
+    }
+}
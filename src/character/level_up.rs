@@ -0,0 +1,618 @@
+use crate::character::ability_improvement::AbilityImprovement;
+use crate::character::persistence::CharacterPersistence;
+use crate::character::Message;
+use crate::core::ability_score::Ability;
+use crate::resources::progression::ClassProgression;
+use crate::resources::template::Templates;
+use crate::resources::Resources;
+use iced::{button, pick_list, Align, Button, Column, Length, PickList, Row, Text};
+use rand::Rng;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum HitPointChoice {
+    Average,
+    Rolled(isize),
+}
+
+/// An ability score improvement raises two abilities by 1, or one ability by 2 when the same
+/// ability is picked twice.
+#[derive(Debug, Clone)]
+pub enum Improvement {
+    Abilities(Option<Ability>, Option<Ability>),
+    Feat(Option<String>),
+}
+
+impl Default for Improvement {
+    fn default() -> Self {
+        Improvement::Abilities(None, None)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LevelUpChoices {
+    class: String,
+    hit_points: Option<HitPointChoice>,
+    improvement: Improvement,
+    spells: Vec<Option<String>>,
+    subclass: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum LevelUpMessage {
+    SelectClass(String),
+    Start,
+    AverageHitPoints,
+    RollHitPoints,
+    ToggleFeat,
+    SelectAbility(usize, Ability),
+    SelectFeat(String),
+    SelectSpell(usize, String),
+    SelectSubclass(String),
+    Confirm,
+    Cancel,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LevelUpState {
+    class: Option<String>,
+    class_list: pick_list::State<String>,
+    start_button: button::State,
+    wizard: Option<Wizard>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Wizard {
+    choices: LevelUpChoices,
+    average_button: button::State,
+    roll_button: button::State,
+    feat_button: button::State,
+    ability_lists: [pick_list::State<Ability>; 2],
+    feat_list: pick_list::State<String>,
+    spell_lists: Vec<pick_list::State<String>>,
+    subclass_list: pick_list::State<String>,
+    /// Why the last confirmation was refused.
+    error: Option<String>,
+    confirm_button: button::State,
+    cancel_button: button::State,
+}
+
+/// A copy of the character with a level gained as chosen, or the choice still to be made.
+pub fn level_up(
+    character: &CharacterPersistence,
+    templates: &Templates,
+    choices: &LevelUpChoices,
+    constitution_modifier: isize,
+) -> Result<CharacterPersistence, String> {
+    let class = choices.class.as_str();
+    let progression = templates
+        .class(class)
+        .ok_or_else(|| format!("There is no progression for {}", class))?;
+
+    let mut leveled = character.clone();
+    let first_level = character.total_level() == 0;
+    let level = leveled.level_up_class(class, progression);
+
+    // The first character level always takes the most the hit die can give.
+    let hit_points = match (first_level, &choices.hit_points) {
+        (true, _) => progression.hit_die(),
+        (false, Some(HitPointChoice::Average)) => progression.average_hit_points(),
+        (false, Some(HitPointChoice::Rolled(roll))) => *roll,
+        (false, None) => return Err("Roll hit points or take the average".to_string()),
+    };
+    leveled.gain_hit_points(std::cmp::max(1, hit_points + constitution_modifier));
+
+    if progression.chooses_subclass_at(level) {
+        match &choices.subclass {
+            Some(subclass) => leveled.set_subclass(class, subclass.clone()),
+            None => return Err("Choose a subclass".to_string()),
+        }
+    }
+
+    if progression.improves_abilities_at(level) {
+        match &choices.improvement {
+            Improvement::Abilities(Some(first), Some(second)) => {
+                leveled.improve(AbilityImprovement::abilities(
+                    class.to_string(),
+                    level,
+                    vec![first.clone(), second.clone()],
+                ))
+            }
+            Improvement::Feat(Some(feat)) => leveled.improve(AbilityImprovement::feat(
+                class.to_string(),
+                level,
+                feat.clone(),
+            )),
+            Improvement::Abilities(_, _) => {
+                return Err("Choose two abilities to improve".to_string())
+            }
+            Improvement::Feat(None) => return Err("Choose a feat".to_string()),
+        }
+    }
+
+    leveled.apply_templates(templates);
+
+    let spells = progression.spells_at(level);
+    for index in 0..spells {
+        match choices.spells.get(index).cloned().flatten() {
+            Some(spell) if leveled.spells().knows(&spell) => {
+                return Err(format!("{} is already known", spell))
+            }
+            Some(spell) => leveled.learn_spell(spell),
+            None => return Err(format!("Choose {} new spells", spells)),
+        }
+    }
+
+    Ok(leveled)
+}
+
+/// What changed between two versions of a character, one line per changed value, such as
+/// `classes[0].level: 15 -> 16`.
+pub fn diff(before: &CharacterPersistence, after: &CharacterPersistence) -> Vec<String> {
+    let mut lines = vec![];
+    match (serde_json::to_value(before), serde_json::to_value(after)) {
+        (Ok(mut before), Ok(mut after)) => {
+            // The features templates grant are listed already; which ones they were isn't news.
+            for value in [&mut before, &mut after] {
+                if let Value::Object(fields) = value {
+                    fields.remove("granted");
+                }
+            }
+            diff_values("", &before, &after, &mut lines)
+        }
+        (Err(e), _) | (_, Err(e)) => lines.push(format!("Could not compare characters: {}", e)),
+    }
+    lines
+}
+
+fn diff_values(path: &str, before: &Value, after: &Value, lines: &mut Vec<String>) {
+    let child = |key: String| {
+        if path.is_empty() {
+            key
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+    match (before, after) {
+        (Value::Object(before), Value::Object(after)) => {
+            for (key, value) in after {
+                match before.get(key) {
+                    Some(old) => diff_values(&child(key.clone()), old, value, lines),
+                    None => lines.push(format!("{}: added {}", child(key.clone()), summary(value))),
+                }
+            }
+            for (key, value) in before {
+                if !after.contains_key(key) {
+                    lines.push(format!(
+                        "{}: removed {}",
+                        child(key.clone()),
+                        summary(value)
+                    ));
+                }
+            }
+        }
+        (Value::Array(before), Value::Array(after)) => {
+            for index in 0..std::cmp::max(before.len(), after.len()) {
+                let path = format!("{}[{}]", path, index);
+                match (before.get(index), after.get(index)) {
+                    (Some(old), Some(new)) => diff_values(&path, old, new, lines),
+                    (None, Some(new)) => lines.push(format!("{}: added {}", path, summary(new))),
+                    (Some(old), None) => lines.push(format!("{}: removed {}", path, summary(old))),
+                    (None, None) => {}
+                }
+            }
+        }
+        (before, after) if before != after => lines.push(format!(
+            "{}: {} -> {}",
+            path,
+            summary(before),
+            summary(after)
+        )),
+        _ => {}
+    }
+}
+
+/// Named objects, such as features, are summarized by their name and those of their children.
+fn summary(value: &Value) -> String {
+    let name = match value.get("name").and_then(|name| name.as_str()) {
+        Some(name) => name,
+        None => return value.to_string(),
+    };
+    let children = value
+        .get("children")
+        .and_then(|children| children.as_array())
+        .map(|children| children.iter().map(summary).collect::<Vec<String>>())
+        .unwrap_or_default();
+    if children.is_empty() {
+        format!("\"{}\"", name)
+    } else {
+        format!("\"{}\" with {}", name, children.join(", "))
+    }
+}
+
+impl LevelUpState {
+    /// Returns the leveled up character once the level up is confirmed.
+    pub fn update(
+        &mut self,
+        message: LevelUpMessage,
+        character: &CharacterPersistence,
+        resources: &Resources,
+        constitution_modifier: isize,
+    ) -> Option<CharacterPersistence> {
+        match message {
+            LevelUpMessage::SelectClass(class) => {
+                self.class = Some(class);
+                None
+            }
+            LevelUpMessage::Start => {
+                if let Some(class) = self.selected_class(character, resources) {
+                    self.wizard = Some(Wizard {
+                        choices: LevelUpChoices {
+                            class,
+                            ..LevelUpChoices::default()
+                        },
+                        ..Wizard::default()
+                    });
+                }
+                None
+            }
+            LevelUpMessage::Cancel => {
+                self.wizard = None;
+                None
+            }
+            LevelUpMessage::Confirm => {
+                let wizard = self.wizard.as_mut()?;
+                match level_up(
+                    character,
+                    resources.templates(),
+                    &wizard.choices,
+                    constitution_modifier,
+                ) {
+                    Ok(leveled) => {
+                        self.wizard = None;
+                        Some(leveled)
+                    }
+                    Err(e) => {
+                        wizard.error = Some(format!("Cannot level up yet: {}", e));
+                        None
+                    }
+                }
+            }
+            message => {
+                let wizard = self.wizard.as_mut()?;
+                wizard.error = None;
+                let choices = &mut wizard.choices;
+                match message {
+                    LevelUpMessage::AverageHitPoints => {
+                        choices.hit_points = Some(HitPointChoice::Average)
+                    }
+                    LevelUpMessage::RollHitPoints => {
+                        let hit_die = resources.templates().class(&choices.class)?.hit_die();
+                        let roll = rand::thread_rng().gen_range(1, hit_die + 1);
+                        choices.hit_points = Some(HitPointChoice::Rolled(roll));
+                    }
+                    LevelUpMessage::ToggleFeat => {
+                        choices.improvement = match choices.improvement {
+                            Improvement::Abilities(_, _) => Improvement::Feat(None),
+                            Improvement::Feat(_) => Improvement::default(),
+                        }
+                    }
+                    LevelUpMessage::SelectAbility(index, ability) => {
+                        if let Improvement::Abilities(first, second) = &mut choices.improvement {
+                            match index {
+                                0 => *first = Some(ability),
+                                _ => *second = Some(ability),
+                            }
+                        }
+                    }
+                    LevelUpMessage::SelectFeat(feat) => {
+                        choices.improvement = Improvement::Feat(Some(feat))
+                    }
+                    LevelUpMessage::SelectSpell(index, spell) => {
+                        if choices.spells.len() <= index {
+                            choices.spells.resize(index + 1, None);
+                        }
+                        choices.spells[index] = Some(spell);
+                    }
+                    LevelUpMessage::SelectSubclass(subclass) => choices.subclass = Some(subclass),
+                    _ => {}
+                }
+                None
+            }
+        }
+    }
+
+    /// The class picked to level up in, or the character's first class with a progression.
+    fn selected_class(
+        &self,
+        character: &CharacterPersistence,
+        resources: &Resources,
+    ) -> Option<String> {
+        let class_names = resources.templates().class_names();
+        self.class.clone().or_else(|| {
+            class_names
+                .iter()
+                .find(|name| character.class(name).is_some())
+                .or_else(|| class_names.first())
+                .cloned()
+        })
+    }
+
+    pub fn view(
+        &mut self,
+        character: &CharacterPersistence,
+        resources: &Resources,
+        constitution_modifier: isize,
+    ) -> Column<Message> {
+        let selected_class = self.selected_class(character, resources);
+        let LevelUpState {
+            class: _,
+            class_list,
+            start_button,
+            wizard,
+        } = self;
+
+        let wizard = match wizard {
+            Some(wizard) => wizard,
+            None => {
+                let class_names = resources.templates().class_names();
+                if class_names.is_empty() {
+                    return Column::new();
+                }
+                return Column::new().push(
+                    Row::new()
+                        .spacing(20)
+                        .align_items(Align::Center)
+                        .push(PickList::new(
+                            class_list,
+                            class_names,
+                            selected_class,
+                            |class| Message::LevelUp(LevelUpMessage::SelectClass(class)),
+                        ))
+                        .push(
+                            Button::new(start_button, Text::new("Level Up").size(16))
+                                .on_press(Message::LevelUp(LevelUpMessage::Start))
+                                .padding(8),
+                        ),
+                );
+            }
+        };
+        let preview = level_up(
+            character,
+            resources.templates(),
+            &wizard.choices,
+            constitution_modifier,
+        );
+        let Wizard {
+            choices,
+            average_button,
+            roll_button,
+            feat_button,
+            ability_lists,
+            feat_list,
+            spell_lists,
+            subclass_list,
+            error,
+            confirm_button,
+            cancel_button,
+        } = wizard;
+
+        let progression = match resources.templates().class(&choices.class) {
+            Some(progression) => progression,
+            None => return Column::new(),
+        };
+        let current = character.class(&choices.class);
+        let level = current.map(|class| class.level()).unwrap_or(0) + 1;
+        let subclass = choices
+            .subclass
+            .clone()
+            .or_else(|| current.and_then(|class| class.subclass().map(|s| s.to_string())));
+
+        let mut column = Column::new()
+            .spacing(8)
+            .push(Text::new(format!("Level {} {}", level, choices.class)).size(24));
+
+        let features = progression.features_at(level, subclass.as_deref());
+        if !features.is_empty() {
+            column = column.push(Text::new(format!("Gains {}", features.join(", "))).size(16));
+        }
+
+        if character.total_level() > 0 {
+            column = column.push(hit_point_row(
+                progression,
+                &choices.hit_points,
+                average_button,
+                roll_button,
+            ));
+        }
+
+        if progression.chooses_subclass_at(level) {
+            column = column.push(
+                Row::new()
+                    .spacing(20)
+                    .align_items(Align::Center)
+                    .push(Text::new("Subclass").size(16))
+                    .push(PickList::new(
+                        subclass_list,
+                        progression.subclasses(),
+                        choices.subclass.clone(),
+                        |subclass| Message::LevelUp(LevelUpMessage::SelectSubclass(subclass)),
+                    )),
+            );
+        }
+
+        if progression.improves_abilities_at(level) {
+            let mut row = Row::new().spacing(20).align_items(Align::Center);
+            let toggle = match &choices.improvement {
+                Improvement::Abilities(first, second) => {
+                    row = row.push(Text::new("Improve").size(16));
+                    let [first_list, second_list] = ability_lists;
+                    for (index, (list, selected)) in
+                        vec![(first_list, first), (second_list, second)]
+                            .into_iter()
+                            .enumerate()
+                    {
+                        row = row.push(PickList::new(
+                            list,
+                            Ability::all(),
+                            selected.clone(),
+                            move |ability| {
+                                Message::LevelUp(LevelUpMessage::SelectAbility(index, ability))
+                            },
+                        ));
+                    }
+                    "Take a feat"
+                }
+                Improvement::Feat(feat) => {
+                    row = row.push(Text::new("Feat").size(16)).push(PickList::new(
+                        feat_list,
+                        resources.templates().feat_names(),
+                        feat.clone(),
+                        |feat| Message::LevelUp(LevelUpMessage::SelectFeat(feat)),
+                    ));
+                    "Improve abilities"
+                }
+            };
+            column = column.push(
+                row.push(
+                    Button::new(feat_button, Text::new(toggle).size(16))
+                        .on_press(Message::LevelUp(LevelUpMessage::ToggleFeat))
+                        .padding(8),
+                ),
+            );
+        }
+
+        let spells = progression.spells_at(level);
+        if spells > 0 {
+            let options = resources
+                .spells()
+                .names()
+                .into_iter()
+                .filter(|name| {
+                    !character.spells().knows(name)
+                        && resources
+                            .spells()
+                            .get(name)
+                            .map(|spell| spell.on_list_of(&choices.class))
+                            .unwrap_or(false)
+                })
+                .collect::<Vec<String>>();
+            spell_lists.resize_with(spells, pick_list::State::default);
+            let mut row = Row::new()
+                .spacing(20)
+                .align_items(Align::Center)
+                .push(Text::new("New spells").size(16));
+            for (index, list) in spell_lists.iter_mut().enumerate() {
+                row = row.push(PickList::new(
+                    list,
+                    options.clone(),
+                    choices.spells.get(index).cloned().flatten(),
+                    move |spell| Message::LevelUp(LevelUpMessage::SelectSpell(index, spell)),
+                ));
+            }
+            column = column.push(row);
+        }
+
+        let mut confirm = Button::new(confirm_button, Text::new("Confirm").size(16)).padding(8);
+        match preview {
+            Ok(leveled) => {
+                column = column.push(Text::new("Changes").size(16));
+                for line in diff(character, &leveled) {
+                    column = column.push(Text::new(line).size(12));
+                }
+                confirm = confirm.on_press(Message::LevelUp(LevelUpMessage::Confirm));
+            }
+            Err(e) => column = column.push(Text::new(e).size(16)),
+        }
+        if let Some(error) = error {
+            column = column.push(Text::new(error.as_str()).size(16));
+        }
+
+        column.push(
+            Row::new().spacing(20).push(confirm).push(
+                Button::new(cancel_button, Text::new("Cancel").size(16))
+                    .on_press(Message::LevelUp(LevelUpMessage::Cancel))
+                    .padding(8),
+            ),
+        )
+    }
+}
+
+fn hit_point_row<'a>(
+    progression: &ClassProgression,
+    choice: &Option<HitPointChoice>,
+    average_button: &'a mut button::State,
+    roll_button: &'a mut button::State,
+) -> Row<'a, Message> {
+    let chosen = match choice {
+        Some(HitPointChoice::Average) => format!("{}", progression.average_hit_points()),
+        Some(HitPointChoice::Rolled(roll)) => format!("Rolled {}", roll),
+        None => "".to_string(),
+    };
+    Row::new()
+        .spacing(20)
+        .align_items(Align::Center)
+        .push(Text::new("Hit points").size(16))
+        .push(
+            Button::new(
+                average_button,
+                Text::new(format!("Average ({})", progression.average_hit_points())).size(16),
+            )
+            .on_press(Message::LevelUp(LevelUpMessage::AverageHitPoints))
+            .padding(8),
+        )
+        .push(
+            Button::new(
+                roll_button,
+                Text::new(format!("Roll d{}", progression.hit_die())).size(16),
+            )
+            .on_press(Message::LevelUp(LevelUpMessage::RollHitPoints))
+            .padding(8),
+        )
+        .push(Text::new(chosen).size(16).width(Length::Shrink))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn level_ups_apply_choices_and_list_the_changes() {
+        let templates: Templates = serde_json::from_str(
+            r#"{ "classes": { "Sorcerer": { "hit_die": 6, "asi_levels": [4],
+                "levels": [{ "level": 4, "features": ["Metamagic"], "spells": 1 }] } } }"#,
+        )
+        .unwrap();
+        let mut character = CharacterPersistence::default();
+        for _ in 0..3 {
+            character.level_up_class("Sorcerer", templates.class("Sorcerer").unwrap());
+        }
+        character.gain_hit_points(20);
+
+        let mut choices = LevelUpChoices {
+            class: "Sorcerer".to_string(),
+            hit_points: Some(HitPointChoice::Average),
+            ..LevelUpChoices::default()
+        };
+        assert_eq!(
+            level_up(&character, &templates, &choices, 2).err(),
+            Some("Choose two abilities to improve".to_string())
+        );
+
+        choices.improvement =
+            Improvement::Abilities(Some(Ability::Charisma), Some(Ability::Charisma));
+        choices.spells = vec![Some("Fireball".to_string())];
+        let leveled = level_up(&character, &templates, &choices, 2).unwrap();
+        assert_eq!(
+            diff(&character, &leveled),
+            vec![
+                "classes[0].level: 3 -> 4",
+                "features[0]: added \"Sorcerer\" with \"Metamagic\"",
+                "hit_points.current_hit_points: 20 -> 26",
+                "hit_points.max_hit_points: 20 -> 26",
+                "improvements[0]: added {\"abilities\":[\"Charisma\",\"Charisma\"],\"class\":\"Sorcerer\",\"feat\":null,\"level\":4}",
+                "spells.known[0]: added \"Fireball\"",
+            ]
+        );
+    }
+}
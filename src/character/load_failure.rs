@@ -0,0 +1,68 @@
+use crate::character::launcher::LauncherMessage;
+use crate::character::persistence::{CharacterPersistenceConfig, LoadError};
+use crate::character::Message;
+use iced::{button, Button, Column, Container, Element, Length, Row, Text};
+
+/// Shown instead of a character that can't be opened, so that nothing is saved over it.
+#[derive(Debug, Clone)]
+pub struct LoadFailureState {
+    config: CharacterPersistenceConfig,
+    error: LoadError,
+    back_button: button::State,
+    quarantine_button: button::State,
+}
+
+impl LoadFailureState {
+    pub fn new(config: CharacterPersistenceConfig, error: LoadError) -> LoadFailureState {
+        LoadFailureState {
+            config,
+            error,
+            back_button: button::State::default(),
+            quarantine_button: button::State::default(),
+        }
+    }
+
+    pub fn storage_root(&self) -> String {
+        self.config.storage_root()
+    }
+
+    pub fn view(&mut self) -> Element<'_, Message> {
+        let LoadFailureState {
+            config,
+            error,
+            back_button,
+            quarantine_button,
+        } = self;
+
+        let mut column = Column::new()
+            .spacing(12)
+            .push(Text::new(format!("Can't open {}", config.character_id())).size(48))
+            .push(Text::new(error.to_string()).size(16));
+        let mut buttons = Row::new().spacing(20).push(
+            Button::new(back_button, Text::new("Back to characters").size(16))
+                .on_press(Message::Launcher(LauncherMessage::Show))
+                .padding(8),
+        );
+        if let LoadError::Parse(_) = error {
+            column = column.push(
+                Text::new(
+                    "The file is left as it is. Fix it by hand, or quarantine it to move it out \
+                     of the characters folder.",
+                )
+                .size(16),
+            );
+            buttons = buttons.push(
+                Button::new(quarantine_button, Text::new("Quarantine").size(16))
+                    .on_press(Message::Launcher(LauncherMessage::Quarantine(
+                        config.character_id(),
+                    )))
+                    .padding(8),
+            );
+        }
+
+        Container::new(column.push(buttons))
+            .padding(40)
+            .width(Length::Fill)
+            .into()
+    }
+}
@@ -1,33 +1,77 @@
 use iced::{
-    scrollable, Align, Application, Column, Command, Container, Element, HorizontalAlignment,
-    Length, Row, Scrollable, Text,
+    button, scrollable, Align, Application, Button, Column, Command, Container, Element,
+    HorizontalAlignment, Length, Row, Scrollable, Subscription, Text,
 };
 
-use class::Classes;
+use ability_generation::{AbilityGenerationMessage, AbilityGenerationState};
+use ability_improvement::{taken_feats, AbilityImprovement};
+use class::{Classes, Preparation};
+use concentration::{ConcentrationMessage, ConcentrationState};
 use description::Description;
-use hitpoints::HitPointState;
+use experience::{ExperienceMessage, ExperienceState};
+use history::{History, HistoryMessage};
+use hitpoints::{HitPointMessage, HitPointState};
+use journal::{EventKind, JournalMessage, JournalState};
+use launcher::{LauncherMessage, LauncherState};
+use level_up::{LevelUpMessage, LevelUpState};
+use load_failure::LoadFailureState;
 use name::Name;
-use persistence::{CharacterPersistence, CharacterPersistenceConfig, LoadError};
+use new_character::{NewCharacterMessage, NewCharacterState, Outcome};
+use persistence::{CharacterPersistence, CharacterPersistenceConfig, LoadError, TemplateGrants};
 use proficiencies::Proficiencies;
+use rest::RestControls;
+use spell_slots::CastingSlot;
+use spellbook::{SpellbookMessage, SpellbookState};
+use spellcasting::Spellcasting;
+use timed_effects::{TimedEffect, TimedEffectMessage, TimedEffectsState};
+use versions::{VersionsMessage, VersionsState};
 
 use crate::character::persistence::LoadData;
-use crate::core::ability_score::AbilityScoresState;
+use crate::core::ability_score::{Ability, AbilityScoreMessage, AbilityScoresState};
+use crate::core::effect::{ActiveEffect, Effect, SpellBonus};
 use crate::core::feature::{FeatureMessage, FeaturesState};
 use crate::core::feature_path::FeaturePath;
+use crate::core::formula::FormulaContext;
+use crate::core::provenance::{Amount, Contribution, Source};
+use crate::core::roll::log::{Amendment, RollLog, RollLogEntry, RollLogMessage};
+use crate::core::roll::rollable::Rollable;
+use crate::core::roll::Dice;
+use crate::core::slot::{Recovery, SlotAmount, SlotCommand};
+use crate::core::stat::{ModifiedValue, Movement, Stat, StatMessage, StatsState, ValueBonus};
 use crate::resources::Resources;
+use std::collections::HashSet;
 
+pub mod ability_generation;
+pub mod ability_improvement;
 pub mod class;
+pub mod concentration;
 pub mod description;
+pub mod experience;
+pub mod history;
 pub mod hitpoints;
+pub mod journal;
+pub mod launcher;
+pub mod level_up;
+pub mod load_failure;
 pub mod name;
+pub mod new_character;
 pub mod persistence;
 pub mod proficiencies;
-//TODO experience, ac, attack
+pub mod rest;
+pub mod spell_slots;
+pub mod spellbook;
+pub mod spellcasting;
+pub mod timed_effects;
+pub mod versions;
+//TODO ac, attack
 
 #[derive(Debug)]
 pub enum Character {
     Loading(CharacterPersistenceConfig),
     Loaded(State),
+    Creating(NewCharacterState),
+    Launching(LauncherState),
+    Failed(LoadFailureState),
 }
 
 #[derive(Debug, Clone, Default)]
@@ -37,10 +81,26 @@ pub struct State {
     name: Name,
     description: Description,
     ability_scores: AbilityScoresState,
+    ability_generation: AbilityGenerationState,
     classes: Classes,
     hit_points: HitPointState,
     proficiencies: Proficiencies,
     features: FeaturesState,
+    spellbook: SpellbookState,
+    stats: StatsState,
+    concentration: ConcentrationState,
+    timed_effects: TimedEffectsState,
+    experience: ExperienceState,
+    feats: Vec<String>,
+    improvements: Vec<AbilityImprovement>,
+    template_grants: TemplateGrants,
+    level_up: LevelUpState,
+    roll_log: RollLog,
+    rest_controls: RestControls,
+    history: History,
+    journal: JournalState,
+    versions: VersionsState,
+    characters_button: button::State,
     saving: bool,
     dirty: bool,
     scroll: scrollable::State,
@@ -48,57 +108,742 @@ pub struct State {
 
 impl State {
     fn persistable(&self) -> CharacterPersistence {
-        CharacterPersistence::from(
-            self.name.clone(),
-            self.description.clone(),
-            self.ability_scores.persistable(),
-            self.classes.persistable(),
-            self.hit_points.persistable(),
-            self.proficiencies.clone(),
-            self.features.persistable(),
-            self.config.clone(),
-        )
+        CharacterPersistence::of(self)
+    }
+
+    /// Replaces the character with another version of it, such as after leveling up or undoing
+    /// a change, keeping the roll log, history and journal.
+    fn replace_character(&mut self, character: CharacterPersistence) {
+        let roll_log = std::mem::take(&mut self.roll_log);
+        let history = std::mem::take(&mut self.history);
+        let journal = std::mem::take(&mut self.journal);
+        let saving = self.saving;
+        *self = LoadData::from(self.resources.clone(), character).to_state();
+        self.roll_log = roll_log;
+        self.history = history;
+        self.journal = journal;
+        self.saving = saving;
+        self.reset_effects();
+        self.dirty = true;
+    }
+
+    /// Journals what a message did besides undoable changes: undoing and redoing, rolls,
+    /// experience, and features or items gained and lost.
+    fn record_in_journal(
+        &mut self,
+        before: CharacterPersistence,
+        changed: bool,
+        undone: Option<String>,
+        amended: Option<usize>,
+        rolls: usize,
+    ) {
+        match undone {
+            Some(label) if changed => self.journal.record(EventKind::Undo, label),
+            None if changed => {
+                for (kind, text) in journal::changes(&before, &self.persistable()) {
+                    self.journal.record(kind, text);
+                }
+            }
+            _ => {}
+        }
+        for entry in rolls..self.roll_log.count() {
+            if let Some(text) = self.roll_log.text_of(entry) {
+                self.journal.record(EventKind::Roll, text);
+            }
+        }
+        if let (Some(entry), true) = (amended, changed) {
+            if let Some(text) = self.roll_log.text_of(entry) {
+                self.journal
+                    .record(EventKind::Roll, format!("Amended {}", text));
+            }
+        }
+    }
+
+    /// Whether changes are still waiting to be saved or written to the journal, so leaving the
+    /// character now would lose them.
+    fn is_busy(&self) -> bool {
+        self.dirty || self.saving || !self.journal.is_written()
+    }
+
+    fn constitution_modifier(&self) -> isize {
+        self.ability_scores
+            .modified()
+            .get(Ability::Constitution)
+            .score()
+            .modifier()
+    }
+
+    fn preparation(&self) -> Vec<Preparation> {
+        self.classes.preparation(&self.ability_scores.modified())
+    }
+
+    /// Rebuilds the generated "Spells" feature from the spellbook and the spell catalog.
+    fn refresh_spells(&mut self) {
+        let spells = self.resources.spells();
+        match self
+            .spellbook
+            .feature(spells, &self.preparation(), &self.spellcasting())
+        {
+            Some(feature) => self.features.set_generated(
+                spellbook::SPELLS_KEY,
+                feature,
+                self.resources.templates().features(),
+            ),
+            None => self.features.remove_generated(spellbook::SPELLS_KEY),
+        }
+    }
+
+    fn reset_effects(&mut self) {
+        let mut active_effects = self.features.effects(self.concentration.concentrating_on());
+        active_effects.extend(self.timed_effects.active_effects());
+        active_effects.extend(self.granted_effects());
+
+        self.ability_scores.apply_all(&active_effects);
+
+        let ability_scores = self.ability_scores.modified();
+        self.stats.apply_all(
+            &active_effects,
+            &FormulaContext::new(&ability_scores, &self.classes),
+        );
+        // Spells are cast with the spell attack and save DC, which effects can change.
+        self.refresh_spells();
+        self.features.apply_effects(&active_effects);
+        self.features
+            .evaluate_formulas(&FormulaContext::new(&ability_scores, &self.classes));
+        self.dirty = self.derive_spell_slots() || self.dirty;
+
+        let max_hit_points = self.stat(&Stat::MaxHitPoints);
+        self.hit_points
+            .set_modified_max_hit_points(max_hit_points.value());
+    }
+
+    /// Effects granted by the character's templates, such as its race, and by its ability
+    /// score improvements. Template effects come from "Kind › Name", such as "Race › Half-Elf".
+    fn granted_effects(&self) -> Vec<ActiveEffect> {
+        let mut effects = self
+            .resources
+            .templates()
+            .granted(
+                &self.classes.persistable(),
+                self.description.race(),
+                self.description.background(),
+                &taken_feats(&self.feats, &self.improvements),
+            )
+            .into_iter()
+            .flat_map(|granted| {
+                let source = FeaturePath::of(vec![granted.kind.to_string(), granted.name.clone()]);
+                granted
+                    .grants
+                    .ability_bonuses()
+                    .iter()
+                    .map(|bonus| Effect::Ability {
+                        bonus: ValueBonus::Modifier {
+                            modifier: bonus.bonus,
+                        },
+                        ability: bonus.ability.clone(),
+                        name: None,
+                    })
+                    .chain(granted.grants.effects().iter().cloned())
+                    .map(|effect| ActiveEffect::new(effect, source.clone()))
+                    .collect::<Vec<ActiveEffect>>()
+            })
+            .collect::<Vec<ActiveEffect>>();
+        for improvement in &self.improvements {
+            effects.extend(improvement.effects());
+        }
+        effects
+    }
+
+    /// Sets spell slot maxima from class levels, adding slot features the character is missing.
+    fn derive_spell_slots(&mut self) -> IsDirty {
+        let mut dirty = false;
+        for (index, max) in spell_slots::spell_slots(&self.classes)
+            .into_iter()
+            .enumerate()
+        {
+            dirty = self.features.derive_slot(
+                &spell_slots::spell_slot_path(index + 1),
+                max,
+                Recovery::LongRest,
+                None,
+            ) || dirty;
+        }
+
+        let (max, description) = match spell_slots::pact_slots(&self.classes) {
+            Some(pact_slots) => (
+                pact_slots.count,
+                Some(format!("Level {} slots", pact_slots.level)),
+            ),
+            None => (0, None),
+        };
+        self.features.derive_slot(
+            &spell_slots::pact_slot_path(),
+            max,
+            Recovery::ShortRest,
+            description,
+        ) || dirty
+    }
+
+    /// Spell and pact slots the character has, lowest level first.
+    fn casting_slots(&self) -> Vec<CastingSlot> {
+        let mut slots = (1..=9)
+            .map(CastingSlot::Spell)
+            .filter(|slot| {
+                self.features
+                    .find_slot(&slot.path())
+                    .and_then(|slot| slot.max())
+                    .map(|max| max > 0)
+                    .unwrap_or(false)
+            })
+            .collect::<Vec<CastingSlot>>();
+        if let Some(pact_slots) = spell_slots::pact_slots(&self.classes) {
+            slots.push(CastingSlot::Pact(pact_slots.level));
+        }
+        slots.sort_by_key(|slot| slot.level());
+        slots
+    }
+
+    /// Casts a known spell, using the slot and rolling its attack, (upcast) damage and the
+    /// target's save with any activated features, such as metamagic, which are then used up.
+    /// Refused when the slot is too low, none are left, or the features' costs by spell level
+    /// can't be paid.
+    fn cast(&mut self, name: String, slot: Option<CastingSlot>) -> IsDirty {
+        let spell = match self.resources.spells().get(&name) {
+            Some(spell) => spell.clone(),
+            None => return false,
+        };
+        let slot_level = match (&slot, spell.level()) {
+            (_, 0) => 0,
+            (None, level) => {
+                self.spellbook.notify(Some(format!(
+                    "{} needs a level {} slot to cast",
+                    name, level
+                )));
+                return false;
+            }
+            (Some(slot), level) if slot.level() < level => {
+                self.spellbook
+                    .notify(Some(format!("{} can't be cast with a {} slot", name, slot)));
+                return false;
+            }
+            (Some(slot), _) => slot.level(),
+        };
+
+        let slot = slot.filter(|_| spell.level() > 0);
+        if let Some(slot) = &slot {
+            let remaining = self
+                .features
+                .find_slot(&slot.path())
+                .map(|slot| slot.current())
+                .unwrap_or(0);
+            if remaining <= 0 {
+                self.spellbook
+                    .notify(Some(format!("No {} slots left to cast {}", slot, name)));
+                return false;
+            }
+        }
+        let costs = self
+            .features
+            .spell_costs()
+            .into_iter()
+            .map(|(feature, cost)| {
+                let purpose = format!("cast {} with {}", name, feature);
+                (purpose, cost.for_spell_level(spell.level()))
+            })
+            .collect::<Vec<(String, SlotAmount)>>();
+        let refused = costs
+            .iter()
+            .find_map(|(purpose, cost)| self.features.refuse_spend(cost, purpose));
+        if refused.is_some() {
+            self.spellbook.notify(refused);
+            return false;
+        }
+
+        let mut dirty = false;
+        for (purpose, cost) in costs.iter() {
+            dirty = self.features.spend(cost, purpose).is_ok() || dirty;
+        }
+        if let Some(slot) = slot {
+            dirty = self.features.update(FeatureMessage::Slot(
+                FeaturePath::of(slot.path()),
+                SlotCommand::Use,
+            )) || dirty;
+        }
+
+        let label = if slot_level > spell.level() {
+            format!("{} (level {})", name, slot_level)
+        } else {
+            name.clone()
+        };
+        let (casting_time, targets) = self.spell_bonuses();
+        let mut notes = vec![];
+        let active = self.features.active();
+        if !active.is_empty() {
+            notes.push(format!("with {}", active.join(", ")));
+        }
+        if let Some(casting_time) = casting_time {
+            notes.push(format!("as {}", casting_time));
+        }
+        let note = if notes.is_empty() {
+            None
+        } else {
+            Some(notes.join(", "))
+        };
+        let ability_scores = self.ability_scores.modified();
+        let path = self
+            .features
+            .generated_path(spellbook::SPELLS_KEY)
+            .map(|spells| spells.with_child(name.clone()))
+            .unwrap_or_else(FeaturePath::empty);
+        for target in 1..=targets {
+            let label = if targets > 1 {
+                format!("{} (target {})", label, target)
+            } else {
+                label.clone()
+            };
+            if let Some(attack) = self.features.find_roll(&path, "Attack") {
+                let outcome = attack
+                    .rollable(&ability_scores, &self.classes)
+                    .roll(&mut rand::thread_rng());
+                self.roll_log.record(RollLogEntry::new(
+                    format!("{} Attack", label),
+                    outcome,
+                    note.clone(),
+                ));
+            }
+            if let Some(damage) = self.features.find_roll(&path, &spell.damage_roll_name()) {
+                let mut rollable = damage.rollable(&ability_scores, &self.classes);
+                let upcast = spell.upcast_dice(slot_level);
+                if !upcast.is_empty() {
+                    let mut extra = Rollable::from(upcast.clone(), HashSet::new(), vec![], 0);
+                    extra.contribute(Contribution::new(
+                        Amount::Dice(upcast),
+                        Source::Roll("Upcast".to_string()),
+                    ));
+                    rollable.merge(extra);
+                }
+                let outcome = rollable.roll(&mut rand::thread_rng());
+                self.roll_log.record(RollLogEntry::new(
+                    format!("{} {}", label, spell.damage_roll_name()),
+                    outcome,
+                    note.clone(),
+                ));
+            }
+            let save = spell
+                .save_roll_name()
+                .and_then(|save| self.features.find_roll(&path, &save));
+            if let Some(save) = save {
+                let outcome = save
+                    .rollable(&ability_scores, &self.classes)
+                    .roll(&mut rand::thread_rng());
+                self.roll_log.record(RollLogEntry::new(
+                    format!("{} target's {}", label, save.name()),
+                    outcome,
+                    note.clone(),
+                ));
+            }
+        }
+
+        self.spellbook
+            .notify(note.map(|note| format!("Cast {} {}", name, note)));
+        if self.features.end_activations() {
+            self.reset_effects();
+        }
+        dirty
+    }
+
+    /// The casting time and number of targets that activated features, such as metamagic, give
+    /// the next spell.
+    fn spell_bonuses(&self) -> (Option<String>, isize) {
+        let (mut casting_time, mut targets) = (None, 1);
+        for active_effect in self.features.effects(self.concentration.concentrating_on()) {
+            match active_effect.effect() {
+                Effect::Spell {
+                    bonus: SpellBonus::CastingTime(time),
+                    ..
+                } => casting_time = Some(time.clone()),
+                Effect::Spell {
+                    bonus: SpellBonus::ExtraTargets(extra),
+                    ..
+                } => targets += extra,
+                _ => {}
+            }
+        }
+        (casting_time, targets)
+    }
+
+    /// The stats shown on the sheet; movement types other than walking only appear when an
+    /// effect grants them.
+    fn stats(&self) -> Vec<(Stat, ModifiedValue)> {
+        let mut stats = vec![
+            Stat::MaxHitPoints,
+            Stat::ArmorClass,
+            Stat::Initiative,
+            Stat::Speed(Movement::Walk),
+        ];
+        for movement in vec![
+            Movement::Fly,
+            Movement::Swim,
+            Movement::Climb,
+            Movement::Burrow,
+        ] {
+            let stat = Stat::Speed(movement);
+            if self.stats.has_bonus(&stat) {
+                stats.push(stat);
+            }
+        }
+        stats.push(Stat::CarryingCapacity);
+
+        stats
+            .into_iter()
+            .map(|stat| {
+                let value = self.stat(&stat);
+                (stat, value)
+            })
+            .collect()
+    }
+
+    /// Spell save DC and spell attack for each class that casts spells.
+    fn spellcasting(&self) -> Vec<Spellcasting> {
+        self.classes
+            .spellcasters()
+            .into_iter()
+            .map(|(class, ability)| {
+                let save_dc = self.ability_stat(&Stat::SpellSaveDc, Some(ability.clone()));
+                let attack = self.ability_stat(&Stat::SpellAttack, Some(ability.clone()));
+                Spellcasting::new(class, ability, save_dc, attack)
+            })
+            .collect()
+    }
+
+    fn stat(&self, stat: &Stat) -> ModifiedValue {
+        self.ability_stat(stat, self.classes.spellcasting_ability())
+    }
+
+    /// A stat, with spellcasting stats using `spellcasting_ability`.
+    fn ability_stat(&self, stat: &Stat, spellcasting_ability: Option<Ability>) -> ModifiedValue {
+        let ability_scores = self.ability_scores.modified();
+        let base = |value: isize| Contribution::new(Amount::Value(value), Source::Base);
+        let proficiency = Contribution::new(
+            Amount::Modifier(self.classes.proficiency_bonus()),
+            Source::Proficiency(None),
+        );
+
+        let contributions = match stat {
+            Stat::MaxHitPoints => vec![base(self.hit_points.max_hit_points())],
+            Stat::Speed(Movement::Walk) => vec![base(self.description.speed())],
+            Stat::Speed(_) => vec![],
+            Stat::Initiative => vec![ability_scores.modifier_contribution(Ability::Dexterity)],
+            Stat::ArmorClass => vec![
+                base(10),
+                ability_scores.modifier_contribution(Ability::Dexterity),
+            ],
+            Stat::CarryingCapacity => {
+                let strength = ability_scores.get(Ability::Strength).score().value();
+                vec![Contribution::new(
+                    Amount::Value(15 * strength),
+                    Source::Ability {
+                        ability: Ability::Strength,
+                        modified_by: ability_scores.get(Ability::Strength).modified_by(),
+                    },
+                )]
+            }
+            Stat::SpellSaveDc => {
+                let mut contributions = vec![base(8), proficiency];
+                if let Some(ability) = spellcasting_ability {
+                    contributions.push(ability_scores.modifier_contribution(ability));
+                }
+                contributions
+            }
+            Stat::SpellAttack => {
+                let mut contributions = vec![proficiency];
+                if let Some(ability) = spellcasting_ability {
+                    contributions.push(ability_scores.modifier_contribution(ability));
+                }
+                contributions
+            }
+        };
+
+        self.stats.value(stat, contributions)
+    }
+
+    /// Rolls on a random table for the feature at `source`, logging the result, applying its
+    /// effects for as long as the entry lasts and refilling the slots it resets.
+    fn roll_table(&mut self, source: FeaturePath, table_name: String) -> IsDirty {
+        self.roll_log.notify(None);
+        let table = match self.resources.tables().get(&table_name) {
+            Some(table) => table.clone(),
+            None => {
+                self.roll_log
+                    .notify(Some(format!("No random table named {}", table_name)));
+                return false;
+            }
+        };
+
+        let outcome =
+            Rollable::from(table.dice(), HashSet::new(), vec![], 0).roll(&mut rand::thread_rng());
+        let roll = outcome.total();
+        let entry = table.entry(roll);
+        self.roll_log.record(RollLogEntry::new(
+            table_name.clone(),
+            outcome,
+            entry.map(|entry| entry.text().to_string()),
+        ));
+
+        let mut dirty = false;
+        if let Some(entry) = entry {
+            if !entry.effects().is_empty() {
+                self.timed_effects.add(TimedEffect::new(
+                    source,
+                    entry.text().to_string(),
+                    entry.effects().clone(),
+                    entry.until(),
+                ));
+                dirty = true;
+            }
+        }
+        for path in table.resets(roll) {
+            let path = self.features.resolve_slot(&path).unwrap_or(path);
+            dirty = self.features.update(FeatureMessage::Slot(
+                FeaturePath::of(path),
+                SlotCommand::Reset,
+            )) || dirty;
+        }
+
+        self.reset_effects();
+        dirty
+    }
+
+    /// Rolls one of a feature's rolls into the roll log.
+    fn roll(&mut self, path: FeaturePath, roll_name: String) {
+        let feature_name = match self.features.feature_name(&path) {
+            Some(name) => name,
+            None => return,
+        };
+        let ability_scores = self.ability_scores.modified();
+        if let Some(roll) = self.features.find_roll(&path, &roll_name) {
+            let outcome = roll
+                .rollable(&ability_scores, &self.classes)
+                .roll(&mut rand::thread_rng());
+            self.roll_log.record(RollLogEntry::new(
+                format!("{} {}", feature_name, roll_name),
+                outcome,
+                None,
+            ));
+        }
+    }
+
+    /// Amends a logged roll with a post-roll modifier, paying its cost and rolling its dice.
+    fn amend_roll(&mut self, entry: usize, modifier: FeaturePath, subtract: bool) -> IsDirty {
+        let modifier = match self
+            .features
+            .post_roll_modifiers()
+            .into_iter()
+            .find(|m| m.path == modifier)
+        {
+            Some(modifier) => modifier,
+            None => return false,
+        };
+
+        let mut dirty = false;
+        self.roll_log.notify(None);
+        if let Some(cost) = modifier.post_roll.cost() {
+            if let Err(refused) = self.features.spend(cost, &format!("use {}", modifier.name)) {
+                self.roll_log.notify(Some(refused));
+                return false;
+            }
+            dirty = true;
+        }
+
+        let outcome = Rollable::from(modifier.post_roll.dice(), HashSet::new(), vec![], 0)
+            .roll(&mut rand::thread_rng());
+        self.roll_log
+            .amend(entry, Amendment::new(modifier.name, outcome, subtract));
+        dirty
+    }
+
+    fn concentration_save(&mut self, damage: isize) -> IsDirty {
+        let feature = match self.concentration.concentrating_on() {
+            Some(feature) => feature.clone(),
+            None => return false,
+        };
+        let feature_name = self
+            .features
+            .feature_name(&feature)
+            .unwrap_or_else(|| feature.to_string());
+
+        let dc = concentration::save_dc(damage);
+        let ability_scores = self.ability_scores.modified();
+        let save = match self
+            .features
+            .path_of(concentration::SAVING_THROWS_FEATURE)
+            .and_then(|path| {
+                self.features
+                    .find_roll(&path, concentration::CONSTITUTION_SAVE_ROLL)
+            }) {
+            Some(roll) => roll.rollable(&ability_scores, &self.classes),
+            None => Rollable::from(
+                vec![Dice::new(1, 20)],
+                HashSet::new(),
+                vec![],
+                ability_scores.get(Ability::Constitution).score().modifier(),
+            ),
+        };
+
+        let outcome = save.roll(&mut rand::thread_rng());
+        let maintained = outcome.total() >= dc;
+        let note = if maintained {
+            format!("still concentrating on {}", feature_name)
+        } else {
+            format!("lost concentration on {}", feature_name)
+        };
+        self.roll_log.record(RollLogEntry::new(
+            format!("Concentration (DC {})", dc),
+            outcome,
+            Some(note),
+        ));
+
+        if maintained {
+            false
+        } else {
+            self.concentration.update(ConcentrationMessage::End);
+            self.reset_effects();
+            true
+        }
     }
 }
 
+type IsDirty = bool;
+
 #[derive(Debug, Clone)]
 pub enum Message {
     Loaded(Result<LoadData, LoadError>),
     Saved(Result<(), LoadError>),
     HitPoint(hitpoints::HitPointMessage),
     Feature(FeatureMessage),
+    Concentration(ConcentrationMessage),
+    AbilityScore(AbilityScoreMessage),
+    AbilityGeneration(AbilityGenerationMessage),
+    Stat(StatMessage),
+    Rest(Recovery),
+    TimedEffect(TimedEffectMessage),
+    RollLog(RollLogMessage),
+    Spellbook(SpellbookMessage),
+    Experience(ExperienceMessage),
+    LevelUp(LevelUpMessage),
+    NewCharacter(NewCharacterMessage),
+    Launcher(LauncherMessage),
+    Versions(VersionsMessage),
+    History(HistoryMessage),
+    Journal(JournalMessage),
     ResetEffects,
 }
 
 impl Application for Character {
     type Executor = iced::executor::Default;
     type Message = Message;
-    type Flags = CharacterPersistenceConfig;
+    /// The storage root, holding the characters to pick from.
+    type Flags = String;
 
-    fn new(flags: CharacterPersistenceConfig) -> (Character, Command<Message>) {
-        (
-            Character::Loading(flags.clone()),
-            Command::perform(flags.load(), Message::Loaded),
-        )
+    fn new(storage_root: String) -> (Character, Command<Message>) {
+        launch(storage_root)
     }
 
     fn title(&self) -> String {
         match self {
-            Character::Loaded(state) => "Character".to_string(),
+            Character::Loaded(_) => "Character".to_string(),
+            Character::Creating(_) => "New Character".to_string(),
+            Character::Launching(_) => "Characters".to_string(),
+            Character::Failed(_) => "Can't open character".to_string(),
             _ => "Loading...".to_string(),
         }
     }
 
     fn update(&mut self, message: Self::Message) -> Command<Self::Message> {
+        let storage_root = match (&*self, &message) {
+            (Character::Loaded(state), Message::Launcher(LauncherMessage::Show))
+                if !state.is_busy() =>
+            {
+                Some(state.config.storage_root())
+            }
+            (Character::Failed(failure), Message::Launcher(LauncherMessage::Show)) => {
+                Some(failure.storage_root())
+            }
+            _ => None,
+        };
+        if let Some(storage_root) = storage_root {
+            let (launcher, command) = launch(storage_root);
+            *self = launcher;
+            return command;
+        }
         match self {
-            Character::Loading(_) => match message {
+            Character::Failed(failure) => match message {
+                Message::Launcher(LauncherMessage::Quarantine(id)) => {
+                    let mut launcher = LauncherState::new(failure.storage_root());
+                    let command = launcher.update(LauncherMessage::Quarantine(id));
+                    *self = Character::Launching(launcher);
+                    command
+                }
+                _ => Command::none(),
+            },
+            Character::Launching(launcher) => match message {
+                Message::Launcher(LauncherMessage::Open(id)) => {
+                    let config = CharacterPersistenceConfig::new(launcher.storage_root(), id);
+                    *self = Character::Loading(config.clone());
+                    Command::perform(config.load(), Message::Loaded)
+                }
+                Message::Launcher(LauncherMessage::NewCharacter) => Command::perform(
+                    crate::resources::load(launcher.storage_root()),
+                    |resources| Message::NewCharacter(NewCharacterMessage::Loaded(resources)),
+                ),
+                Message::NewCharacter(NewCharacterMessage::Loaded(Ok(resources))) => {
+                    *self = Character::Creating(NewCharacterState::new(
+                        launcher.storage_root(),
+                        resources,
+                    ));
+                    Command::none()
+                }
+                Message::NewCharacter(NewCharacterMessage::Loaded(Err(e))) => {
+                    println!("Cannot load templates for a new character {:?}", e);
+                    Command::none()
+                }
+                Message::Launcher(launcher_message) => launcher.update(launcher_message),
+                _ => Command::none(),
+            },
+            Character::Creating(creation) => {
+                let outcome = match message {
+                    Message::NewCharacter(new_character_message) => {
+                        creation.update(new_character_message)
+                    }
+                    Message::AbilityGeneration(generation_message) => {
+                        creation.generate(generation_message);
+                        None
+                    }
+                    _ => None,
+                };
+                match outcome {
+                    Some(Outcome::Created(character)) => {
+                        *self = Character::Loading(character.config());
+                        Command::perform(character.create(), Message::Loaded)
+                    }
+                    Some(Outcome::Cancelled(storage_root)) => {
+                        let (launcher, command) = launch(storage_root);
+                        *self = launcher;
+                        command
+                    }
+                    None => Command::none(),
+                }
+            }
+            Character::Loading(config) => match message {
                 Message::Loaded(Ok(loaded)) => {
-                    *self = Character::Loaded(loaded.to_state());
+                    let mut state = loaded.to_state();
+                    state.journal.start_session();
+                    *self = Character::Loaded(state);
                     self.update(Message::ResetEffects)
                 }
                 Message::Loaded(Err(e)) => {
                     println!("Encountered error {:?}", e);
-                    *self = Character::Loaded(State::default());
+                    *self = Character::Failed(LoadFailureState::new(config.clone(), e));
                     Command::none()
                 }
                 unexpected => {
@@ -110,115 +855,368 @@ impl Application for Character {
                 }
             },
             Character::Loaded(state) => {
+                let before = state.persistable();
+                let undoing = matches!(message, Message::History(_));
+                let undoable = if undoing {
+                    None
+                } else {
+                    Some((message.clone(), history::reading(state, &message)))
+                };
+                let undone = match &message {
+                    Message::History(HistoryMessage::Undo) => state
+                        .history
+                        .next_undo()
+                        .map(|label| format!("Undid {}", label)),
+                    Message::History(HistoryMessage::Redo) => state
+                        .history
+                        .next_redo()
+                        .map(|label| format!("Redid {}", label)),
+                    _ => None,
+                };
+                let amended = match &message {
+                    Message::RollLog(RollLogMessage::Amend { entry, .. }) => Some(*entry),
+                    _ => None,
+                };
+                let rolls = state.roll_log.count();
+                let pending = state.dirty;
+                state.dirty = false;
                 match message {
+                    Message::Versions(VersionsMessage::Restore(backup)) => {
+                        let config = state.config.clone();
+                        *self = Character::Loading(config.clone());
+                        return Command::perform(config.restore(backup), Message::Loaded);
+                    }
+                    Message::Versions(versions_message) => {
+                        return state.versions.update(versions_message, &state.config);
+                    }
+                    Message::Journal(journal_message) => {
+                        return state.journal.update(journal_message, &state.config);
+                    }
+                    Message::History(history_message) => {
+                        let current = state.persistable();
+                        let character = match history_message {
+                            HistoryMessage::Undo => state.history.undo(current),
+                            HistoryMessage::Redo => state.history.redo(current),
+                        };
+                        if let Some(character) = character {
+                            state.replace_character(character);
+                        }
+                    }
                     Message::ResetEffects => {
-                        let mut active_effects = state.features.effects();
-
-                        state.ability_scores.apply_all(&active_effects);
-                        state.features.apply_effects(&active_effects);
+                        state.reset_effects();
                     }
-                    Message::Loaded(_) => {}
+                    Message::Loaded(_) | Message::NewCharacter(_) | Message::Launcher(_) => {}
                     Message::Saved(_) => {
                         state.saving = false;
                     }
                     Message::HitPoint(hit_point_message) => {
-                        state.dirty = state.hit_points.update(hit_point_message)
+                        let damage = match hit_point_message {
+                            HitPointMessage::Damage => state.hit_points.delta(),
+                            _ => 0,
+                        };
+                        state.dirty = state.hit_points.update(hit_point_message);
+                        if damage > 0 {
+                            state.dirty = state.concentration_save(damage) || state.dirty;
+                        }
+                    }
+                    Message::Feature(FeatureMessage::Concentrate(path)) => {
+                        state.dirty = state
+                            .concentration
+                            .update(ConcentrationMessage::Start(path));
+                        state.reset_effects();
+                    }
+                    Message::Feature(FeatureMessage::Activate(path)) => {
+                        state.dirty = state.features.update(FeatureMessage::Activate(path));
+                        state.reset_effects();
+                    }
+                    Message::Feature(FeatureMessage::Roll(path, roll_name)) => {
+                        state.roll(path, roll_name);
+                    }
+                    Message::Feature(FeatureMessage::RollTable(path, table)) => {
+                        state.dirty = state.roll_table(path, table);
                     }
                     Message::Feature(feature_message) => {
                         state.dirty = state.features.update(feature_message);
                     }
+                    Message::RollLog(RollLogMessage::Amend {
+                        entry,
+                        modifier,
+                        subtract,
+                    }) => {
+                        state.dirty = state.amend_roll(entry, modifier, subtract);
+                    }
+                    Message::TimedEffect(timed_effect_message) => {
+                        state.dirty = state.timed_effects.update(timed_effect_message);
+                        state.reset_effects();
+                    }
+                    Message::AbilityScore(ability_score_message) => {
+                        state.ability_scores.update(ability_score_message);
+                    }
+                    Message::AbilityGeneration(generation_message) => {
+                        if let Some(ability_scores) =
+                            state.ability_generation.update(generation_message)
+                        {
+                            state.ability_scores = ability_scores.to_state();
+                            state.reset_effects();
+                            state.dirty = true;
+                        }
+                    }
+                    Message::Stat(stat_message) => {
+                        state.stats.update(stat_message);
+                    }
+                    Message::Spellbook(SpellbookMessage::Cast(name, slot)) => {
+                        state.dirty = state.cast(name, slot);
+                    }
+                    Message::Spellbook(spellbook_message) => {
+                        let preparation = state.preparation();
+                        state.dirty = state.spellbook.update(
+                            spellbook_message,
+                            state.resources.spells(),
+                            &preparation,
+                        );
+                        if state.dirty {
+                            state.reset_effects();
+                        }
+                    }
+                    Message::Experience(experience_message) => {
+                        state.dirty = state.experience.update(experience_message);
+                    }
+                    Message::LevelUp(level_up_message) => {
+                        let character = state.persistable();
+                        let constitution_modifier = state.constitution_modifier();
+                        if let Some(leveled) = state.level_up.update(
+                            level_up_message,
+                            &character,
+                            &state.resources,
+                            constitution_modifier,
+                        ) {
+                            state.replace_character(leveled);
+                        }
+                    }
+                    Message::Rest(rest) => {
+                        let mut dirty = state.features.rest(&rest);
+                        if state.timed_effects.rest(&rest) {
+                            state.reset_effects();
+                            dirty = true;
+                        }
+                        if rest == Recovery::LongRest {
+                            dirty = state.hit_points.update(HitPointMessage::FullHealth) || dirty;
+                        }
+                        state.dirty = dirty;
+                    }
+                    Message::Concentration(concentration_message) => {
+                        state.dirty = state.concentration.update(concentration_message);
+                        state.reset_effects();
+                    }
+                }
+
+                let changed = state.dirty;
+                state.dirty = pending || changed;
+                match undoable {
+                    Some((message, reading)) if changed && history::tracks(&message) => {
+                        if let Some(label) = history::label(state, &message, reading) {
+                            if let Some(kind) = journal::kind(&message) {
+                                state.journal.record(kind, label.clone());
+                            }
+                            state.history.record(label, before.clone());
+                        }
+                    }
+                    Some((message, _)) if changed => {
+                        let label =
+                            history::describe(state, &message, &before, &state.persistable());
+                        state.history.record(label, before.clone());
+                    }
+                    _ => {}
                 }
+                state.record_in_journal(before, changed, undone, amended, rolls);
 
+                let journal = state.journal.write(&state.config);
                 if state.dirty && !state.saving {
                     state.dirty = false;
                     state.saving = true;
                     let persistant_data = state.persistable();
-                    Command::perform(persistant_data.save(), Message::Saved)
+                    Command::batch(vec![
+                        Command::perform(persistant_data.save(), Message::Saved),
+                        journal,
+                    ])
                 } else {
-                    Command::none()
+                    journal
                 }
             }
         }
     }
 
+    fn subscription(&self) -> Subscription<Message> {
+        match self {
+            Character::Loaded(_) => history::shortcuts(),
+            _ => Subscription::none(),
+        }
+    }
+
     fn view(&mut self) -> Element<'_, Self::Message> {
         match self {
             Character::Loading(_) => loading(),
-            Character::Loaded(State {
-                config,
-                resources,
-                name,
-                description,
-                ability_scores,
-                classes,
-                hit_points,
-                proficiencies,
-                features,
-                saving,
-                dirty,
-                scroll,
-            }) => {
-                let modified_ability_scores = &ability_scores.modified();
-
-                let name = name.view().padding(4);
-                let description = description.view().padding(4);
-
-                let features = features.view(
-                    FeaturePath::empty(),
-                    &modified_ability_scores.ability_scores(),
-                    classes,
-                    &Message::Feature,
-                );
-
-                let ability_scores = ability_scores.view().padding(4);
-
-                let proficiencies = proficiencies.view().padding(4);
-                let classes = classes.view().padding(4);
-
-                let hp_view = hit_points
-                    .view()
-                    .max_width(800)
-                    .spacing(20)
-                    .padding(20)
-                    .width(Length::FillPortion(1));
-
-                let layout = Column::new()
-                    .align_items(Align::Start)
+            Character::Creating(creation) => creation.view(),
+            Character::Launching(launcher) => launcher.view(),
+            Character::Failed(failure) => failure.view(),
+            Character::Loaded(state) => {
+                let stat_values = state.stats();
+                state.view(stat_values)
+            }
+        }
+    }
+}
+
+impl State {
+    fn view(&mut self, stat_values: Vec<(Stat, ModifiedValue)>) -> Element<'_, Message> {
+        let preparation = self.preparation();
+        let casting_slots = self.casting_slots();
+        let post_roll_modifiers = self.features.post_roll_modifiers();
+        let spellcasting = self.spellcasting();
+        let character = self.persistable();
+        let constitution_modifier = self.constitution_modifier();
+        let busy = self.is_busy();
+        let State {
+            config,
+            resources,
+            name,
+            description,
+            ability_scores,
+            ability_generation,
+            classes,
+            hit_points,
+            proficiencies,
+            features,
+            spellbook,
+            stats,
+            concentration,
+            timed_effects,
+            experience,
+            feats: _,
+            improvements: _,
+            template_grants: _,
+            level_up,
+            roll_log,
+            rest_controls,
+            history,
+            journal,
+            versions,
+            characters_button,
+            saving,
+            dirty,
+            scroll,
+        } = self;
+
+        let modified_ability_scores = &ability_scores.modified();
+        let concentration_name = concentration
+            .concentrating_on()
+            .and_then(|path| features.feature_name(path));
+
+        let mut characters = Button::new(
+            characters_button,
+            Text::new(if busy { "Saving..." } else { "Characters" }).size(16),
+        )
+        .padding(8);
+        if !busy {
+            characters = characters.on_press(Message::Launcher(LauncherMessage::Show));
+        }
+        let name = name.view().padding(4).push(characters);
+        let description = description.view().padding(4);
+
+        let features = features.view(
+            FeaturePath::empty(),
+            modified_ability_scores,
+            classes,
+            &Message::Feature,
+        );
+
+        let ability_scores = ability_scores.view(&Message::AbilityScore).padding(4);
+        let ability_generation = ability_generation.view().padding(4);
+        let stats = stats.view(stat_values, &Message::Stat).padding(4);
+        let spellcasting = spellcasting::view(&spellcasting).padding(4);
+
+        let proficiencies = proficiencies.view().padding(4);
+        let spellbook = spellbook
+            .view(resources.spells(), &preparation, &casting_slots)
+            .padding(4);
+        let experience = experience.view(classes.total_level()).padding(4);
+        let classes = classes.view().padding(4);
+        let level_up = level_up
+            .view(&character, resources, constitution_modifier)
+            .padding(4);
+
+        let hp_view = hit_points
+            .view()
+            .max_width(800)
+            .spacing(20)
+            .padding(20)
+            .width(Length::FillPortion(1));
+
+        let rest_controls = rest_controls.view().padding(4);
+        let history = history.view().padding(4);
+        let journal = journal.view().padding(4);
+        let versions = versions.view(*saving).padding(4);
+        let concentration = concentration.view(concentration_name).padding(4);
+        let timed_effects = timed_effects.view().padding(4);
+        let roll_log = roll_log
+            .view(&post_roll_modifiers, &Message::RollLog)
+            .padding(4);
+
+        let layout = Column::new()
+            .align_items(Align::Start)
+            .push(
+                Row::new()
+                    .spacing(8)
                     .push(
-                        Row::new()
-                            .spacing(8)
-                            .push(
-                                Column::new()
-                                    .push(name)
-                                    .push(ability_scores)
-                                    .width(Length::FillPortion(1)),
-                            )
-                            .push(
-                                Column::new()
-                                    .push(classes)
-                                    .push(description)
-                                    .width(Length::FillPortion(1)),
-                            ),
+                        Column::new()
+                            .push(name)
+                            .push(ability_scores)
+                            .push(ability_generation)
+                            .width(Length::FillPortion(1)),
                     )
-                    .push(Row::new().push(hp_view))
                     .push(
-                        Row::new().spacing(8).push(
-                            Column::new()
-                                .push(proficiencies)
-                                .width(Length::FillPortion(1)),
-                        ),
-                    )
-                    .push(features);
+                        Column::new()
+                            .push(classes)
+                            .push(experience)
+                            .push(level_up)
+                            .push(description)
+                            .push(stats)
+                            .push(spellcasting)
+                            .width(Length::FillPortion(1)),
+                    ),
+            )
+            .push(Row::new().push(hp_view))
+            .push(history)
+            .push(rest_controls)
+            .push(concentration)
+            .push(timed_effects)
+            .push(roll_log)
+            .push(
+                Row::new().spacing(8).push(
+                    Column::new()
+                        .push(proficiencies)
+                        .push(spellbook)
+                        .width(Length::FillPortion(1)),
+                ),
+            )
+            .push(features)
+            .push(journal)
+            .push(versions);
 
-                Scrollable::new(scroll)
-                    .padding(40)
-                    .push(Container::new(layout).width(Length::Fill).center_x())
-                    .into()
-            }
-        }
+        Scrollable::new(scroll)
+            .padding(40)
+            .push(Container::new(layout).width(Length::Fill).center_x())
+            .into()
     }
 }
 
+/// The launcher, listing the characters saved under `storage_root`.
+fn launch(storage_root: String) -> (Character, Command<Message>) {
+    let launcher = LauncherState::new(storage_root);
+    let command = launcher.refresh();
+    (Character::Launching(launcher), command)
+}
+
 fn loading<'a>() -> Element<'a, Message> {
     Container::new(
         Text::new("Loading...")
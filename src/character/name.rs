@@ -7,6 +7,10 @@ pub struct Name {
     name: String,
 }
 impl Name {
+    pub fn of(name: String) -> Name {
+        Name { name }
+    }
+
     pub fn view(&mut self) -> Row<Message> {
         Row::new().push(
             Text::new(self.name.clone())
@@ -0,0 +1,453 @@
+use crate::character::ability_generation::{
+    AbilityGeneration, AbilityGenerationMessage, AbilityGenerationState,
+};
+use crate::character::description::Description;
+use crate::character::name::Name;
+use crate::character::persistence::{CharacterPersistence, CharacterPersistenceConfig};
+use crate::character::Message;
+use crate::core::ability_score::{Ability, AbilityScore, AbilityScores};
+use crate::core::feature::Feature;
+use crate::resources::progression::ClassProgression;
+use crate::resources::template::{TemplateKind, Templates};
+use crate::resources::{ResourceError, Resources};
+use iced::{
+    button, pick_list, scrollable, text_input, Align, Button, Column, Container, Element, Length,
+    PickList, Row, Scrollable, Text, TextInput,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct NewCharacterChoices {
+    name: String,
+    race: Option<String>,
+    class: Option<String>,
+    subclass: Option<String>,
+    background: Option<String>,
+    ability_scores: Option<AbilityScores>,
+    ability_generation: Option<AbilityGeneration>,
+    skills: Vec<Option<String>>,
+    equipment_pack: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum NewCharacterMessage {
+    /// Resources loaded to create a character that hasn't been saved yet.
+    Loaded(Result<Resources, ResourceError>),
+    ChangeName(String),
+    SelectRace(String),
+    SelectClass(String),
+    SelectSubclass(String),
+    SelectBackground(String),
+    SelectSkill(usize, String),
+    SelectEquipmentPack(String),
+    Create,
+    Cancel,
+}
+
+pub enum Outcome {
+    Created(CharacterPersistence),
+    /// Back to the launcher for the storage root.
+    Cancelled(String),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NewCharacterState {
+    storage_root: String,
+    resources: Resources,
+    choices: NewCharacterChoices,
+    ability_generation: AbilityGenerationState,
+    /// Why the character can't be created, checked whenever a choice changes.
+    problem: Option<String>,
+    name_input: text_input::State,
+    race_list: pick_list::State<String>,
+    class_list: pick_list::State<String>,
+    subclass_list: pick_list::State<String>,
+    background_list: pick_list::State<String>,
+    skill_lists: Vec<pick_list::State<String>>,
+    equipment_pack_list: pick_list::State<String>,
+    create_button: button::State,
+    cancel_button: button::State,
+    scroll: scrollable::State,
+}
+
+/// The file name for a character, from its name: "Vynne Ashborn" is saved as "vynne-ashborn".
+pub fn character_id(name: &str) -> String {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(|part| part.to_lowercase())
+        .collect::<Vec<String>>()
+        .join("-")
+}
+
+/// The class skills that can be chosen, leaving out those the background already grants.
+fn skill_options(
+    templates: &Templates,
+    progression: &ClassProgression,
+    background: Option<&str>,
+) -> Vec<String> {
+    let granted = background
+        .and_then(|background| templates.background(background))
+        .map(|background| background.features().clone())
+        .unwrap_or_default();
+    progression
+        .skill_choice()
+        .1
+        .iter()
+        .filter(|skill| !granted.contains(&proficiency(skill)))
+        .cloned()
+        .collect()
+}
+
+fn proficiency(skill: &str) -> String {
+    format!("{} Proficiency", skill)
+}
+
+/// A level 1 character saved under `storage_root`, or the choice still to be made. Spells are
+/// learned from the spellbook once the character is open.
+pub fn create(
+    storage_root: &str,
+    templates: &Templates,
+    choices: &NewCharacterChoices,
+) -> Result<CharacterPersistence, String> {
+    let id = character_id(&choices.name);
+    if id.is_empty() {
+        return Err("Name the character".to_string());
+    }
+    let config = CharacterPersistenceConfig::new(storage_root.to_string(), id.clone());
+    if config.exists() {
+        return Err(format!("There already is a character saved as {}", id));
+    }
+    let race = choices.race.clone().ok_or("Choose a race")?;
+    let class = choices.class.clone().ok_or("Choose a class")?;
+    let background = choices.background.clone().ok_or("Choose a background")?;
+    let ability_scores = choices
+        .ability_scores
+        .clone()
+        .ok_or("Generate ability scores")?;
+    let progression = templates
+        .class(&class)
+        .ok_or_else(|| format!("There is no progression for {}", class))?;
+
+    let (count, _) = progression.skill_choice();
+    let options = skill_options(templates, progression, Some(&background));
+    let mut skills = vec![];
+    for index in 0..count {
+        match choices.skills.get(index).cloned().flatten() {
+            Some(skill) if skills.contains(&proficiency(&skill)) => {
+                return Err(format!("{} is chosen twice", skill))
+            }
+            Some(skill) if !options.contains(&skill) => {
+                return Err(format!("{} can't be chosen", skill))
+            }
+            Some(skill) => skills.push(proficiency(&skill)),
+            None => return Err(format!("Choose {} skills", count)),
+        }
+    }
+    let equipment_pack = choices
+        .equipment_pack
+        .clone()
+        .ok_or("Choose an equipment pack")?;
+
+    let mut character = CharacterPersistence::new(
+        config,
+        Name::of(choices.name.trim().to_string()),
+        Description::new(race.clone(), Some(background)),
+        ability_scores.clone(),
+        choices.ability_generation.clone(),
+    );
+    character.level_up_class(&class, progression);
+    if progression.chooses_subclass_at(1) {
+        match &choices.subclass {
+            Some(subclass) => character.set_subclass(&class, subclass.clone()),
+            None => return Err("Choose a subclass".to_string()),
+        }
+    }
+
+    // The first level takes the most the hit die can give, with the racial constitution.
+    let constitution = ability_scores.get(Ability::Constitution).value()
+        + templates
+            .granted(&[], Some(&race), None, &[])
+            .iter()
+            .flat_map(|granted| granted.grants.ability_bonuses().clone())
+            .filter(|bonus| bonus.ability == Ability::Constitution)
+            .map(|bonus| bonus.bonus)
+            .sum::<isize>();
+    character.gain_hit_points(std::cmp::max(
+        1,
+        progression.hit_die() + AbilityScore::of(constitution).modifier(),
+    ));
+
+    character.apply_templates(templates);
+    character.grant_features(
+        Feature::named(&class).with_description(TemplateKind::Class.to_string()),
+        &skills,
+    );
+    if let Some(pack) = templates.equipment_pack(&equipment_pack) {
+        character.grant_features(
+            Feature::named(&equipment_pack).with_description("Equipment".to_string()),
+            pack.features(),
+        );
+    }
+    Ok(character)
+}
+
+impl NewCharacterState {
+    pub fn new(storage_root: String, resources: Resources) -> NewCharacterState {
+        let mut state = NewCharacterState {
+            storage_root,
+            resources,
+            ..NewCharacterState::default()
+        };
+        state.validate();
+        state
+    }
+
+    fn validate(&mut self) {
+        self.problem = create(
+            &self.storage_root,
+            self.resources.templates(),
+            &self.choices,
+        )
+        .err();
+    }
+
+    pub fn update(&mut self, message: NewCharacterMessage) -> Option<Outcome> {
+        let choices = &mut self.choices;
+        match message {
+            NewCharacterMessage::ChangeName(name) => choices.name = name,
+            NewCharacterMessage::SelectRace(race) => choices.race = Some(race),
+            NewCharacterMessage::SelectClass(class) => {
+                choices.class = Some(class);
+                choices.subclass = None;
+                choices.skills = vec![];
+            }
+            NewCharacterMessage::SelectSubclass(subclass) => choices.subclass = Some(subclass),
+            NewCharacterMessage::SelectBackground(background) => {
+                choices.background = Some(background);
+                choices.skills = vec![];
+            }
+            NewCharacterMessage::SelectSkill(index, skill) => {
+                if choices.skills.len() <= index {
+                    choices.skills.resize(index + 1, None);
+                }
+                choices.skills[index] = Some(skill);
+            }
+            NewCharacterMessage::SelectEquipmentPack(pack) => choices.equipment_pack = Some(pack),
+            NewCharacterMessage::Create => {
+                match create(&self.storage_root, self.resources.templates(), choices) {
+                    Ok(character) => return Some(Outcome::Created(character)),
+                    Err(e) => self.problem = Some(format!("Cannot create character: {}", e)),
+                }
+                return None;
+            }
+            NewCharacterMessage::Cancel => {
+                return Some(Outcome::Cancelled(self.storage_root.clone()))
+            }
+            NewCharacterMessage::Loaded(_) => {}
+        }
+        self.validate();
+        None
+    }
+
+    /// Generates the base ability scores with the same generator as the character sheet.
+    pub fn generate(&mut self, message: AbilityGenerationMessage) {
+        if let Some(ability_scores) = self.ability_generation.update(message) {
+            self.choices.ability_scores = Some(ability_scores);
+            self.choices.ability_generation = self.ability_generation.persistable();
+            self.validate();
+        }
+    }
+
+    pub fn view(&mut self) -> Element<'_, Message> {
+        let NewCharacterState {
+            storage_root: _,
+            resources,
+            choices,
+            ability_generation,
+            problem,
+            name_input,
+            race_list,
+            class_list,
+            subclass_list,
+            background_list,
+            skill_lists,
+            equipment_pack_list,
+            create_button,
+            cancel_button,
+            scroll,
+        } = self;
+        let templates = resources.templates();
+        let message = |message| Message::NewCharacter(message);
+        let labeled = |label: &str| {
+            Row::new()
+                .spacing(20)
+                .align_items(Align::Center)
+                .push(Text::new(label).size(16).width(Length::Units(120)))
+        };
+
+        let mut column = Column::new()
+            .spacing(12)
+            .push(Text::new("New Character").size(48))
+            .push(
+                labeled("Name").push(
+                    TextInput::new(name_input, "Name", choices.name.as_str(), move |name| {
+                        message(NewCharacterMessage::ChangeName(name))
+                    })
+                    .padding(8)
+                    .size(16),
+                ),
+            )
+            .push(labeled("Race").push(PickList::new(
+                race_list,
+                templates.race_names(),
+                choices.race.clone(),
+                move |race| message(NewCharacterMessage::SelectRace(race)),
+            )))
+            .push(labeled("Class").push(PickList::new(
+                class_list,
+                templates.class_names(),
+                choices.class.clone(),
+                move |class| message(NewCharacterMessage::SelectClass(class)),
+            )));
+
+        let progression = choices
+            .class
+            .as_ref()
+            .and_then(|class| templates.class(class));
+        if let Some(progression) = progression.filter(|p| p.chooses_subclass_at(1)) {
+            column = column.push(labeled("Subclass").push(PickList::new(
+                subclass_list,
+                progression.subclasses(),
+                choices.subclass.clone(),
+                move |subclass| message(NewCharacterMessage::SelectSubclass(subclass)),
+            )));
+        }
+
+        column = column.push(labeled("Background").push(PickList::new(
+            background_list,
+            templates.background_names(),
+            choices.background.clone(),
+            move |background| message(NewCharacterMessage::SelectBackground(background)),
+        )));
+
+        let scores = match &choices.ability_scores {
+            Some(ability_scores) => Ability::all()
+                .into_iter()
+                .map(|ability| {
+                    format!(
+                        "{} {}",
+                        ability.abbreviation(),
+                        ability_scores.get(ability.clone()).value()
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join("  "),
+            None => "Not generated".to_string(),
+        };
+        column = column
+            .push(labeled("Ability scores").push(Text::new(scores).size(16)))
+            .push(ability_generation.view());
+
+        if let Some(progression) = progression {
+            let (count, _) = progression.skill_choice();
+            let options = skill_options(templates, progression, choices.background.as_deref());
+            skill_lists.resize_with(count, pick_list::State::default);
+            let mut row = labeled("Skills");
+            for (index, list) in skill_lists.iter_mut().enumerate() {
+                row = row.push(PickList::new(
+                    list,
+                    options.clone(),
+                    choices.skills.get(index).cloned().flatten(),
+                    move |skill| message(NewCharacterMessage::SelectSkill(index, skill)),
+                ));
+            }
+            column = column.push(row);
+        }
+
+        column = column.push(labeled("Equipment").push(PickList::new(
+            equipment_pack_list,
+            templates.equipment_pack_names(),
+            choices.equipment_pack.clone(),
+            move |pack| message(NewCharacterMessage::SelectEquipmentPack(pack)),
+        )));
+
+        let mut create_button = Button::new(create_button, Text::new("Create").size(16)).padding(8);
+        match problem {
+            None => create_button = create_button.on_press(message(NewCharacterMessage::Create)),
+            Some(problem) => column = column.push(Text::new(problem.as_str()).size(16)),
+        }
+        let buttons = Row::new().spacing(20).push(create_button).push(
+            Button::new(cancel_button, Text::new("Cancel").size(16))
+                .on_press(message(NewCharacterMessage::Cancel))
+                .padding(8),
+        );
+
+        Scrollable::new(scroll)
+            .padding(40)
+            .push(Container::new(column.push(buttons)).width(Length::Fill))
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn the_wizard_checks_the_choices_as_they_change() {
+        let mut state =
+            NewCharacterState::new("new-character-test".to_string(), Resources::default());
+        assert_eq!(state.problem, Some("Name the character".to_string()));
+
+        state.update(NewCharacterMessage::ChangeName("Vynne".to_string()));
+        assert_eq!(state.problem, Some("Choose a race".to_string()));
+        state.update(NewCharacterMessage::Create);
+        assert_eq!(
+            state.problem,
+            Some("Cannot create character: Choose a race".to_string())
+        );
+    }
+
+    #[test]
+    fn new_characters_take_their_first_level() {
+        let templates: Templates = serde_json::from_str(
+            r#"{
+                "classes": { "Sorcerer": { "hit_die": 6,
+                    "skills": { "choose": 2, "from": ["Arcana", "Deception", "Insight"] },
+                    "subclass_level": 1, "subclasses": { "Wild Magic": {} } } },
+                "races": { "Hill Dwarf": { "ability_bonuses": [{ "ability": "Constitution", "bonus": 2 }] } },
+                "backgrounds": { "Charlatan": { "features": ["Deception Proficiency"] } },
+                "equipment_packs": { "Explorer's Pack": { "features": ["Bedroll"] } }
+            }"#,
+        )
+        .unwrap();
+        let mut choices = NewCharacterChoices {
+            name: "Vynne Ashborn".to_string(),
+            race: Some("Hill Dwarf".to_string()),
+            class: Some("Sorcerer".to_string()),
+            subclass: Some("Wild Magic".to_string()),
+            background: Some("Charlatan".to_string()),
+            ability_scores: Some(
+                AbilityScores::default().with(Ability::Constitution, AbilityScore::of(13)),
+            ),
+            skills: vec![Some("Arcana".to_string()), Some("Deception".to_string())],
+            equipment_pack: Some("Explorer's Pack".to_string()),
+            ..NewCharacterChoices::default()
+        };
+        assert_eq!(
+            create("new-character-test", &templates, &choices).err(),
+            Some("Deception can't be chosen".to_string())
+        );
+
+        choices.skills[1] = Some("Insight".to_string());
+        let character = create("new-character-test", &templates, &choices).unwrap();
+        let json = serde_json::to_value(&character).unwrap();
+        assert_eq!(json["config"]["character_id"], "vynne-ashborn");
+        assert_eq!(json["classes"][0]["subclass"], "Wild Magic");
+        assert_eq!(json["hit_points"]["max_hit_points"], 8);
+        assert_eq!(
+            json["features"][1]["children"][1]["name"],
+            "Insight Proficiency"
+        );
+        assert_eq!(json["features"][2]["name"], "Explorer's Pack");
+    }
+}
@@ -1,20 +1,45 @@
 use super::State;
+use crate::character::ability_generation::{AbilityGeneration, AbilityGenerationState};
+use crate::character::ability_improvement::{taken_feats, AbilityImprovement};
 use crate::character::class::{Class, Classes};
+use crate::character::concentration::Concentration;
 use crate::character::description::Description;
+use crate::character::experience::Experience;
 use crate::character::hitpoints::HitPoints;
 use crate::character::name::Name;
 use crate::character::proficiencies::Proficiencies;
+use crate::character::spellbook::Spellbook;
+use crate::character::timed_effects::TimedEffects;
 use crate::core::ability_score::AbilityScores;
-use crate::core::feature::{Feature, FeatureState, FeaturesState};
+use crate::core::feature::{grant, revoke, Feature, FeaturesState};
+use crate::core::feature_path::FeaturePath;
+use crate::migration::{self, Read, ReadError, SchemaError, SCHEMA_VERSION};
+use crate::resources::progression::ClassProgression;
+use crate::resources::template::{Grants, Templates};
 use crate::resources::{ResourceError, Resources};
-use crate::store::Store;
+use crate::store::{Backup, ParseError, Store, StoreError};
 use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
 
 #[derive(Debug, Clone, Eq, PartialOrd, PartialEq)]
 pub enum LoadError {
     Store(crate::store::StoreError),
     Serialize(String),
     Resource(ResourceError),
+    Parse(ParseError),
+    Schema(SchemaError),
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::Store(e) => write!(f, "{:?}", e),
+            LoadError::Serialize(e) => write!(f, "{}", e),
+            LoadError::Resource(e) => write!(f, "{}", e),
+            LoadError::Parse(e) => write!(f, "{}", e),
+            LoadError::Schema(e) => write!(f, "{}", e),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialOrd, PartialEq)]
@@ -30,7 +55,16 @@ pub struct LoadData {
 }
 
 impl LoadData {
+    pub fn from(resources: Resources, character: CharacterPersistence) -> LoadData {
+        LoadData {
+            resources,
+            character,
+        }
+    }
+
     pub fn to_state(self) -> State {
+        let mut character = self.character;
+        let granted = character.apply_templates(self.resources.templates());
         let CharacterPersistence {
             name,
             description,
@@ -40,7 +74,16 @@ impl LoadData {
             hit_points,
             proficiencies,
             features,
-        } = self.character;
+            concentration,
+            spells,
+            timed_effects,
+            experience,
+            feats,
+            ability_generation,
+            improvements,
+            granted: template_grants,
+            schema_version: _,
+        } = character;
         let classes = Classes::from(classes);
         let features_templates = self.resources.templates().features();
 
@@ -53,7 +96,16 @@ impl LoadData {
             hit_points: hit_points.to_state(),
             proficiencies: proficiencies,
             features: FeaturesState::from(features, features_templates),
+            concentration: concentration.to_state(),
+            spellbook: spells.to_state(),
+            timed_effects: timed_effects.to_state(),
+            experience: experience.to_state(),
+            feats,
+            improvements,
+            template_grants,
+            ability_generation: AbilityGenerationState::from(ability_generation),
             resources: self.resources,
+            dirty: granted,
             ..State::default()
         }
     }
@@ -87,10 +139,44 @@ impl CharacterPersistenceConfig {
     fn store(&self) -> Result<Store, LoadError> {
         Store::new(self.storage_root.clone()).map_err(|e| LoadError::Store(e))
     }
+
+    pub fn character_id(&self) -> String {
+        self.character_id.clone()
+    }
+
+    /// Whether the character has been saved before.
+    pub fn exists(&self) -> bool {
+        std::path::Path::new(&self.storage_root)
+            .join(CharacterPersistence::key(self.character_id.clone()))
+            .exists()
+    }
+
+    /// Earlier saves of the character, newest first.
+    pub async fn backups(self) -> Result<Vec<Backup>, LoadError> {
+        self.store()?
+            .backups(CharacterPersistence::key(self.character_id.clone()))
+            .await
+            .map_err(LoadError::Store)
+    }
+
+    /// Puts an earlier save back in place, then loads it.
+    pub async fn restore(self, backup: Backup) -> Result<LoadData, LoadError> {
+        self.store()?
+            .restore(
+                CharacterPersistence::key(self.character_id.clone()),
+                &backup,
+            )
+            .await
+            .map_err(LoadError::Store)?;
+        self.load().await
+    }
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct CharacterPersistence {
+    /// Written as `SCHEMA_VERSION` on every save; older files are upgraded as they load.
+    #[serde(default)]
+    schema_version: u64,
     name: Name,
     description: Description,
     ability_scores: AbilityScores,
@@ -99,29 +185,210 @@ pub struct CharacterPersistence {
     proficiencies: Proficiencies,
     features: Vec<Feature>,
     config: CharacterPersistenceConfig,
+    #[serde(default)]
+    concentration: Concentration,
+    #[serde(default)]
+    spells: Spellbook,
+    #[serde(default)]
+    timed_effects: TimedEffects,
+    #[serde(default)]
+    experience: Experience,
+    /// Feat templates taken, by name.
+    #[serde(default)]
+    feats: Vec<String>,
+    #[serde(default)]
+    ability_generation: Option<AbilityGeneration>,
+    /// Ability score improvements and feats taken at class levels.
+    #[serde(default)]
+    improvements: Vec<AbilityImprovement>,
+    #[serde(default)]
+    granted: TemplateGrants,
+}
+
+/// What the character's templates added to it, by name, so that it can be taken away again once
+/// no template grants it. What the character had before is left alone.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct TemplateGrants {
+    #[serde(default)]
+    features: Vec<String>,
+    #[serde(default)]
+    languages: Vec<String>,
+    #[serde(default)]
+    tools: Vec<String>,
 }
 
 impl CharacterPersistence {
-    pub fn from(
+    /// The character as `state` has it, to save.
+    pub fn of(state: &State) -> CharacterPersistence {
+        CharacterPersistence {
+            schema_version: SCHEMA_VERSION,
+            name: state.name.clone(),
+            description: state.description.clone(),
+            ability_scores: state.ability_scores.persistable(),
+            classes: state.classes.persistable(),
+            hit_points: state.hit_points.persistable(),
+            proficiencies: state.proficiencies.clone(),
+            features: state.features.persistable(),
+            config: state.config.clone(),
+            concentration: state.concentration.persistable(),
+            spells: state.spellbook.persistable(),
+            timed_effects: state.timed_effects.persistable(),
+            experience: state.experience.persistable(),
+            feats: state.feats.clone(),
+            ability_generation: state.ability_generation.persistable(),
+            improvements: state.improvements.clone(),
+            granted: state.template_grants.clone(),
+        }
+    }
+
+    /// A character with no levels yet.
+    pub fn new(
+        config: CharacterPersistenceConfig,
         name: Name,
         description: Description,
         ability_scores: AbilityScores,
-        classes: Vec<Class>,
-        hit_points: HitPoints,
-        proficiencies: Proficiencies,
-        features: Vec<Feature>,
-        config: CharacterPersistenceConfig,
+        ability_generation: Option<AbilityGeneration>,
     ) -> CharacterPersistence {
         CharacterPersistence {
+            config,
             name,
             description,
             ability_scores,
-            classes,
-            hit_points,
-            proficiencies,
-            features,
+            ability_generation,
+            ..CharacterPersistence::default()
+        }
+    }
+
+    /// The same character saved under another id and name.
+    pub fn with_identity(
+        self,
+        config: CharacterPersistenceConfig,
+        name: Name,
+    ) -> CharacterPersistence {
+        CharacterPersistence {
             config,
+            name,
+            ..self
+        }
+    }
+
+    pub fn config(&self) -> CharacterPersistenceConfig {
+        self.config.clone()
+    }
+
+    pub fn spells(&self) -> &Spellbook {
+        &self.spells
+    }
+
+    pub fn experience(&self) -> &Experience {
+        &self.experience
+    }
+
+    /// The path of every feature, from each top level feature down.
+    pub fn feature_paths(&self) -> Vec<FeaturePath> {
+        self.features
+            .iter()
+            .flat_map(|feature| feature.paths(&FeaturePath::empty()))
+            .collect()
+    }
+
+    pub fn total_level(&self) -> isize {
+        self.classes.iter().map(|class| class.level()).sum()
+    }
+
+    pub fn class(&self, name: &str) -> Option<&Class> {
+        self.classes.iter().find(|class| class.name() == name)
+    }
+
+    /// Adds a level in the named class, taking it at level 1 if the character doesn't have it.
+    /// Returns the new class level.
+    pub fn level_up_class(&mut self, name: &str, progression: &ClassProgression) -> isize {
+        self.experience.level_up();
+        match self.classes.iter_mut().find(|class| class.name() == name) {
+            Some(class) => {
+                class.level_up();
+                class.level()
+            }
+            None => {
+                let mut class = Class::new(name.to_string(), 1);
+                if let Some(caster) = progression.caster() {
+                    class = class.with_caster(caster);
+                }
+                if let Some(ability) = progression.spellcasting_ability() {
+                    class = class.with_spellcasting_ability(ability);
+                }
+                self.classes.push(class);
+                1
+            }
+        }
+    }
+
+    pub fn set_subclass(&mut self, name: &str, subclass: String) {
+        if let Some(class) = self.classes.iter_mut().find(|class| class.name() == name) {
+            *class = class.clone().with_subclass(Some(subclass));
+        }
+    }
+
+    pub fn gain_hit_points(&mut self, hit_points: isize) {
+        self.hit_points.gain(hit_points);
+    }
+
+    pub fn improve(&mut self, improvement: AbilityImprovement) {
+        self.improvements.push(improvement);
+    }
+
+    /// Keeps what the character's class, subclass, race, background and feat templates grant in
+    /// step with them. Features, languages and tools an earlier template added that none grants
+    /// any more are removed. Those the character doesn't have yet are added, features referenced
+    /// by name under a feature named for the template, along with speed and size.
+    pub fn apply_templates(&mut self, templates: &Templates) -> bool {
+        let granted = templates.granted(
+            &self.classes,
+            self.description.race(),
+            self.description.background(),
+            &taken_feats(&self.feats, &self.improvements),
+        );
+        let take_stale = |names: &mut Vec<String>, pick: fn(&Grants) -> &Vec<String>| {
+            let (kept, stale) = names.drain(..).partition::<Vec<String>, _>(|name| {
+                granted
+                    .iter()
+                    .any(|granted| pick(&granted.grants).contains(name))
+            });
+            *names = kept;
+            stale
+        };
+        let stale_features = take_stale(&mut self.granted.features, Grants::features);
+        let stale_languages = take_stale(&mut self.granted.languages, Grants::languages);
+        let stale_tools = take_stale(&mut self.granted.tools, Grants::tools);
+
+        let mut changed = false;
+        for name in stale_features.iter() {
+            changed = revoke(&mut self.features, name) || changed;
         }
+        changed = self.proficiencies.revoke(&stale_languages, &stale_tools) || changed;
+
+        for granted in granted {
+            let grants = &granted.grants;
+            let group = Feature::named(&granted.name).with_description(granted.kind.to_string());
+            let features = grant(&mut self.features, group, grants.features());
+            let (languages, tools) = self.proficiencies.grant(grants.languages(), grants.tools());
+            changed = !(features.is_empty() && languages.is_empty() && tools.is_empty()) || changed;
+            self.granted.features.extend(features);
+            self.granted.languages.extend(languages);
+            self.granted.tools.extend(tools);
+            changed = self.description.derive(grants.speed(), grants.size()) || changed;
+        }
+        changed
+    }
+
+    /// Adds features by name under `group`, as templates do. They are the character's own, such
+    /// as its chosen skills, so changing templates leaves them.
+    pub fn grant_features(&mut self, group: Feature, names: &[String]) -> bool {
+        !grant(&mut self.features, group, names).is_empty()
+    }
+
+    pub fn learn_spell(&mut self, name: String) {
+        self.spells.learn(name);
     }
 
     fn default_from(config: CharacterPersistenceConfig) -> CharacterPersistence {
@@ -131,38 +398,254 @@ impl CharacterPersistence {
         }
     }
 
+    /// A character that hasn't been saved yet starts out as a default one. A file that can't
+    /// be read is left as it is, to be fixed or quarantined.
     pub async fn load(
         config: CharacterPersistenceConfig,
     ) -> Result<CharacterPersistence, LoadError> {
         let store = config.store()?;
         let key = CharacterPersistence::key(config.character_id.clone());
-        match store
-            .load(key)
-            .await
-            .map_err(|e| LoadError::Store(e))
-            .and_then(|content| {
-                serde_json::from_str(content.as_str())
-                    .map_err(|e| LoadError::Serialize(e.to_string()))
-            }) {
-            Ok(r) => Ok(r),
-            Err(_) => {
+        match store.load(key.clone()).await {
+            Ok(content) => {
+                let read: Read<CharacterPersistence> =
+                    migration::read(&key, &content).map_err(|e| match e {
+                        ReadError::Parse(e) => LoadError::Parse(e),
+                        ReadError::Schema(e) => LoadError::Schema(e),
+                    })?;
+                if let Some(version) = read.upgraded_from {
+                    let upgraded = read.value.to_json()?;
+                    migration::write_back(&store, key, content, version, upgraded)
+                        .await
+                        .map_err(LoadError::Store)?;
+                }
+                Ok(read.value)
+            }
+            Err(StoreError::NotFound(_)) => {
                 let default = CharacterPersistence::default_from(config);
                 default.clone().save().await.map(|_| default)
             }
+            Err(e) => Err(LoadError::Store(e)),
         }
     }
 
+    /// Moves the character's file under `quarantine/`, returning where it went.
+    pub async fn quarantine(config: CharacterPersistenceConfig) -> Result<String, LoadError> {
+        config
+            .store()?
+            .quarantine(CharacterPersistence::key(config.character_id))
+            .await
+            .map_err(LoadError::Store)
+    }
+
+    /// Saves a new character, then loads it back to open it.
+    pub async fn create(self) -> Result<LoadData, LoadError> {
+        let config = self.config.clone();
+        self.save().await?;
+        config.load().await
+    }
+
     pub async fn save(self) -> Result<(), LoadError> {
         let key = CharacterPersistence::key(self.config.character_id.clone());
-        let json =
-            serde_json::to_string_pretty(&self).map_err(|e| LoadError::Serialize(e.to_string()))?;
+        let json = self.to_json()?;
 
         let store = self.config.store()?;
 
         store.save(key, json).await.map_err(|e| LoadError::Store(e))
     }
 
-    fn key(character_id: String) -> String {
+    fn to_json(&self) -> Result<String, LoadError> {
+        let mut character = self.clone();
+        character.schema_version = SCHEMA_VERSION;
+        serde_json::to_string_pretty(&character).map_err(|e| LoadError::Serialize(e.to_string()))
+    }
+
+    pub fn key(character_id: String) -> String {
         format!("characters/{}.json", character_id)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::store::test::TestDirectory;
+
+    #[actix_rt::test]
+    async fn unreadable_characters_are_never_replaced() {
+        let root = TestDirectory::new("persistence-test");
+        let path = |file: &str| format!("{}/{}", root.0, file);
+        std::fs::create_dir_all(path("characters")).unwrap();
+        let broken = "{\n  \"name\": { \"name\": \"Vynne\" },\n  \"classes\": [\n}";
+        std::fs::write(path("characters/vynne.json"), broken).unwrap();
+
+        let vynne = CharacterPersistenceConfig::new(root.0.clone(), "vynne".to_string());
+        match CharacterPersistence::load(vynne.clone()).await {
+            Err(LoadError::Parse(e)) => assert_eq!((e.line, e.column), (4, 1)),
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+        assert_eq!(
+            std::fs::read_to_string(path("characters/vynne.json")).unwrap(),
+            broken
+        );
+
+        let quarantined = CharacterPersistence::quarantine(vynne).await.unwrap();
+        assert!(quarantined.starts_with("quarantine/characters/vynne.json."));
+        assert!(!std::path::Path::new(&path("characters/vynne.json")).exists());
+
+        let new = CharacterPersistenceConfig::new(root.0.clone(), "new".to_string());
+        assert!(CharacterPersistence::load(new.clone()).await.is_ok());
+        assert!(new.exists());
+    }
+
+    #[actix_rt::test]
+    async fn older_characters_are_upgraded_after_a_backup() {
+        let root = TestDirectory::new("migration-test");
+        let path = |file: &str| format!("{}/{}", root.0, file);
+        let fixture = |file: &str| format!("fixtures/schema-v0/{}", file);
+        std::fs::create_dir_all(path("characters")).unwrap();
+        std::fs::copy(fixture("template.json"), path("template.json")).unwrap();
+        for id in vec!["vynne", "bashid"] {
+            let file = format!("characters/{}.json", id);
+            std::fs::copy(fixture(&file), path(&file)).unwrap();
+            let original = std::fs::read_to_string(fixture(&file)).unwrap();
+            assert!(original.contains("show_reset_chidren"));
+
+            let config = CharacterPersistenceConfig::new(root.0.clone(), id.to_string());
+            let loaded = config.clone().load().await.unwrap();
+            assert!(!loaded.character.features.is_empty());
+            assert_eq!(
+                std::fs::read_to_string(path(&format!("migrated/{}.v0", file))).unwrap(),
+                original
+            );
+            let upgraded = std::fs::read_to_string(path(&file)).unwrap();
+            assert!(upgraded.contains("show_reset_children"));
+            assert!(!upgraded.contains("show_reset_chidren"));
+            let document: serde_json::Value = serde_json::from_str(&upgraded).unwrap();
+            assert_eq!(migration::schema_version(&document), SCHEMA_VERSION);
+
+            config.load().await.unwrap();
+            assert_eq!(std::fs::read_to_string(path(&file)).unwrap(), upgraded);
+        }
+        let template = std::fs::read_to_string(path("template.json")).unwrap();
+        assert!(template.contains("show_reset_children"));
+        assert!(!template.contains("show_reset_chidren"));
+        assert!(std::path::Path::new(&path("migrated/template.json.v0")).exists());
+    }
+
+    #[test]
+    fn template_grants_follow_the_templates_taken() {
+        let templates: Templates = serde_json::from_str(
+            r#"{
+                "races": {
+                    "Half-Elf": { "features": ["Darkvision", "Fey Ancestry"],
+                        "languages": ["Common", "Elvish"] },
+                    "Hill Dwarf": { "features": ["Darkvision", "Dwarven Resilience"],
+                        "languages": ["Common", "Dwarvish"] }
+                }
+            }"#,
+        )
+        .unwrap();
+        let names = |features: &Vec<Feature>| {
+            features
+                .iter()
+                .flat_map(|feature| feature.paths(&FeaturePath::empty()))
+                .map(|path| path.breadcrumb())
+                .collect::<Vec<String>>()
+        };
+        let mut character = CharacterPersistence {
+            description: Description::new("Half-Elf".to_string(), None),
+            features: vec![
+                Feature::named("Feats").with_children(vec![Feature::named("Fey Ancestry")])
+            ],
+            ..CharacterPersistence::default()
+        };
+
+        assert!(character.apply_templates(&templates));
+        assert!(!character.apply_templates(&templates));
+        assert_eq!(
+            names(&character.features),
+            vec![
+                "Feats",
+                "Feats › Fey Ancestry",
+                "Half-Elf",
+                "Half-Elf › Darkvision"
+            ]
+        );
+
+        character.description = Description::new("Hill Dwarf".to_string(), None);
+        assert!(character.apply_templates(&templates));
+        assert_eq!(
+            names(&character.features),
+            vec![
+                "Feats",
+                "Feats › Fey Ancestry",
+                "Half-Elf",
+                "Half-Elf › Darkvision",
+                "Hill Dwarf",
+                "Hill Dwarf › Dwarven Resilience"
+            ]
+        );
+        assert_eq!(character.granted.languages, vec!["Common", "Dwarvish"]);
+
+        character.description = Description::new(String::new(), None);
+        assert!(character.apply_templates(&templates));
+        assert_eq!(
+            names(&character.features),
+            vec!["Feats", "Feats › Fey Ancestry"]
+        );
+        assert_eq!(character.granted, TemplateGrants::default());
+    }
+
+    #[test]
+    fn sorcerers_built_from_templates_spend_sorcery_points() {
+        use crate::core::feature::FeatureMessage;
+        use crate::core::formula::FormulaContext;
+
+        let content = std::fs::read_to_string(".store/template.json").unwrap();
+        let templates: Templates = migration::read("template.json", &content).unwrap().value;
+        let mut character = CharacterPersistence::default();
+        for _ in 0..3 {
+            character.level_up_class("Sorcerer", templates.class("Sorcerer").unwrap());
+        }
+        character.set_subclass("Sorcerer", "Wild Magic".to_string());
+        character.apply_templates(&templates);
+
+        let load = |character: &CharacterPersistence| {
+            let mut features =
+                FeaturesState::from(character.features.clone(), templates.features());
+            let ability_scores = AbilityScores::default().to_state().modified();
+            let classes = Classes::from(character.classes.clone());
+            features.evaluate_formulas(&FormulaContext::new(&ability_scores, &classes));
+            features
+        };
+        let current = |features: &FeaturesState, path: [&str; 2]| {
+            let path = path
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<String>>();
+            features.find_slot(&path).map(|slot| slot.current())
+        };
+        let mut features = load(&character);
+        assert_eq!(
+            current(&features, ["Font of Magic", "Sorcery Points"]),
+            Some(3)
+        );
+        assert_eq!(
+            current(&features, ["Wild Magic", "Tides of Chaos"]),
+            Some(1)
+        );
+
+        let quickened = features.path_of("Quickened Spell").unwrap();
+        assert!(features.update(FeatureMessage::Activate(quickened)));
+        assert_eq!(
+            current(&features, ["Font of Magic", "Sorcery Points"]),
+            Some(1)
+        );
+
+        character.features = features.persistable();
+        let reloaded = load(&character);
+        assert_eq!(
+            current(&reloaded, ["Font of Magic", "Sorcery Points"]),
+            Some(1)
+        );
+    }
+}
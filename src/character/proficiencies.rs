@@ -62,6 +62,44 @@ pub struct Proficiencies {
 }
 
 impl Proficiencies {
+    /// Adds full proficiency in each language and tool the character doesn't have yet,
+    /// returning the languages and the tools added.
+    pub fn grant(&mut self, languages: &[String], tools: &[String]) -> (Vec<String>, Vec<String>) {
+        let mut added = vec![];
+        for (proficiencies, names) in
+            vec![(&mut self.languages, languages), (&mut self.tools, tools)]
+        {
+            let mut missing = vec![];
+            for name in names {
+                if !proficiencies
+                    .iter()
+                    .any(|p| p.name.eq_ignore_ascii_case(name))
+                {
+                    proficiencies.push(Proficiency {
+                        name: name.clone(),
+                        proficiency_type: ProficiencyType::Full,
+                    });
+                    missing.push(name.clone());
+                }
+            }
+            added.push(missing);
+        }
+        let tools = added.pop().unwrap_or_default();
+        let languages = added.pop().unwrap_or_default();
+        (languages, tools)
+    }
+
+    /// Removes the languages and tools named.
+    pub fn revoke(&mut self, languages: &[String], tools: &[String]) -> bool {
+        let mut changed = false;
+        for (proficiencies, names) in [(&mut self.languages, languages), (&mut self.tools, tools)] {
+            let before = proficiencies.len();
+            proficiencies.retain(|p| !names.iter().any(|name| p.name.eq_ignore_ascii_case(name)));
+            changed = proficiencies.len() != before || changed;
+        }
+        changed
+    }
+
     pub fn view(&mut self) -> Column<Message> {
         let Proficiencies { tools, languages } = self;
         Column::new()
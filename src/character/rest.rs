@@ -0,0 +1,32 @@
+use crate::character::Message;
+use crate::core::slot::Recovery;
+use iced::{button, Align, Button, Row, Text};
+
+#[derive(Debug, Clone, Default)]
+pub struct RestControls {
+    short_rest_button: button::State,
+    long_rest_button: button::State,
+}
+
+impl RestControls {
+    pub fn view(&mut self) -> Row<Message> {
+        let RestControls {
+            short_rest_button,
+            long_rest_button,
+        } = self;
+
+        Row::new()
+            .spacing(20)
+            .align_items(Align::Center)
+            .push(
+                Button::new(short_rest_button, Text::new("Short Rest").size(16))
+                    .on_press(Message::Rest(Recovery::ShortRest))
+                    .padding(8),
+            )
+            .push(
+                Button::new(long_rest_button, Text::new("Long Rest").size(16))
+                    .on_press(Message::Rest(Recovery::LongRest))
+                    .padding(8),
+            )
+    }
+}
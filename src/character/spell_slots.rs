@@ -0,0 +1,155 @@
+use crate::character::class::Classes;
+
+pub const SPELLCASTING_FEATURE: &str = "Spellcasting";
+pub const SPELL_SLOTS_FEATURE: &str = "Spell Slots";
+pub const PACT_SLOTS_FEATURE: &str = "Pact Slots";
+
+/// Slots of each spell level (1st through 9th) by caster level, from the multiclass
+/// spellcaster table.
+const SPELL_SLOTS: [[isize; 9]; 20] = [
+    [2, 0, 0, 0, 0, 0, 0, 0, 0],
+    [3, 0, 0, 0, 0, 0, 0, 0, 0],
+    [4, 2, 0, 0, 0, 0, 0, 0, 0],
+    [4, 3, 0, 0, 0, 0, 0, 0, 0],
+    [4, 3, 2, 0, 0, 0, 0, 0, 0],
+    [4, 3, 3, 0, 0, 0, 0, 0, 0],
+    [4, 3, 3, 1, 0, 0, 0, 0, 0],
+    [4, 3, 3, 2, 0, 0, 0, 0, 0],
+    [4, 3, 3, 3, 1, 0, 0, 0, 0],
+    [4, 3, 3, 3, 2, 0, 0, 0, 0],
+    [4, 3, 3, 3, 2, 1, 0, 0, 0],
+    [4, 3, 3, 3, 2, 1, 0, 0, 0],
+    [4, 3, 3, 3, 2, 1, 1, 0, 0],
+    [4, 3, 3, 3, 2, 1, 1, 0, 0],
+    [4, 3, 3, 3, 2, 1, 1, 1, 0],
+    [4, 3, 3, 3, 2, 1, 1, 1, 0],
+    [4, 3, 3, 3, 2, 1, 1, 1, 1],
+    [4, 3, 3, 3, 3, 1, 1, 1, 1],
+    [4, 3, 3, 3, 3, 2, 1, 1, 1],
+    [4, 3, 3, 3, 3, 2, 2, 1, 1],
+];
+
+/// The number of pact magic slots, and their level, by warlock level.
+const PACT_SLOTS: [(isize, isize); 20] = [
+    (1, 1),
+    (2, 1),
+    (2, 2),
+    (2, 2),
+    (2, 3),
+    (2, 3),
+    (2, 4),
+    (2, 4),
+    (2, 5),
+    (2, 5),
+    (3, 5),
+    (3, 5),
+    (3, 5),
+    (3, 5),
+    (3, 5),
+    (3, 5),
+    (4, 5),
+    (4, 5),
+    (4, 5),
+    (4, 5),
+];
+
+/// A slot a spell can be cast with.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum CastingSlot {
+    Spell(isize),
+    Pact(isize),
+}
+
+impl CastingSlot {
+    pub fn level(&self) -> isize {
+        match self {
+            CastingSlot::Spell(level) | CastingSlot::Pact(level) => *level,
+        }
+    }
+
+    pub fn path(&self) -> Vec<String> {
+        match self {
+            CastingSlot::Spell(level) => spell_slot_path(*level as usize),
+            CastingSlot::Pact(_) => pact_slot_path(),
+        }
+    }
+}
+
+impl std::fmt::Display for CastingSlot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CastingSlot::Spell(level) => write!(f, "Level {}", level),
+            CastingSlot::Pact(level) => write!(f, "Pact (level {})", level),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PactSlots {
+    pub count: isize,
+    pub level: isize,
+}
+
+/// Maximum slots for each spell level, 1st through 9th.
+pub fn spell_slots(classes: &Classes) -> Vec<isize> {
+    match classes.caster_level() {
+        level if level < 1 => vec![0; 9],
+        level => SPELL_SLOTS[(std::cmp::min(level, 20) - 1) as usize].to_vec(),
+    }
+}
+
+pub fn pact_slots(classes: &Classes) -> Option<PactSlots> {
+    match classes.pact_level() {
+        level if level < 1 => None,
+        level => {
+            let (count, level) = PACT_SLOTS[(std::cmp::min(level, 20) - 1) as usize];
+            Some(PactSlots { count, level })
+        }
+    }
+}
+
+pub fn spell_slot_path(spell_level: usize) -> Vec<String> {
+    vec![
+        SPELLCASTING_FEATURE.to_string(),
+        SPELL_SLOTS_FEATURE.to_string(),
+        format!("Level {}", spell_level),
+    ]
+}
+
+pub fn pact_slot_path() -> Vec<String> {
+    vec![
+        SPELLCASTING_FEATURE.to_string(),
+        PACT_SLOTS_FEATURE.to_string(),
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::character::class::{CasterType, Class};
+
+    fn caster(name: &str, level: isize, caster: CasterType) -> Class {
+        Class::new(name.to_string(), level).with_caster(caster)
+    }
+
+    #[test]
+    fn single_class_half_casters_round_up() {
+        let paladin = Classes::from(vec![caster("Paladin", 5, CasterType::Half)]);
+        assert_eq!(spell_slots(&paladin)[0..3], [4, 2, 0]);
+
+        let paladin = Classes::from(vec![caster("Paladin", 1, CasterType::Half)]);
+        assert_eq!(spell_slots(&paladin), vec![0; 9]);
+    }
+
+    #[test]
+    fn multiclass_casters_share_the_table() {
+        let classes = Classes::from(vec![
+            caster("Sorcerer", 5, CasterType::Full),
+            caster("Paladin", 5, CasterType::Half),
+            caster("Warlock", 3, CasterType::Pact),
+            Class::new("Fighter".to_string(), 2),
+        ]);
+        assert_eq!(spell_slots(&classes), vec![4, 3, 3, 1, 0, 0, 0, 0, 0]);
+        assert_eq!(pact_slots(&classes), Some(PactSlots { count: 2, level: 2 }));
+    }
+}
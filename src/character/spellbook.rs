@@ -0,0 +1,437 @@
+use crate::character::class::Preparation;
+use crate::character::spell_slots::CastingSlot;
+use crate::character::spellcasting::Spellcasting;
+use crate::character::Message;
+use crate::core::feature::Feature;
+use crate::resources::spell::Spells;
+use iced::{button, pick_list, Align, Button, Column, Length, PickList, Row, Text};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub const SPELLS_FEATURE: &str = "Spells";
+/// The reserved key the generated "Spells" feature is kept under, apart from any feature of the
+/// character's own with that name.
+pub const SPELLS_KEY: &str = "Spellbook";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Spellbook {
+    #[serde(default)]
+    known: Vec<String>,
+    #[serde(default)]
+    prepared: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum SpellbookMessage {
+    Select(String),
+    Learn,
+    Forget(String),
+    TogglePrepared(String),
+    SelectSlot(String, CastingSlot),
+    /// Cast a spell, using a slot unless it is a cantrip.
+    Cast(String, Option<CastingSlot>),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SpellbookState {
+    spellbook: Spellbook,
+    selected: Option<String>,
+    spell_list: pick_list::State<String>,
+    learn_button: button::State,
+    spell_controls: Vec<SpellControls>,
+    selected_slots: HashMap<String, CastingSlot>,
+    notice: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct SpellControls {
+    prepare_button: button::State,
+    forget_button: button::State,
+    slot_list: pick_list::State<CastingSlot>,
+    cast_button: button::State,
+}
+
+type IsDirty = bool;
+
+impl Spellbook {
+    pub fn knows(&self, name: &str) -> bool {
+        self.known.iter().any(|known| known == name)
+    }
+
+    pub fn known(&self) -> &Vec<String> {
+        &self.known
+    }
+
+    pub fn prepares(&self, name: &str) -> bool {
+        self.prepared.iter().any(|prepared| prepared == name)
+    }
+
+    pub fn learn(&mut self, name: String) {
+        if !self.knows(&name) {
+            self.known.push(name);
+        }
+    }
+
+    pub fn to_state(self) -> SpellbookState {
+        SpellbookState {
+            spellbook: self,
+            ..SpellbookState::default()
+        }
+    }
+}
+
+impl SpellbookState {
+    pub fn persistable(&self) -> Spellbook {
+        self.spellbook.clone()
+    }
+
+    /// Spells that can be cast right now: every known spell, except that spells a class
+    /// prepares have to be prepared first.
+    pub fn castable(&self, spells: &Spells, preparation: &[Preparation]) -> Vec<String> {
+        let Spellbook { known, prepared } = &self.spellbook;
+        known
+            .iter()
+            .filter(|name| {
+                preparing_class(name, spells, preparation).is_none() || prepared.contains(name)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// The "Spells" feature, with one child per castable spell found in the catalog. Each spell
+    /// is cast as the first class with it on its spell list, or the first spellcasting class.
+    pub fn feature(
+        &self,
+        spells: &Spells,
+        preparation: &[Preparation],
+        spellcasting: &Vec<Spellcasting>,
+    ) -> Option<Feature> {
+        let children = self
+            .castable(spells, preparation)
+            .into_iter()
+            .filter_map(|name| {
+                spells.get(&name).map(|spell| {
+                    let casting = spellcasting
+                        .iter()
+                        .find(|casting| spell.on_list_of(casting.class()))
+                        .or_else(|| spellcasting.first());
+                    spell.to_feature(&name, casting)
+                })
+            })
+            .collect::<Vec<Feature>>();
+
+        if children.is_empty() {
+            None
+        } else {
+            Some(Feature::named(SPELLS_FEATURE).with_children(children))
+        }
+    }
+
+    /// Shows a message about the last cast, such as why it was refused.
+    pub fn notify(&mut self, notice: Option<String>) {
+        self.notice = notice;
+    }
+
+    pub fn update(
+        &mut self,
+        message: SpellbookMessage,
+        spells: &Spells,
+        preparation: &[Preparation],
+    ) -> IsDirty {
+        let Spellbook { known, prepared } = &mut self.spellbook;
+        match message {
+            SpellbookMessage::Select(name) => {
+                self.selected = Some(name);
+                false
+            }
+            SpellbookMessage::Learn => match self.selected.take() {
+                Some(name) if !known.contains(&name) => {
+                    known.push(name);
+                    true
+                }
+                _ => false,
+            },
+            SpellbookMessage::Forget(name) => {
+                let count = known.len();
+                known.retain(|n| n != &name);
+                prepared.retain(|n| n != &name);
+                count != known.len()
+            }
+            SpellbookMessage::TogglePrepared(name) => {
+                if prepared.contains(&name) {
+                    prepared.retain(|n| n != &name);
+                    true
+                } else if known.contains(&name)
+                    && preparing_class(&name, spells, preparation)
+                        .and_then(|class| {
+                            let count = prepared_count(prepared, spells, preparation, &class.class);
+                            Some((count as isize) < class.limit?)
+                        })
+                        .unwrap_or(false)
+                {
+                    prepared.push(name);
+                    true
+                } else {
+                    false
+                }
+            }
+            SpellbookMessage::SelectSlot(name, slot) => {
+                self.selected_slots.insert(name, slot);
+                false
+            }
+            SpellbookMessage::Cast(_, _) => false,
+        }
+    }
+
+    /// `slots` are the slots the character has, whether or not any are left.
+    pub fn view(
+        &mut self,
+        spells: &Spells,
+        preparation: &[Preparation],
+        slots: &Vec<CastingSlot>,
+    ) -> Column<Message> {
+        let castable = self.castable(spells, preparation);
+        let SpellbookState {
+            spellbook,
+            selected,
+            spell_list,
+            learn_button,
+            spell_controls,
+            selected_slots,
+            notice,
+        } = self;
+        let Spellbook { known, prepared } = spellbook;
+
+        let mut column = Column::new()
+            .spacing(4)
+            .push(Row::new().push(Text::new("Spells Known").size(24)));
+
+        for class in preparation.iter() {
+            if let Some(limit) = class.limit {
+                column = column.push(
+                    Text::new(format!(
+                        "Prepared {} / {} ({})",
+                        prepared_count(prepared, spells, preparation, &class.class),
+                        limit,
+                        class.class
+                    ))
+                    .size(16),
+                );
+            }
+        }
+
+        if let Some(notice) = notice {
+            column = column.push(Text::new(notice.clone()).size(16));
+        }
+
+        spell_controls.resize_with(known.len(), SpellControls::default);
+        for (name, controls) in known.iter().zip(spell_controls.iter_mut()) {
+            let SpellControls {
+                prepare_button,
+                forget_button,
+                slot_list,
+                cast_button,
+            } = controls;
+            let level = spells.get(name).map(|s| s.level());
+            let label = match level {
+                Some(0) => format!("{} (cantrip)", name),
+                Some(level) => format!("{} (level {})", name, level),
+                None => format!("{} (not in catalog)", name),
+            };
+
+            let mut row = Row::new()
+                .spacing(20)
+                .align_items(Align::Center)
+                .push(Text::new(label).size(16).width(Length::FillPortion(2)));
+            if castable.contains(name) {
+                match level {
+                    Some(0) => {
+                        row = row.push(
+                            Button::new(cast_button, Text::new("Cast").size(16))
+                                .on_press(Message::Spellbook(SpellbookMessage::Cast(
+                                    name.clone(),
+                                    None,
+                                )))
+                                .padding(8),
+                        );
+                    }
+                    Some(level) => {
+                        let options = slots
+                            .iter()
+                            .filter(|slot| slot.level() >= level)
+                            .cloned()
+                            .collect::<Vec<CastingSlot>>();
+                        let slot = selected_slots
+                            .get(name)
+                            .filter(|slot| options.contains(slot))
+                            .or_else(|| options.first())
+                            .cloned();
+                        let spell_name = name.clone();
+                        row = row.push(PickList::new(
+                            slot_list,
+                            options,
+                            slot.clone(),
+                            move |slot| {
+                                Message::Spellbook(SpellbookMessage::SelectSlot(
+                                    spell_name.clone(),
+                                    slot,
+                                ))
+                            },
+                        ));
+                        let mut cast =
+                            Button::new(cast_button, Text::new("Cast").size(16)).padding(8);
+                        if slot.is_some() {
+                            cast = cast.on_press(Message::Spellbook(SpellbookMessage::Cast(
+                                name.clone(),
+                                slot,
+                            )));
+                        }
+                        row = row.push(cast);
+                    }
+                    None => {}
+                }
+            }
+            if level != Some(0) && preparing_class(name, spells, preparation).is_some() {
+                let text = if prepared.contains(name) {
+                    "Unprepare"
+                } else {
+                    "Prepare"
+                };
+                row = row.push(
+                    Button::new(prepare_button, Text::new(text).size(16))
+                        .on_press(Message::Spellbook(SpellbookMessage::TogglePrepared(
+                            name.clone(),
+                        )))
+                        .padding(8),
+                );
+            }
+            row = row.push(
+                Button::new(forget_button, Text::new("Forget").size(16))
+                    .on_press(Message::Spellbook(SpellbookMessage::Forget(name.clone())))
+                    .padding(8),
+            );
+            column = column.push(row);
+        }
+
+        let options = spells
+            .names()
+            .into_iter()
+            .filter(|name| !known.contains(name))
+            .collect::<Vec<String>>();
+        if !options.is_empty() {
+            column = column.push(
+                Row::new()
+                    .spacing(20)
+                    .align_items(Align::Center)
+                    .push(PickList::new(
+                        spell_list,
+                        options,
+                        selected.clone(),
+                        |name| Message::Spellbook(SpellbookMessage::Select(name)),
+                    ))
+                    .push(
+                        Button::new(learn_button, Text::new("Learn").size(16))
+                            .on_press(Message::Spellbook(SpellbookMessage::Learn))
+                            .padding(8),
+                    ),
+            );
+        }
+
+        column
+    }
+}
+
+/// The class that has to prepare the spell before casting it, or `None` when it is always
+/// ready: cantrips, and spells on the list of a class that casts the spells it knows. A spell
+/// on no caster's list counts as being on all of them.
+fn preparing_class<'a>(
+    name: &str,
+    spells: &Spells,
+    preparation: &'a [Preparation],
+) -> Option<&'a Preparation> {
+    let spell = spells.get(name);
+    if spell.map(|s| s.level() == 0).unwrap_or(false) {
+        return None;
+    }
+    let on_list = preparation
+        .iter()
+        .filter(|class| spell.map(|s| s.on_list_of(&class.class)).unwrap_or(true))
+        .collect::<Vec<&Preparation>>();
+    let classes = if on_list.is_empty() {
+        preparation.iter().collect()
+    } else {
+        on_list
+    };
+    if classes.iter().any(|class| class.limit.is_none()) {
+        None
+    } else {
+        classes.first().cloned()
+    }
+}
+
+/// Prepared spells counting towards `class`'s limit. Cantrips are always ready and don't count.
+fn prepared_count(
+    prepared: &Vec<String>,
+    spells: &Spells,
+    preparation: &[Preparation],
+    class: &str,
+) -> usize {
+    prepared
+        .iter()
+        .filter(|name| {
+            preparing_class(name, spells, preparation)
+                .map(|preparing| preparing.class == class)
+                .unwrap_or(false)
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn only_classes_that_prepare_spells_need_them_prepared() {
+        let spell = |level: isize, class: &str| {
+            serde_json::json!({
+                "level": level, "school": "", "casting_time": "", "range": "", "duration": "",
+                "classes": [class],
+            })
+        };
+        let spells: Spells = serde_json::from_value(serde_json::json!({ "spells": {
+            "Shield": spell(1, "Sorcerer"),
+            "Bless": spell(1, "Cleric"),
+            "Guiding Bolt": spell(1, "Cleric"),
+        }}))
+        .unwrap();
+        let preparation = vec![
+            Preparation {
+                class: "Sorcerer".to_string(),
+                limit: None,
+            },
+            Preparation {
+                class: "Cleric".to_string(),
+                limit: Some(1),
+            },
+        ];
+        let mut state = Spellbook {
+            known: vec![
+                "Shield".to_string(),
+                "Bless".to_string(),
+                "Guiding Bolt".to_string(),
+            ],
+            prepared: vec![],
+        }
+        .to_state();
+        assert_eq!(state.castable(&spells, &preparation), vec!["Shield"]);
+
+        let prepare = |name: &str| SpellbookMessage::TogglePrepared(name.to_string());
+        assert!(!state.update(prepare("Shield"), &spells, &preparation));
+        assert!(state.update(prepare("Bless"), &spells, &preparation));
+        assert!(!state.update(prepare("Guiding Bolt"), &spells, &preparation));
+        assert_eq!(
+            state.castable(&spells, &preparation),
+            vec!["Shield", "Bless"]
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use crate::character::Message;
+use crate::core::ability_score::Ability;
+use crate::core::stat::ModifiedValue;
+use iced::{Column, HorizontalAlignment, Length, Row, Text, VerticalAlignment};
+
+/// A class's spell save DC and spell attack modifier, including effects on either.
+#[derive(Debug, Clone)]
+pub struct Spellcasting {
+    class: String,
+    ability: Ability,
+    save_dc: ModifiedValue,
+    attack: ModifiedValue,
+}
+
+impl Spellcasting {
+    pub fn new(
+        class: String,
+        ability: Ability,
+        save_dc: ModifiedValue,
+        attack: ModifiedValue,
+    ) -> Spellcasting {
+        Spellcasting {
+            class,
+            ability,
+            save_dc,
+            attack,
+        }
+    }
+
+    pub fn class(&self) -> &str {
+        &self.class
+    }
+
+    pub fn save_dc(&self) -> isize {
+        self.save_dc.value()
+    }
+
+    pub fn attack(&self) -> isize {
+        self.attack.value()
+    }
+}
+
+pub fn view<'a>(spellcasting: &Vec<Spellcasting>) -> Column<'a, Message> {
+    if spellcasting.is_empty() {
+        return Column::new();
+    }
+    let mut column = Column::new()
+        .spacing(4)
+        .push(Row::new().push(Text::new("Spellcasting").size(24)));
+
+    for casting in spellcasting {
+        let value = |text: String| {
+            Text::new(text)
+                .size(24)
+                .horizontal_alignment(HorizontalAlignment::Left)
+                .vertical_alignment(VerticalAlignment::Bottom)
+                .width(Length::FillPortion(1))
+        };
+        column = column
+            .push(
+                Row::new()
+                    .width(Length::Fill)
+                    .spacing(4)
+                    .push(
+                        Text::new(format!("{} ({})", casting.class, casting.ability))
+                            .size(16)
+                            .vertical_alignment(VerticalAlignment::Bottom)
+                            .width(Length::FillPortion(1)),
+                    )
+                    .push(value(format!("DC {}", casting.save_dc())))
+                    .push(value(format!("{:+} to hit", casting.attack()))),
+            )
+            .push(
+                Text::new(format!(
+                    "Save DC: {}; Attack: {}",
+                    casting.save_dc.breakdown(),
+                    casting.attack.breakdown()
+                ))
+                .size(12),
+            );
+    }
+
+    column
+}
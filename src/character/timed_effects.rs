@@ -0,0 +1,168 @@
+use crate::character::Message;
+use crate::core::effect::{ActiveEffect, Effect};
+use crate::core::feature_path::FeaturePath;
+use crate::core::slot::Recovery;
+use iced::{button, Align, Button, Column, Row, Text};
+use serde::{Deserialize, Serialize};
+
+/// Effects applied for a while by something that happened in play, such as a wild magic surge.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TimedEffects {
+    #[serde(default)]
+    effects: Vec<TimedEffect>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimedEffect {
+    source: FeaturePath,
+    text: String,
+    effects: Vec<Effect>,
+    /// The rest that ends the effects. Without one they last until ended by hand.
+    until: Option<Recovery>,
+}
+
+#[derive(Debug, Clone)]
+pub enum TimedEffectMessage {
+    End(usize),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TimedEffectsState {
+    timed_effects: TimedEffects,
+    end_buttons: Vec<button::State>,
+}
+
+type IsDirty = bool;
+
+impl TimedEffects {
+    pub fn to_state(self) -> TimedEffectsState {
+        TimedEffectsState {
+            timed_effects: self,
+            ..TimedEffectsState::default()
+        }
+    }
+}
+
+impl TimedEffect {
+    pub fn new(
+        source: FeaturePath,
+        text: String,
+        effects: Vec<Effect>,
+        until: Option<Recovery>,
+    ) -> TimedEffect {
+        TimedEffect {
+            source,
+            text,
+            effects,
+            until,
+        }
+    }
+}
+
+impl TimedEffectsState {
+    pub fn persistable(&self) -> TimedEffects {
+        self.timed_effects.clone()
+    }
+
+    pub fn active_effects(&self) -> Vec<ActiveEffect> {
+        self.timed_effects
+            .effects
+            .iter()
+            .flat_map(|timed| {
+                timed
+                    .effects
+                    .iter()
+                    .map(move |effect| ActiveEffect::new(effect.clone(), timed.source.clone()))
+            })
+            .collect()
+    }
+
+    pub fn add(&mut self, timed_effect: TimedEffect) {
+        self.timed_effects.effects.push(timed_effect);
+    }
+
+    /// Ends the effects that last until `rest`; a long rest also ends short rest effects.
+    pub fn rest(&mut self, rest: &Recovery) -> IsDirty {
+        let count = self.timed_effects.effects.len();
+        self.timed_effects
+            .effects
+            .retain(|timed| match (rest, &timed.until) {
+                (_, None) | (Recovery::ShortRest, Some(Recovery::LongRest)) => true,
+                _ => false,
+            });
+        count != self.timed_effects.effects.len()
+    }
+
+    pub fn update(&mut self, message: TimedEffectMessage) -> IsDirty {
+        match message {
+            TimedEffectMessage::End(index) => {
+                if index < self.timed_effects.effects.len() {
+                    self.timed_effects.effects.remove(index);
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+
+    pub fn view(&mut self) -> Column<Message> {
+        let TimedEffectsState {
+            timed_effects,
+            end_buttons,
+        } = self;
+
+        let mut column = Column::new().spacing(4);
+        end_buttons.resize_with(timed_effects.effects.len(), button::State::default);
+        for (index, (timed, end_button)) in timed_effects
+            .effects
+            .iter()
+            .zip(end_buttons.iter_mut())
+            .enumerate()
+        {
+            let until = match &timed.until {
+                Some(Recovery::ShortRest) => " (until a short rest)",
+                Some(Recovery::LongRest) => " (until a long rest)",
+                None => "",
+            };
+            column = column.push(
+                Row::new()
+                    .spacing(20)
+                    .align_items(Align::Center)
+                    .push(Text::new(format!("{}: {}{}", timed.source, timed.text, until)).size(16))
+                    .push(
+                        Button::new(end_button, Text::new("End").size(16))
+                            .on_press(Message::TimedEffect(TimedEffectMessage::End(index)))
+                            .padding(8),
+                    ),
+            );
+        }
+
+        column
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rests_end_effects_lasting_until_them() {
+        let mut state = TimedEffects::default().to_state();
+        let surge = FeaturePath::of(vec!["Wild Magic Surge".to_string()]);
+        for until in vec![Some(Recovery::ShortRest), Some(Recovery::LongRest), None] {
+            state.add(TimedEffect::new(
+                surge.clone(),
+                "".to_string(),
+                vec![],
+                until,
+            ));
+        }
+
+        assert!(state.rest(&Recovery::ShortRest));
+        assert_eq!(state.persistable().effects.len(), 2);
+        assert!(state.rest(&Recovery::LongRest));
+        assert_eq!(state.persistable().effects.len(), 1);
+        assert!(!state.rest(&Recovery::LongRest));
+    }
+}
@@ -0,0 +1,149 @@
+use crate::character::persistence::CharacterPersistenceConfig;
+use crate::character::Message;
+use crate::store::Backup;
+use iced::{button, pick_list, Align, Button, Column, Command, PickList, Row, Text};
+use std::fmt::{Display, Formatter};
+
+/// An earlier save of the character, labelled by how long ago it was replaced.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Version {
+    backup: Backup,
+    label: String,
+}
+
+impl Version {
+    fn of(backup: Backup, now: u128) -> Version {
+        let minutes = now.saturating_sub(backup.saved_at) / 60_000;
+        let label = match minutes {
+            0 => "less than a minute ago".to_string(),
+            1 => "1 minute ago".to_string(),
+            m if m < 60 => format!("{} minutes ago", m),
+            m if m < 60 * 24 => format!("{} hours ago", m / 60),
+            m => format!("{} days ago", m / (60 * 24)),
+        };
+        Version { backup, label }
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.label)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum VersionsMessage {
+    Show,
+    Listed(Result<Vec<Backup>, String>),
+    Select(Version),
+    Restore(Backup),
+    Hide,
+}
+
+/// Earlier saves of the character to go back to.
+#[derive(Debug, Clone, Default)]
+pub struct VersionsState {
+    open: bool,
+    versions: Vec<Version>,
+    selected: Option<Version>,
+    /// Why the earlier versions couldn't be listed.
+    error: Option<String>,
+    version_list: pick_list::State<Version>,
+    show_button: button::State,
+    restore_button: button::State,
+    hide_button: button::State,
+}
+
+impl VersionsState {
+    pub fn update(
+        &mut self,
+        message: VersionsMessage,
+        config: &CharacterPersistenceConfig,
+    ) -> Command<Message> {
+        match message {
+            VersionsMessage::Show => {
+                self.open = true;
+                self.error = None;
+                return Command::perform(config.clone().backups(), |backups| {
+                    Message::Versions(VersionsMessage::Listed(backups.map_err(|e| e.to_string())))
+                });
+            }
+            VersionsMessage::Listed(Ok(backups)) => {
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|time| time.as_millis())
+                    .unwrap_or_default();
+                self.versions = backups
+                    .into_iter()
+                    .map(|backup| Version::of(backup, now))
+                    .collect();
+                self.selected = self.versions.first().cloned();
+            }
+            VersionsMessage::Listed(Err(e)) => {
+                self.error = Some(format!("Cannot list earlier versions: {}", e));
+            }
+            VersionsMessage::Select(version) => self.selected = Some(version),
+            VersionsMessage::Restore(_) => {}
+            VersionsMessage::Hide => self.open = false,
+        }
+        Command::none()
+    }
+
+    /// Restoring waits while a save is still being written, so the save can't land after it.
+    pub fn view(&mut self, saving: bool) -> Column<'_, Message> {
+        let VersionsState {
+            open,
+            versions,
+            selected,
+            error,
+            version_list,
+            show_button,
+            restore_button,
+            hide_button,
+        } = self;
+
+        if !*open {
+            return Column::new().push(
+                Button::new(show_button, Text::new("Versions").size(16))
+                    .on_press(Message::Versions(VersionsMessage::Show))
+                    .padding(8),
+            );
+        }
+
+        let mut row = Row::new().spacing(20).align_items(Align::Center);
+        if let Some(error) = error {
+            row = row.push(Text::new(error.as_str()).size(16));
+        } else if versions.is_empty() {
+            row = row.push(Text::new("No earlier versions").size(16));
+        } else {
+            row = row.push(PickList::new(
+                version_list,
+                versions.clone(),
+                selected.clone(),
+                |version| Message::Versions(VersionsMessage::Select(version)),
+            ));
+            let mut restore = Button::new(
+                restore_button,
+                Text::new("Restore previous version").size(16),
+            )
+            .padding(8);
+            if let (Some(version), false) = (selected, saving) {
+                restore = restore.on_press(Message::Versions(VersionsMessage::Restore(
+                    version.backup.clone(),
+                )));
+            }
+            row = row.push(restore);
+        }
+
+        Column::new()
+            .spacing(8)
+            .push(Text::new("Versions").size(24))
+            .push(
+                row.push(
+                    Button::new(hide_button, Text::new("Hide").size(16))
+                        .on_press(Message::Versions(VersionsMessage::Hide))
+                        .padding(8),
+                ),
+            )
+    }
+}
@@ -1,19 +1,22 @@
-use crate::core::effect::Effect;
+use crate::core::effect::{ActiveEffect, Effect, EffectBonus};
+use crate::core::provenance::{Amount, Contribution, Source};
 use crate::core::roll::rollable::Rollable;
 use crate::core::roll::{Dice, Roll};
-use crate::util::format_modifier;
-use iced::{Column, Element, HorizontalAlignment, Length, Row, Text, VerticalAlignment};
+use crate::core::stat::{modify_up_to, ValueBonus};
+use crate::resources::template::TemplateKind;
+use iced::{
+    button, Button, Column, Element, HorizontalAlignment, Length, Row, Text, VerticalAlignment,
+};
 use serde::export::Formatter;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use std::fmt::{Debug, Display};
 
-#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
-#[serde(tag = "type")]
-pub enum AbilityScoreBonus {
-    Modifier { modifier: isize },
-    Become { value: isize },
-}
+/// The highest modifiers can raise an ability score to, unless an effect raises the maximum.
+pub const MAXIMUM: isize = 20;
+
+/// The root of the source path of effects from ability score improvements.
+pub const IMPROVEMENTS: &str = "Ability Score Improvement";
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct AbilityScores {
@@ -35,6 +38,11 @@ pub struct ModifiedAbilityScores {
     charisma: ModifiedAbilityScore,
 }
 
+#[derive(Debug, Clone)]
+pub enum AbilityScoreMessage {
+    ToggleBreakdown(Ability),
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 pub enum Ability {
     Strength,
@@ -45,6 +53,87 @@ pub enum Ability {
     Charisma,
 }
 
+impl Ability {
+    pub fn all() -> Vec<Ability> {
+        vec![
+            Ability::Strength,
+            Ability::Dexterity,
+            Ability::Constitution,
+            Ability::Intelligence,
+            Ability::Wisdom,
+            Ability::Charisma,
+        ]
+    }
+
+    pub fn abbreviation(&self) -> &'static str {
+        match self {
+            Ability::Strength => "STR",
+            Ability::Dexterity => "DEX",
+            Ability::Constitution => "CON",
+            Ability::Intelligence => "INT",
+            Ability::Wisdom => "WIS",
+            Ability::Charisma => "CHA",
+        }
+    }
+}
+
+/// Where a change to an ability score comes from, shown separately in the breakdown. Template
+/// and improvement effects are told apart by the root of their source path, which names the
+/// kind of template; anything else, such as a magic item, is an item.
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Layer {
+    Base,
+    Race,
+    Background,
+    Class,
+    Improvement,
+    Item,
+}
+
+impl Layer {
+    fn all() -> Vec<Layer> {
+        vec![
+            Layer::Base,
+            Layer::Race,
+            Layer::Background,
+            Layer::Class,
+            Layer::Improvement,
+            Layer::Item,
+        ]
+    }
+
+    fn of(source: &Source) -> Layer {
+        let root = match source {
+            Source::Base => return Layer::Base,
+            Source::Feature { path, .. } => path.first().unwrap_or_default(),
+            _ => return Layer::Item,
+        };
+        let is = |kind: TemplateKind| root == kind.to_string();
+        if is(TemplateKind::Race) {
+            Layer::Race
+        } else if is(TemplateKind::Background) {
+            Layer::Background
+        } else if is(TemplateKind::Class) || is(TemplateKind::Subclass) {
+            Layer::Class
+        } else if root == IMPROVEMENTS || is(TemplateKind::Feat) {
+            Layer::Improvement
+        } else {
+            Layer::Item
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Layer::Base => "Base",
+            Layer::Race => "Race",
+            Layer::Background => "Background",
+            Layer::Class => "Class",
+            Layer::Improvement => "ASI",
+            Layer::Item => "Items",
+        }
+    }
+}
+
 impl Display for Ability {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:?}", self)
@@ -111,6 +200,18 @@ impl ModifiedAbilityScores {
         }
     }
 
+    /// The ability modifier as a contribution to a derived value, naming what modified the score.
+    pub fn modifier_contribution(&self, ability: Ability) -> Contribution {
+        let score = self.get(ability.clone());
+        Contribution::new(
+            Amount::Modifier(score.score().modifier()),
+            Source::Ability {
+                ability,
+                modified_by: score.modified_by(),
+            },
+        )
+    }
+
     pub fn ability_scores(&self) -> AbilityScores {
         AbilityScores {
             strength: self.strength.score.clone(),
@@ -155,10 +256,19 @@ impl AbilityScoresState {
         self.charisma.reset();
     }
 
-    pub fn apply_all(&mut self, modifiers: &Vec<Effect>) {
+    pub fn apply_all(&mut self, modifiers: &Vec<ActiveEffect>) {
         self.reset_modifiers();
         for modifier in modifiers {
-            self.apply(modifier.clone());
+            self.apply(modifier);
+        }
+    }
+
+    pub fn update(&mut self, message: AbilityScoreMessage) {
+        match message {
+            AbilityScoreMessage::ToggleBreakdown(ability) => {
+                let score = self.get_mut(ability);
+                score.show_breakdown = !score.show_breakdown;
+            }
         }
     }
 
@@ -173,11 +283,19 @@ impl AbilityScoresState {
         }
     }
 
-    fn apply(&mut self, modifier: Effect) {
-        match modifier {
-            Effect::Ability { ability, bonus } => {
+    fn apply(&mut self, modifier: &ActiveEffect) {
+        match modifier.effect() {
+            Effect::Ability {
+                ability,
+                bonus,
+                name,
+            } => {
                 let score = self.get_mut(ability.clone());
-                score.value_modifiers.push(bonus);
+                score.value_modifiers.push(EffectBonus::new(
+                    name.clone(),
+                    modifier.source().clone(),
+                    bonus.clone(),
+                ));
             }
             _ => {}
         };
@@ -187,34 +305,56 @@ impl AbilityScoresState {
         self.ability_scores.clone()
     }
 
-    pub fn view<'a, T: Debug + Clone + 'a>(&'a mut self) -> Column<'a, T> {
+    pub fn view<'a, T, F>(&'a mut self, f: &'a F) -> Column<'a, T>
+    where
+        T: Debug + Clone + 'a,
+        F: Fn(AbilityScoreMessage) -> T,
+    {
+        let AbilityScoresState {
+            strength,
+            dexterity,
+            constitution,
+            intelligence,
+            wisdom,
+            charisma,
+            ..
+        } = self;
         Column::new()
             .push(Row::new().push(Text::new("Ability Scores").size(24)))
-            .push(self.strength.view("STR"))
-            .push(self.dexterity.view("DEX"))
-            .push(self.constitution.view("CON"))
-            .push(self.intelligence.view("INT"))
-            .push(self.wisdom.view("WIS"))
-            .push(self.charisma.view("CHA"))
+            .push(strength.view(Ability::Strength, f))
+            .push(dexterity.view(Ability::Dexterity, f))
+            .push(constitution.view(Ability::Constitution, f))
+            .push(intelligence.view(Ability::Intelligence, f))
+            .push(wisdom.view(Ability::Wisdom, f))
+            .push(charisma.view(Ability::Charisma, f))
     }
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct AbilityScoreState {
     ability_score: AbilityScore,
-    value_modifiers: Vec<AbilityScoreBonus>,
+    value_modifiers: Vec<EffectBonus<ValueBonus>>,
+    show_breakdown: bool,
+    breakdown_button: button::State,
 }
 
 impl AbilityScoreState {
     pub fn reset(&mut self) {
         self.value_modifiers = vec![];
     }
-    pub fn view<T: Debug + Clone>(&mut self, name: &str) -> Row<T> {
-        let ModifiedAbilityScore { score } = self.modified();
+    pub fn view<'a, T, F>(&'a mut self, ability: Ability, f: &'a F) -> Column<'a, T>
+    where
+        T: Debug + Clone + 'a,
+        F: Fn(AbilityScoreMessage) -> T,
+    {
+        let ModifiedAbilityScore {
+            score,
+            contributions,
+        } = self.modified();
 
         let modified_score = score;
 
-        let name_cell = Text::new(name)
+        let name_cell = Text::new(ability.abbreviation())
             .size(16)
             .horizontal_alignment(HorizontalAlignment::Left)
             .vertical_alignment(VerticalAlignment::Bottom)
@@ -235,57 +375,82 @@ impl AbilityScoreState {
             .vertical_alignment(VerticalAlignment::Bottom)
             .width(Length::FillPortion(1));
 
-        Row::new()
+        let row = Row::new()
             .width(Length::Fill)
             .spacing(4)
             .push(name_cell)
-            .push(value_cell)
+            .push(value_cell);
+
+        let mut column = Column::new().push(
+            Button::new(&mut self.breakdown_button, row)
+                .on_press(f(AbilityScoreMessage::ToggleBreakdown(ability)))
+                .padding(0),
+        );
+
+        if self.show_breakdown {
+            for layer in Layer::all() {
+                let layered = contributions
+                    .iter()
+                    .filter(|c| Layer::of(c.source()) == layer)
+                    .map(|c| c.to_string())
+                    .collect::<Vec<String>>();
+                if !layered.is_empty() {
+                    column = column.push(
+                        Text::new(format!("{}: {}", layer.label(), layered.join(", "))).size(12),
+                    );
+                }
+            }
+        }
+
+        column
     }
 
+    /// Bonuses go through the shared `stat::modify` pipeline, so they apply the same way
+    /// regardless of feature order. Modifiers stop at `MAXIMUM`.
     fn modified(&self) -> ModifiedAbilityScore {
         let AbilityScoreState {
             ability_score,
             value_modifiers,
+            ..
         } = self;
 
-        let mut become_value: Option<isize> = None;
-        let mut score_modifiers = vec![];
+        let (value, contributions) = modify_up_to(
+            vec![Contribution::new(
+                Amount::Value(ability_score.value),
+                Source::Base,
+            )],
+            value_modifiers,
+            Some(MAXIMUM),
+        );
 
-        for value_modifier in value_modifiers {
-            match value_modifier {
-                AbilityScoreBonus::Become { value } => {
-                    become_value = Some(value.clone());
-                }
-                AbilityScoreBonus::Modifier { modifier } => score_modifiers.push(modifier.clone()),
-            }
+        ModifiedAbilityScore {
+            score: AbilityScore::of(value),
+            contributions,
         }
-
-        let score_modifier_total = if score_modifiers.is_empty() || become_value.is_some() {
-            0
-        } else {
-            score_modifiers.into_iter().sum::<isize>()
-        };
-
-        let score = match become_value {
-            Some(value) => AbilityScore::of(value),
-            None => AbilityScore::of(ability_score.value + score_modifier_total),
-        };
-
-        ModifiedAbilityScore { score }
     }
 }
 #[derive(Debug, Clone)]
 pub struct ModifiedAbilityScore {
     score: AbilityScore,
+    contributions: Vec<Contribution>,
 }
 
 impl ModifiedAbilityScore {
     pub fn roll(&self) -> Rollable {
-        Rollable::from(vec![], HashSet::new(), 0, self.score.modifier())
+        Rollable::from(vec![], HashSet::new(), vec![], self.score.modifier())
     }
     pub fn score(&self) -> AbilityScore {
         self.score.clone()
     }
+
+    /// Labels of everything that changed this score from its base value.
+    pub fn modified_by(&self) -> Vec<String> {
+        self.contributions
+            .iter()
+            .filter(|c| c.source() != &Source::Base)
+            .map(|c| c.source().label())
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
@@ -310,6 +475,9 @@ impl AbilityScore {
     pub fn of(value: isize) -> AbilityScore {
         AbilityScore { value }
     }
+    pub fn value(&self) -> isize {
+        self.value
+    }
     pub fn modifier(&self) -> isize {
         if self.value < 10 {
             (self.value - 11) / 2
@@ -321,7 +489,11 @@ impl AbilityScore {
 
 #[cfg(test)]
 mod test {
-    use crate::core::ability_score::AbilityScore;
+    use crate::core::ability_score::{Ability, AbilityScore, AbilityScores, Layer};
+    use crate::core::effect::{ActiveEffect, Effect, EffectBonus};
+    use crate::core::feature_path::FeaturePath;
+    use crate::core::provenance::Source;
+    use crate::core::stat::ValueBonus;
 
     fn assert_modfier(value: isize, modifier: isize) {
         assert_eq!(
@@ -359,4 +531,108 @@ mod test {
         .into_iter()
         .for_each(|(value, modifier)| assert_modfier(value, modifier))
     }
+
+    fn modified_constitution(base: isize, bonuses: Vec<(Option<&str>, ValueBonus)>) -> isize {
+        let mut state = AbilityScore::of(base).to_state();
+        for (name, bonus) in bonuses {
+            state.value_modifiers.push(EffectBonus::new(
+                name.map(|n| n.to_string()),
+                FeaturePath::empty(),
+                bonus,
+            ));
+        }
+        state.modified().score.value
+    }
+
+    #[test]
+    fn become_only_raises_a_lower_score() {
+        let amulet = ValueBonus::Become { value: 19 };
+        assert_eq!(modified_constitution(16, vec![(None, amulet.clone())]), 19);
+        assert_eq!(modified_constitution(20, vec![(None, amulet)]), 20);
+    }
+
+    #[test]
+    fn set_replaces_the_score_before_modifiers() {
+        let bonuses = vec![
+            (None, ValueBonus::Modifier { modifier: 2 }),
+            (None, ValueBonus::Set { value: 8 }),
+        ];
+        assert_eq!(modified_constitution(16, bonuses), 10);
+    }
+
+    #[test]
+    fn modifiers_apply_before_become() {
+        let bonuses = vec![
+            (None, ValueBonus::Become { value: 19 }),
+            (None, ValueBonus::Modifier { modifier: 2 }),
+        ];
+        assert_eq!(modified_constitution(16, bonuses.clone()), 19);
+        assert_eq!(modified_constitution(18, bonuses), 20);
+    }
+
+    #[test]
+    fn modifiers_stop_at_the_maximum_unless_it_is_raised() {
+        let improvement = ValueBonus::Modifier { modifier: 2 };
+        assert_eq!(
+            modified_constitution(19, vec![(None, improvement.clone())]),
+            20
+        );
+        assert_eq!(
+            modified_constitution(21, vec![(None, improvement.clone())]),
+            21
+        );
+        let bonuses = vec![
+            (None, improvement),
+            (None, ValueBonus::Maximum { value: 24 }),
+        ];
+        assert_eq!(modified_constitution(19, bonuses), 21);
+    }
+
+    #[test]
+    fn effects_apply_regardless_of_order() {
+        let effects = vec![
+            Effect::Ability {
+                bonus: ValueBonus::Modifier { modifier: 2 },
+                ability: Ability::Constitution,
+                name: None,
+            },
+            Effect::Ability {
+                bonus: ValueBonus::Become { value: 19 },
+                ability: Ability::Constitution,
+                name: None,
+            },
+        ]
+        .into_iter()
+        .map(|e| ActiveEffect::new(e, FeaturePath::empty()))
+        .collect::<Vec<ActiveEffect>>();
+        let mut reversed = effects.clone();
+        reversed.reverse();
+
+        let scores = AbilityScores::default().with(Ability::Constitution, AbilityScore::of(18));
+        let mut forward = scores.clone().to_state();
+        forward.apply_all(&effects);
+        let mut backward = scores.to_state();
+        backward.apply_all(&reversed);
+
+        assert_eq!(
+            forward.modified().get(Ability::Constitution).score(),
+            backward.modified().get(Ability::Constitution).score()
+        );
+    }
+
+    #[test]
+    fn template_bonuses_are_layered_by_kind() {
+        let layer = |root: &str| {
+            Layer::of(&Source::feature(
+                FeaturePath::of(vec![root.to_string(), "Anything".to_string()]),
+                None,
+            ))
+        };
+        assert_eq!(layer("Race"), Layer::Race);
+        assert_eq!(layer("Background"), Layer::Background);
+        assert_eq!(layer("Class"), Layer::Class);
+        assert_eq!(layer("Subclass"), Layer::Class);
+        assert_eq!(layer("Feat"), Layer::Improvement);
+        assert_eq!(layer("Belt of Giant Strength"), Layer::Item);
+    }
 }
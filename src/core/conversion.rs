@@ -0,0 +1,35 @@
+use crate::core::overlay::Overlay;
+use crate::core::slot::SlotAmount;
+use serde::{Deserialize, Serialize};
+
+/// Trades charges of one slot for charges of another, such as sorcery points for a spell slot.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Conversion {
+    name: String,
+    spend: SlotAmount,
+    gain: SlotAmount,
+}
+
+impl Conversion {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn spend(&self) -> &SlotAmount {
+        &self.spend
+    }
+
+    pub fn gain(&self) -> &SlotAmount {
+        &self.gain
+    }
+}
+
+impl Overlay for Conversion {
+    fn overlay_by(&self) -> String {
+        self.name.clone()
+    }
+
+    fn overlay(&self, overlay: &Self) -> Self {
+        overlay.clone()
+    }
+}
@@ -1,8 +1,13 @@
-use crate::core::ability_score::{Ability, AbilityScoreBonus};
+use crate::core::ability_score::Ability;
+use crate::core::feature_path::FeaturePath;
+use crate::core::formula::Formula;
+use crate::core::provenance::Source;
 use crate::core::roll::{Dice, RollBonus, RollScope};
+use crate::core::stat::{Stat, ValueBonus};
 use crate::util::format_modifier;
 use iced::{Column, Element, HorizontalAlignment, Length, Row, Text, VerticalAlignment};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fmt::{Debug, Display, Formatter};
 
 #[derive(Debug, Clone, Default)]
@@ -73,39 +78,181 @@ impl EffectState {
     }
 }
 
+/// Effects sharing a `name` (two castings of Bless, say) do not stack with each other; only
+/// the most potent one applies. Unnamed effects always stack.
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 #[serde(tag = "type")]
 pub enum Effect {
     Ability {
-        bonus: AbilityScoreBonus,
+        bonus: ValueBonus,
         ability: Ability,
+        #[serde(default)]
+        name: Option<String>,
     },
     Roll {
         bonus: RollBonus,
         scope: RollScope,
+        #[serde(default)]
+        name: Option<String>,
+    },
+    Stat {
+        bonus: ValueBonus<Formula>,
+        stat: Stat,
+        #[serde(default)]
+        name: Option<String>,
+    },
+    /// Changes how spells are cast, such as metamagic.
+    Spell {
+        bonus: SpellBonus,
+        #[serde(default)]
+        name: Option<String>,
     },
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(tag = "type", content = "value")]
+pub enum SpellBonus {
+    /// Cast in this time instead, such as "1 bonus action".
+    CastingTime(String),
+    /// Targets this many more creatures, each rolled for separately.
+    ExtraTargets(isize),
+}
+
 impl Effect {
     pub fn to_state(self) -> EffectState {
         EffectState { effect: self }
     }
+
+    pub fn name(&self) -> Option<String> {
+        match self {
+            Effect::Ability { name, .. } => name.clone(),
+            Effect::Roll { name, .. } => name.clone(),
+            Effect::Stat { name, .. } => name.clone(),
+            Effect::Spell { name, .. } => name.clone(),
+        }
+    }
+}
+
+/// An effect together with the path of the feature that granted it.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ActiveEffect {
+    effect: Effect,
+    source: FeaturePath,
+}
+
+impl ActiveEffect {
+    pub fn new(effect: Effect, source: FeaturePath) -> ActiveEffect {
+        ActiveEffect { effect, source }
+    }
+
+    pub fn effect(&self) -> &Effect {
+        &self.effect
+    }
+
+    pub fn source(&self) -> &FeaturePath {
+        &self.source
+    }
+
+    pub fn with_effect(&self, effect: Effect) -> ActiveEffect {
+        ActiveEffect {
+            effect,
+            source: self.source.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EffectBonus<T> {
+    pub name: Option<String>,
+    pub source: FeaturePath,
+    pub bonus: T,
+}
+
+impl<T> EffectBonus<T> {
+    pub fn new(name: Option<String>, source: FeaturePath, bonus: T) -> EffectBonus<T> {
+        EffectBonus {
+            name,
+            source,
+            bonus,
+        }
+    }
+
+    pub fn provenance(&self) -> Source {
+        Source::feature(self.source.clone(), self.name.clone())
+    }
+}
+
+/// Applies the stacking rule: every unnamed bonus is kept, in order, followed by the most
+/// potent bonus of each name, ordered by name. A negative potency is a penalty, and penalties
+/// stack apart from bonuses, so the largest penalty of a name applies alongside its largest
+/// bonus. Ties keep the first bonus seen.
+pub fn stack<T, F>(bonuses: &[EffectBonus<T>], potency: F) -> Vec<EffectBonus<T>>
+where
+    T: Clone,
+    F: Fn(&T) -> isize,
+{
+    let mut unnamed = vec![];
+    let mut strongest: BTreeMap<(String, bool), &EffectBonus<T>> = BTreeMap::new();
+
+    for bonus in bonuses {
+        match &bonus.name {
+            None => unnamed.push(bonus.clone()),
+            Some(name) => {
+                let key = (name.clone(), potency(&bonus.bonus) < 0);
+                match strongest.get(&key) {
+                    Some(existing)
+                        if potency(&existing.bonus).abs() >= potency(&bonus.bonus).abs() => {}
+                    _ => {
+                        strongest.insert(key, bonus);
+                    }
+                }
+            }
+        }
+    }
+
+    unnamed.extend(strongest.into_iter().map(|(_, bonus)| bonus.clone()));
+    unnamed
 }
 
 impl Display for Effect {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(name) = self.name() {
+            write!(f, "{}: ", name)?;
+        }
         match self {
-            Effect::Ability { bonus, ability } => match bonus {
-                AbilityScoreBonus::Modifier { modifier } => {
+            Effect::Ability { bonus, ability, .. } => match bonus {
+                ValueBonus::Modifier { modifier } => {
                     write!(f, "{} {:?}", format_modifier(modifier.clone()), ability)
                 }
-                AbilityScoreBonus::Become { value } => {
+                ValueBonus::Become { value } => {
                     write!(f, "{:?} becomes {}", ability, value)
                 }
+                ValueBonus::Set { value } => {
+                    write!(f, "{:?} is set to {}", ability, value)
+                }
+                ValueBonus::Maximum { value } => {
+                    write!(f, "{:?} maximum is {}", ability, value)
+                }
             },
-            Effect::Roll { bonus, scope } => {
+            Effect::Roll { bonus, scope, .. } => {
                 write!(f, "{:?} to {}", bonus, scope.to_string())
             }
+            Effect::Stat { bonus, stat, .. } => match bonus {
+                ValueBonus::Modifier { modifier } => match modifier.constant() {
+                    Some(modifier) => write!(f, "{} {}", format_modifier(modifier), stat),
+                    None => write!(f, "+({}) {}", modifier, stat),
+                },
+                ValueBonus::Become { value } => write!(f, "{} becomes {}", stat, value),
+                ValueBonus::Set { value } => write!(f, "{} is set to {}", stat, value),
+                ValueBonus::Maximum { value } => write!(f, "{} maximum is {}", stat, value),
+            },
+            Effect::Spell { bonus, .. } => match bonus {
+                SpellBonus::CastingTime(time) => write!(f, "Spells are cast as {}", time),
+                SpellBonus::ExtraTargets(1) => write!(f, "Spells target 1 more creature"),
+                SpellBonus::ExtraTargets(count) => {
+                    write!(f, "Spells target {} more creatures", count)
+                }
+            },
         }
     }
 }
@@ -115,8 +262,12 @@ mod test {
     use super::*;
 
     pub fn ability_becomes(value: isize, ability: Ability) -> Effect {
-        let bonus = AbilityScoreBonus::Become { value };
-        Effect::Ability { bonus, ability }
+        let bonus = ValueBonus::Become { value };
+        Effect::Ability {
+            bonus,
+            ability,
+            name: None,
+        }
     }
 
     #[test]
@@ -128,4 +279,25 @@ mod test {
         // );
         assert_eq!(effect.to_string(), "Constitution becomes 19".to_string());
     }
+
+    #[test]
+    pub fn same_named_bonuses_do_not_stack() {
+        let bonus = |name: Option<&str>, value: isize| {
+            EffectBonus::new(name.map(|n| n.to_string()), FeaturePath::empty(), value)
+        };
+        let bonuses = vec![
+            bonus(Some("Bless"), 2),
+            bonus(None, 1),
+            bonus(Some("Bless"), 4),
+            bonus(None, 1),
+            bonus(Some("Aid"), 5),
+        ];
+
+        let stacked = stack(&bonuses, |b| *b)
+            .into_iter()
+            .map(|b| b.bonus)
+            .collect::<Vec<isize>>();
+
+        assert_eq!(stacked, vec![1, 1, 5, 4]);
+    }
 }
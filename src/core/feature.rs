@@ -1,10 +1,13 @@
 use super::feature_path::FeaturePath;
 use crate::character::class::Classes;
-use crate::core::ability_score::AbilityScores;
-use crate::core::effect::{Effect, EffectState, EffectsState};
+use crate::core::ability_score::ModifiedAbilityScores;
+use crate::core::conversion::Conversion;
+use crate::core::effect::{ActiveEffect, Effect, EffectState, EffectsState};
+use crate::core::formula::FormulaContext;
 use crate::core::overlay::{overlay_all, Overlay};
+use crate::core::roll::log::{PostRoll, PostRollModifier};
 use crate::core::roll::{Roll, RollScope, RollState};
-use crate::core::slot::{FromSlotCommand, Slot, SlotCommand, SlotState};
+use crate::core::slot::{FromSlotCommand, Recovery, Slot, SlotAmount, SlotCommand, SlotState};
 use iced::futures::StreamExt;
 use iced::{button, Button, Column, Element, Length, Row, Text};
 use serde::export::Formatter;
@@ -16,6 +19,8 @@ use std::fmt::{Debug, Display};
 #[derive(Debug, Clone, Default)]
 pub struct FeaturesState {
     feature_state: Vec<FeatureState>,
+    /// Why the last conversion or activation was refused.
+    notice: Option<String>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -26,13 +31,31 @@ pub struct FeatureState {
     children: Vec<FeatureState>,
     effects_state: EffectsState,
     rolls_state: Vec<RollState>,
+    concentrate_button: button::State,
+    conversion_buttons: Vec<button::State>,
+    active: bool,
+    activate_button: button::State,
+    table_button: button::State,
+    /// The reserved key a top level feature was generated under, if it isn't the character's.
+    generated: Option<String>,
 }
 
 type IsDirty = bool;
 
+const CONVERSIONS_PER_ROW: usize = 5;
+
 #[derive(Debug, Clone)]
 pub enum FeatureMessage {
     Slot(FeaturePath, SlotCommand),
+    Concentrate(FeaturePath),
+    ToggleRollBreakdown(FeaturePath, String),
+    Convert(Conversion),
+    /// Pays a feature's cost so its effects apply, or cancels it for a refund when it is active.
+    Activate(FeaturePath),
+    /// Rolls on the named random table for the feature at the path.
+    RollTable(FeaturePath, String),
+    /// Rolls the named roll of the feature at the path into the roll log.
+    Roll(FeaturePath, String),
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -42,7 +65,7 @@ pub struct Feature {
     slot: Option<Slot>,
     #[serde(default)]
     children: Vec<Feature>,
-    show_reset_chidren: Option<bool>,
+    show_reset_children: Option<bool>,
     child_display_orientation: Option<DisplayOrientation>,
     #[serde(default)]
     effects: Vec<Effect>,
@@ -50,6 +73,15 @@ pub struct Feature {
     rolls: Vec<Roll>,
     #[serde(default)]
     templates: Vec<String>,
+    concentration: Option<bool>,
+    #[serde(default)]
+    conversions: Vec<Conversion>,
+    /// Paid each time the feature is activated; its effects only apply while it is active.
+    cost: Option<SlotAmount>,
+    /// A random table the feature rolls on, such as the wild magic surge table.
+    table: Option<String>,
+    /// Dice the feature can add to a roll after it is made, such as Bend Luck.
+    post_roll: Option<PostRoll>,
 }
 
 impl Overlay for Feature {
@@ -63,31 +95,46 @@ impl Overlay for Feature {
             description,
             slot,
             children,
-            show_reset_chidren,
+            show_reset_children,
             child_display_orientation,
             effects,
             rolls,
             templates,
+            concentration,
+            conversions,
+            cost,
+            table,
+            post_roll,
         } = overlay;
         let overlay_name = name;
         let overlay_descripion = description;
         let overlay_slot = slot;
         let overlay_children = children;
-        let overlay_show_reset_children = show_reset_chidren;
+        let overlay_show_reset_children = show_reset_children;
         let overlay_child_display_orientation = child_display_orientation;
         let overlay_effects = effects;
         let overlay_rolls = rolls;
         let overlay_templates = templates;
+        let overlay_concentration = concentration;
+        let overlay_conversions = conversions;
+        let overlay_cost = cost;
+        let overlay_table = table;
+        let overlay_post_roll = post_roll;
         let Feature {
             name,
             description,
             slot,
             children,
-            show_reset_chidren,
+            show_reset_children,
             child_display_orientation,
             effects,
             rolls,
             templates,
+            concentration,
+            conversions,
+            cost,
+            table,
+            post_roll,
         } = self;
 
         let mut effects = effects.clone();
@@ -104,23 +151,126 @@ impl Overlay for Feature {
             description: overlay_descripion.clone().or_else(|| description.clone()),
             slot: overlay_slot.clone().or_else(|| slot.clone()),
             children: overlay_all(children, overlay_children),
-            show_reset_chidren: overlay_show_reset_children
+            show_reset_children: overlay_show_reset_children
                 .clone()
-                .or_else(|| show_reset_chidren.clone()),
+                .or_else(|| show_reset_children.clone()),
             child_display_orientation: overlay_child_display_orientation
                 .clone()
                 .or_else(|| child_display_orientation.clone()),
             effects: effects,
             rolls: overlay_all(rolls, overlay_rolls),
             templates: templates,
+            concentration: overlay_concentration.or(*concentration),
+            conversions: overlay_all(conversions, overlay_conversions),
+            cost: overlay_cost.clone().or_else(|| cost.clone()),
+            table: overlay_table.clone().or_else(|| table.clone()),
+            post_roll: overlay_post_roll.clone().or_else(|| post_roll.clone()),
         }
     }
 }
 
 impl Feature {
+    pub fn named(name: &str) -> Feature {
+        Feature {
+            name: name.to_string(),
+            ..Feature::default()
+        }
+    }
+
+    /// Whether this feature, or any feature under it, is named `name` or built from the
+    /// template `name`.
+    pub fn has(&self, name: &str) -> bool {
+        self.name == name
+            || self.templates.iter().any(|template| template == name)
+            || self.children.iter().any(|child| child.has(name))
+    }
+
+    /// The path of this feature under `parent`, followed by those of the features under it.
+    pub fn paths(&self, parent: &FeaturePath) -> Vec<FeaturePath> {
+        let path = parent.with_child(self.name.clone());
+        let mut paths = vec![path.clone()];
+        for child in self.children.iter() {
+            paths.extend(child.paths(&path));
+        }
+        paths
+    }
+
+    pub fn with_description(self, description: String) -> Feature {
+        Feature {
+            description: Some(description),
+            ..self
+        }
+    }
+
+    pub fn with_rolls(self, rolls: Vec<Roll>) -> Feature {
+        Feature { rolls, ..self }
+    }
+
+    pub fn with_children(self, children: Vec<Feature>) -> Feature {
+        Feature { children, ..self }
+    }
+
+    pub fn with_concentration(self, concentration: bool) -> Feature {
+        Feature {
+            concentration: Some(concentration),
+            ..self
+        }
+    }
+
     pub fn matches(&self, path: FeaturePath) -> (bool, FeaturePath) {
         path.matches(self.name.clone())
     }
+
+    pub fn requires_concentration(&self) -> bool {
+        self.concentration.unwrap_or(false)
+    }
+
+    pub fn requires_activation(&self) -> bool {
+        self.cost.is_some()
+    }
+}
+
+/// Adds a feature referencing each of the templates `names` under the top level feature named
+/// like `group`, which is added if missing. Features the character already has are skipped;
+/// the names of those added are returned.
+pub fn grant(features: &mut Vec<Feature>, group: Feature, names: &[String]) -> Vec<String> {
+    let mut added: Vec<String> = vec![];
+    for name in names {
+        if !added.contains(name) && !features.iter().any(|feature| feature.has(name)) {
+            added.push(name.clone());
+        }
+    }
+    if added.is_empty() {
+        return added;
+    }
+    let missing = added.iter().map(|name| Feature::named(name)).collect();
+    match features
+        .iter_mut()
+        .find(|feature| feature.name == group.name)
+    {
+        Some(feature) => feature.children.extend(missing),
+        None => features.push(group.with_children(missing)),
+    }
+    added
+}
+
+/// Removes every feature named `name`, as granted by a template, along with any feature only
+/// left empty by that.
+pub fn revoke(features: &mut Vec<Feature>, name: &str) -> bool {
+    let before = features.len();
+    features.retain(|feature| feature.name != name);
+    let mut removed = features.len() != before;
+    features.retain_mut(|feature| {
+        if !revoke(&mut feature.children, name) {
+            return true;
+        }
+        removed = true;
+        !feature.children.is_empty()
+            || feature.slot.is_some()
+            || !feature.effects.is_empty()
+            || !feature.rolls.is_empty()
+    });
+    removed
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -136,33 +286,327 @@ impl Default for DisplayOrientation {
 }
 
 impl FeaturesState {
-    pub fn effects(&self) -> Vec<Effect> {
-        let FeaturesState { feature_state } = self;
+    pub fn effects(&self, concentrating_on: Option<&FeaturePath>) -> Vec<ActiveEffect> {
+        let FeaturesState { feature_state, .. } = self;
         let mut result = vec![];
         for state in feature_state {
-            result.extend(state.effects())
+            result.extend(state.effects(FeaturePath::empty(), concentrating_on))
         }
         result
     }
 
-    pub fn apply_effects<'a, 'b>(&'a mut self, effects: &'b Vec<Effect>) {
-        let FeaturesState { feature_state } = self;
+    /// The roll named `roll_name` of the feature at `path`.
+    pub fn find_roll(&self, path: &FeaturePath, roll_name: &str) -> Option<&RollState> {
+        let (name, rest) = path.segments().split_first()?;
+        self.feature_state
+            .iter()
+            .find(|state| &state.feature.name == name)
+            .and_then(|state| state.find_roll(rest, roll_name))
+    }
+
+    /// The path of the first feature named `name`, searching each feature before its children.
+    pub fn path_of(&self, name: &str) -> Option<FeaturePath> {
+        self.feature_state
+            .iter()
+            .find_map(|state| state.path_of(FeaturePath::empty(), name))
+    }
+
+    /// The path of the feature generated under `key`, if there is one.
+    pub fn generated_path(&self, key: &str) -> Option<FeaturePath> {
+        self.feature_state
+            .iter()
+            .find(|state| state.generated.as_deref() == Some(key))
+            .map(|state| FeaturePath::of(vec![state.feature.name.clone()]))
+    }
+
+    pub fn feature_name(&self, path: &FeaturePath) -> Option<String> {
+        self.feature_state
+            .iter()
+            .find_map(|state| state.feature_name(path))
+    }
+
+    pub fn find_slot(&self, path: &[String]) -> Option<&SlotState> {
+        let path = self.resolve_slot(path)?;
+        let (name, rest) = path.split_first()?;
+        self.feature_state
+            .iter()
+            .find(|state| &state.feature.name == name)
+            .and_then(|state| state.find_slot(rest))
+    }
+
+    /// The full path of the slot at `path`, which is either from the top level down or, for a
+    /// feature granted under its template's group, from the first feature named like its start.
+    pub fn resolve_slot(&self, path: &[String]) -> Option<Vec<String>> {
+        let (name, rest) = path.split_first()?;
+        let from_top = self
+            .feature_state
+            .iter()
+            .find(|state| &state.feature.name == name)
+            .and_then(|state| state.find_slot(rest));
+        if from_top.is_some() {
+            return Some(path.to_vec());
+        }
+        self.feature_state
+            .iter()
+            .find_map(|state| state.resolve_slot(vec![], name, rest))
+    }
+
+    /// Names of the features currently activated, such as metamagic for the next spell.
+    pub fn active(&self) -> Vec<String> {
+        let mut names = vec![];
+        for state in self.feature_state.iter() {
+            state.active(&mut names);
+        }
+        names
+    }
+
+    /// Features that can amend a roll after it is made.
+    pub fn post_roll_modifiers(&self) -> Vec<PostRollModifier> {
+        let mut modifiers = vec![];
+        for state in self.feature_state.iter() {
+            state.post_roll_modifiers(FeaturePath::empty(), &mut modifiers);
+        }
+        modifiers
+    }
+
+    /// Ends every activation without a refund, once the features have been used.
+    pub fn end_activations(&mut self) -> IsDirty {
+        let mut ended = false;
+        for state in self.feature_state.iter_mut() {
+            ended = state.end_activations() || ended;
+        }
+        ended
+    }
+
+    fn activate(&mut self, path: &FeaturePath) -> IsDirty {
+        let (cost, active) = match self.feature_state.iter().find_map(|state| state.find(path)) {
+            Some(state) => match &state.overlayed_feature.cost {
+                Some(cost) => (cost.clone(), state.active),
+                None => return false,
+            },
+            None => return false,
+        };
+
+        if cost.is_per_spell_level() {
+            // Only known, and paid, once the spell is cast.
+        } else if active {
+            if let Some(slot) = self.find_slot_mut(cost.path()) {
+                slot.adjust(cost.amount());
+            }
+        } else if let Err(refused) = self.spend(&cost, &format!("activate {}", path.breadcrumb())) {
+            self.notice = Some(refused);
+            return false;
+        }
+        for state in self.feature_state.iter_mut() {
+            state.apply(path, &|feature_state: &mut FeatureState| {
+                feature_state.active = !active;
+                true
+            });
+        }
+        true
+    }
+
+    /// Pays `cost` from its slot, refusing when too few charges are left. `purpose` describes
+    /// what the cost was for in the reason it is refused.
+    pub fn spend(&mut self, cost: &SlotAmount, purpose: &str) -> Result<(), String> {
+        if let Some(refused) = self.refuse_spend(cost, purpose) {
+            return Err(refused);
+        }
+        if let Some(slot) = self.find_slot_mut(cost.path()) {
+            slot.adjust(-cost.amount());
+        }
+        Ok(())
+    }
+
+    /// Why `cost` can't be paid right now, if it can't.
+    pub fn refuse_spend(&self, cost: &SlotAmount, purpose: &str) -> Option<String> {
+        match self.find_slot(cost.path()) {
+            None => Some(format!(
+                "Cannot {}: the character doesn't have {}",
+                purpose,
+                cost.path().join(" > ")
+            )),
+            Some(slot) if slot.current() < cost.amount() => Some(format!(
+                "Cannot {}: needs {}, {} left",
+                purpose,
+                cost,
+                slot.current()
+            )),
+            Some(_) => None,
+        }
+    }
+
+    /// The costs of the activated features that are paid by spell level, with the features'
+    /// names, to be charged when the spell is cast.
+    pub fn spell_costs(&self) -> Vec<(String, SlotAmount)> {
+        let mut costs = vec![];
+        for state in self.feature_state.iter() {
+            state.spell_costs(&mut costs);
+        }
+        costs
+    }
+
+    fn find_slot_mut(&mut self, path: &[String]) -> Option<&mut SlotState> {
+        let path = self.resolve_slot(path)?;
+        let (name, rest) = path.split_first()?;
+        self.feature_state
+            .iter_mut()
+            .find(|state| &state.feature.name == name)
+            .and_then(|state| state.find_slot_mut(rest))
+    }
+
+    /// Spends one slot's charges for another's, changing neither unless both can change: the
+    /// spent slot needs enough charges and the gained slot can't go over its maximum.
+    fn convert(&mut self, conversion: &Conversion) -> IsDirty {
+        match self.refuse_conversion(conversion) {
+            Some(refused) => {
+                self.notice = Some(refused);
+                false
+            }
+            None => self.apply_conversion(conversion),
+        }
+    }
+
+    /// Why `conversion` can't be made right now, if it can't.
+    fn refuse_conversion(&self, conversion: &Conversion) -> Option<String> {
+        let (spend, gain) = (conversion.spend(), conversion.gain());
+        let available = self.find_slot(spend.path()).map(|slot| slot.current());
+        let room = self
+            .find_slot(gain.path())
+            .map(|slot| slot.max().map(|max| max - slot.current()));
+        match (available, room) {
+            (Some(available), Some(_)) if available < spend.amount() => Some(format!(
+                "Cannot {}: needs {} {}, {} left",
+                conversion.name(),
+                spend.amount(),
+                spend.path().join(" > "),
+                available
+            )),
+            (Some(_), Some(Some(room))) if room < gain.amount() => Some(format!(
+                "Cannot {}: {} is full",
+                conversion.name(),
+                gain.path().join(" > ")
+            )),
+            (Some(_), Some(_)) => None,
+            _ => Some(format!(
+                "Cannot {}: the character doesn't have both {} and {}",
+                conversion.name(),
+                spend.path().join(" > "),
+                gain.path().join(" > ")
+            )),
+        }
+    }
+
+    fn apply_conversion(&mut self, conversion: &Conversion) -> IsDirty {
+        let (spend, gain) = (conversion.spend(), conversion.gain());
+
+        let spent = self
+            .find_slot_mut(spend.path())
+            .map(|slot| slot.adjust(-spend.amount()))
+            .unwrap_or(false);
+        let gained = self
+            .find_slot_mut(gain.path())
+            .map(|slot| slot.adjust(gain.amount()))
+            .unwrap_or(false);
+        spent || gained
+    }
+
+    pub fn apply_effects<'a, 'b>(&'a mut self, effects: &'b Vec<ActiveEffect>) {
+        let FeaturesState { feature_state, .. } = self;
         for state in feature_state {
+            state.reset_effects();
             state.apply_effects(effects)
         }
     }
+
+    pub fn evaluate_formulas(&mut self, context: &FormulaContext) {
+        for state in self.feature_state.iter_mut() {
+            state.evaluate_formulas(context)
+        }
+    }
+
+    /// Sets the maximum of the slot at `path` to a derived value. When the character doesn't
+    /// have that feature yet and the maximum is above 0, it is added (along with any missing
+    /// parents) with a full slot recovering on `recovery`.
+    pub fn derive_slot(
+        &mut self,
+        path: &[String],
+        max: isize,
+        recovery: Recovery,
+        description: Option<String>,
+    ) -> IsDirty {
+        let (name, rest) = match path.split_first() {
+            Some(split) => split,
+            None => return false,
+        };
+        match self
+            .feature_state
+            .iter_mut()
+            .find(|state| &state.feature.name == name)
+        {
+            Some(state) => state.derive_slot(rest, max, recovery, description),
+            None if max > 0 => {
+                let mut state = FeatureState::from(Feature::named(name), &HashMap::new());
+                state.derive_slot(rest, max, recovery, description);
+                self.feature_state.push(state);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn rest(&mut self, rest: &Recovery) -> IsDirty {
+        let mut dirty = false;
+        for state in self.feature_state.iter_mut() {
+            dirty = state.rest(rest) || dirty;
+        }
+        dirty
+    }
     pub fn is_empty(&self) -> bool {
         self.feature_state.is_empty()
     }
 
     pub fn persistable(&self) -> Vec<Feature> {
         self.feature_state
-            .clone()
-            .into_iter()
+            .iter()
+            .filter(|f| f.generated.is_none())
             .map(|f| f.persistable())
             .collect()
     }
 
+    /// Adds, or replaces, the top level feature generated under the reserved `key` from other
+    /// resources (such as the spell catalog) rather than the character file. Generated features
+    /// are not saved, and one named like a feature of the character's is shown as "name (key)".
+    pub fn set_generated(
+        &mut self,
+        key: &str,
+        mut feature: Feature,
+        feature_templates: &HashMap<String, Feature>,
+    ) {
+        if self
+            .feature_state
+            .iter()
+            .any(|state| state.generated.is_none() && state.feature.name == feature.name)
+        {
+            feature.name = format!("{} ({})", feature.name, key);
+        }
+        let mut state = FeatureState::from(feature, feature_templates);
+        state.generated = Some(key.to_string());
+        match self
+            .feature_state
+            .iter_mut()
+            .find(|state| state.generated.as_deref() == Some(key))
+        {
+            Some(existing) => *existing = state,
+            None => self.feature_state.push(state),
+        }
+    }
+
+    pub fn remove_generated(&mut self, key: &str) {
+        self.feature_state
+            .retain(|state| state.generated.as_deref() != Some(key));
+    }
+
     pub fn from(
         features: Vec<Feature>,
         feature_templates: &HashMap<String, Feature>,
@@ -172,11 +616,23 @@ impl FeaturesState {
                 .into_iter()
                 .map(|f| FeatureState::from(f, feature_templates))
                 .collect(),
+            notice: None,
         }
     }
 
+    /// Shows why something the features were asked to do was refused, or clears it with `None`.
+    pub fn notify(&mut self, notice: Option<String>) {
+        self.notice = notice;
+    }
+
     pub fn update(&mut self, message: FeatureMessage) -> IsDirty {
-        let FeaturesState { feature_state } = self;
+        self.notice = None;
+        match &message {
+            FeatureMessage::Convert(conversion) => return self.convert(conversion),
+            FeatureMessage::Activate(path) => return self.activate(path),
+            _ => {}
+        }
+        let FeaturesState { feature_state, .. } = self;
         let mut dirty = false;
         for state in feature_state {
             dirty = state.update(message.clone()) || dirty;
@@ -187,7 +643,7 @@ impl FeaturesState {
     pub fn view<'a, 'b, 'c, T, F>(
         &'a mut self,
         root_path: FeaturePath,
-        ability_scores: &'b AbilityScores,
+        ability_scores: &'b ModifiedAbilityScores,
         classes: &'c Classes,
         f: &'a F,
     ) -> Column<'a, T>
@@ -197,7 +653,13 @@ impl FeaturesState {
     {
         let mut column = Column::new().padding(2).spacing(8);
 
-        let FeaturesState { feature_state } = self;
+        let FeaturesState {
+            feature_state,
+            notice,
+        } = self;
+        if let Some(notice) = notice {
+            column = column.push(Text::new(notice.clone()).size(16));
+        }
 
         for state in feature_state {
             column = column.push(state.view(root_path.clone(), ability_scores, classes, f));
@@ -208,27 +670,246 @@ impl FeaturesState {
 }
 
 impl FeatureState {
-    pub fn effects(&self) -> Vec<Effect> {
+    pub fn effects(
+        &self,
+        parent_path: FeaturePath,
+        concentrating_on: Option<&FeaturePath>,
+    ) -> Vec<ActiveEffect> {
         let FeatureState {
+            overlayed_feature,
             effects_state,
             children,
             ..
         } = self;
+        let this_path = parent_path.with_child(overlayed_feature.name.clone());
+        if overlayed_feature.requires_concentration() && concentrating_on != Some(&this_path) {
+            return vec![];
+        }
+        if overlayed_feature.requires_activation() && !self.active {
+            return vec![];
+        }
+
         let mut effects = vec![];
-        effects.extend(effects_state.effect());
+        effects.extend(
+            effects_state
+                .effect()
+                .into_iter()
+                .map(|effect| ActiveEffect::new(effect, this_path.clone())),
+        );
         for child in children {
-            effects.extend(child.effects())
+            effects.extend(child.effects(this_path.clone(), concentrating_on))
         }
         effects
     }
 
-    pub fn apply_effects<'a, 'b>(&'a mut self, effects: &'b Vec<Effect>) {
+    fn find_roll(&self, path: &[String], roll_name: &str) -> Option<&RollState> {
+        match path.split_first() {
+            None => self
+                .rolls_state
+                .iter()
+                .find(|roll| roll.name() == roll_name),
+            Some((name, rest)) => self
+                .children
+                .iter()
+                .find(|child| &child.feature.name == name)
+                .and_then(|child| child.find_roll(rest, roll_name)),
+        }
+    }
+
+    fn path_of(&self, parent: FeaturePath, name: &str) -> Option<FeaturePath> {
+        let path = parent.with_child(self.feature.name.clone());
+        if self.feature.name == name {
+            return Some(path);
+        }
+        self.children
+            .iter()
+            .find_map(|child| child.path_of(path.clone(), name))
+    }
+
+    pub fn feature_name(&self, path: &FeaturePath) -> Option<String> {
+        match path.matches(self.feature.name.clone()) {
+            (true, remaining) if remaining.is_empty() => Some(self.feature.name.clone()),
+            (true, remaining) => self
+                .children
+                .iter()
+                .find_map(|child| child.feature_name(&remaining)),
+            (false, _) => None,
+        }
+    }
+
+    fn reset_effects(&mut self) {
+        for roll_state in self.rolls_state.iter_mut() {
+            roll_state.reset();
+        }
+        for child in self.children.iter_mut() {
+            child.reset_effects();
+        }
+    }
+
+    pub fn apply_effects<'a, 'b>(&'a mut self, effects: &'b Vec<ActiveEffect>) {
         for effect in effects {
             self.apply_effect(effect)
         }
     }
 
-    pub fn apply_effect<'a, 'b>(&'a mut self, effect: &'b Effect) {
+    fn evaluate_formulas(&mut self, context: &FormulaContext) {
+        if let Some(slot_state) = self.slot_state.as_mut() {
+            slot_state.evaluate(context);
+        }
+        for child in self.children.iter_mut() {
+            child.evaluate_formulas(context);
+        }
+    }
+
+    fn find_slot(&self, path: &[String]) -> Option<&SlotState> {
+        match path.split_first() {
+            None => self.slot_state.as_ref(),
+            Some((name, rest)) => self
+                .children
+                .iter()
+                .find(|child| &child.feature.name == name)
+                .and_then(|child| child.find_slot(rest)),
+        }
+    }
+
+    fn resolve_slot(
+        &self,
+        mut parent: Vec<String>,
+        name: &str,
+        rest: &[String],
+    ) -> Option<Vec<String>> {
+        parent.push(self.feature.name.clone());
+        if self.feature.name == name && self.find_slot(rest).is_some() {
+            parent.extend(rest.iter().cloned());
+            return Some(parent);
+        }
+        self.children
+            .iter()
+            .find_map(|child| child.resolve_slot(parent.clone(), name, rest))
+    }
+
+    fn find(&self, path: &FeaturePath) -> Option<&FeatureState> {
+        match path.matches(self.feature.name.clone()) {
+            (true, remaining) if remaining.is_empty() => Some(self),
+            (true, remaining) => self
+                .children
+                .iter()
+                .find_map(|child| child.find(&remaining)),
+            (false, _) => None,
+        }
+    }
+
+    fn post_roll_modifiers(&self, parent_path: FeaturePath, modifiers: &mut Vec<PostRollModifier>) {
+        let this_path = parent_path.with_child(self.feature.name.clone());
+        if let Some(post_roll) = &self.overlayed_feature.post_roll {
+            modifiers.push(PostRollModifier {
+                path: this_path.clone(),
+                name: self.overlayed_feature.name.clone(),
+                post_roll: post_roll.clone(),
+            });
+        }
+        for child in self.children.iter() {
+            child.post_roll_modifiers(this_path.clone(), modifiers);
+        }
+    }
+
+    fn active(&self, names: &mut Vec<String>) {
+        if self.active {
+            names.push(self.overlayed_feature.name.clone());
+        }
+        for child in self.children.iter() {
+            child.active(names);
+        }
+    }
+
+    fn spell_costs(&self, costs: &mut Vec<(String, SlotAmount)>) {
+        if let (true, Some(cost)) = (self.active, &self.overlayed_feature.cost) {
+            if cost.is_per_spell_level() {
+                costs.push((self.overlayed_feature.name.clone(), cost.clone()));
+            }
+        }
+        for child in self.children.iter() {
+            child.spell_costs(costs);
+        }
+    }
+
+    fn end_activations(&mut self) -> IsDirty {
+        let mut ended = std::mem::replace(&mut self.active, false);
+        for child in self.children.iter_mut() {
+            ended = child.end_activations() || ended;
+        }
+        ended
+    }
+
+    fn find_slot_mut(&mut self, path: &[String]) -> Option<&mut SlotState> {
+        match path.split_first() {
+            None => self.slot_state.as_mut(),
+            Some((name, rest)) => self
+                .children
+                .iter_mut()
+                .find(|child| &child.feature.name == name)
+                .and_then(|child| child.find_slot_mut(rest)),
+        }
+    }
+
+    fn derive_slot(
+        &mut self,
+        path: &[String],
+        max: isize,
+        recovery: Recovery,
+        description: Option<String>,
+    ) -> IsDirty {
+        let (name, rest) = match path.split_first() {
+            Some(split) => split,
+            None => {
+                let mut dirty = false;
+                if description.is_some() && self.feature.description != description {
+                    self.feature.description = description;
+                    dirty = true;
+                }
+                return match self.slot_state.as_mut() {
+                    Some(slot_state) => slot_state.set_max(max) || dirty,
+                    None if max > 0 => {
+                        let slot = Slot::new(max, Some(max)).recovers_on(recovery);
+                        self.feature.slot = Some(slot.clone());
+                        self.slot_state = Some(SlotState::from(slot));
+                        true
+                    }
+                    None => dirty,
+                };
+            }
+        };
+
+        match self
+            .children
+            .iter_mut()
+            .find(|child| &child.feature.name == name)
+        {
+            Some(child) => child.derive_slot(rest, max, recovery, description),
+            None if max > 0 => {
+                let feature = Feature::named(name);
+                let mut child = FeatureState::from(feature.clone(), &HashMap::new());
+                child.derive_slot(rest, max, recovery, description);
+                self.feature.children.push(feature);
+                self.children.push(child);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn rest(&mut self, rest: &Recovery) -> IsDirty {
+        let mut dirty = match self.slot_state.as_mut() {
+            Some(slot_state) => slot_state.rest(rest),
+            None => false,
+        };
+        for child in self.children.iter_mut() {
+            dirty = child.rest(rest) || dirty;
+        }
+        dirty
+    }
+
+    pub fn apply_effect<'a, 'b>(&'a mut self, active_effect: &'b ActiveEffect) {
         let FeatureState {
             feature,
             rolls_state,
@@ -236,13 +917,14 @@ impl FeatureState {
             ..
         } = self;
 
-        match effect.clone() {
-            Effect::Roll { bonus, scope } => {
+        match active_effect.effect().clone() {
+            Effect::Roll { bonus, scope, name } => {
                 let (matches, scope) = scope.matches(feature);
-                let effect = Effect::Roll {
+                let effect = active_effect.with_effect(Effect::Roll {
                     bonus: bonus.clone(),
                     scope: scope.clone(),
-                };
+                    name,
+                });
                 if (matches) {
                     for roll_state in rolls_state {
                         roll_state.apply(&effect)
@@ -264,6 +946,7 @@ impl FeatureState {
             effects_state,
             rolls_state,
             overlayed_feature,
+            ..
         } = self;
         let mut feature = feature.clone();
         let child_names = feature
@@ -276,6 +959,8 @@ impl FeatureState {
         for child in children {
             if child_names.contains(&child.feature.name) {
                 feature.children.push(child.persistable())
+            } else if let Some(reference) = child.reference() {
+                feature.children.push(reference)
             }
         }
 
@@ -294,8 +979,46 @@ impl FeatureState {
         feature
     }
 
+    /// A feature that comes from a template, saved as a reference to it by name along with the
+    /// slots on and under it, if it has any.
+    fn reference(&self) -> Option<Feature> {
+        let slot = self.slot_state.as_ref().map(SlotState::persistable);
+        let children = self
+            .children
+            .iter()
+            .filter_map(FeatureState::reference)
+            .collect::<Vec<Feature>>();
+        if slot.is_none() && children.is_empty() {
+            return None;
+        }
+        Some(Feature {
+            slot,
+            children,
+            ..Feature::named(&self.feature.name)
+        })
+    }
+
+    /// Marks the slots on and under a feature from a template as never saved, so they start full.
+    fn unsaved(mut self) -> FeatureState {
+        self.slot_state = self
+            .slot_state
+            .map(|slot_state| SlotState::unsaved(slot_state.persistable()));
+        self.children = self
+            .children
+            .into_iter()
+            .map(FeatureState::unsaved)
+            .collect();
+        self
+    }
+
     pub fn from(feature: Feature, feature_templates: &HashMap<String, Feature>) -> FeatureState {
         let original_feature = feature.clone();
+        let feature_slot = feature.slot.clone();
+        let child_names = feature
+            .children
+            .iter()
+            .map(|child| child.name.clone())
+            .collect::<HashSet<String>>();
         let overlayed_feature =
             feature
                 .templates
@@ -313,7 +1036,10 @@ impl FeatureState {
             .map(|template| template.overlay(&overlayed_feature))
             .unwrap_or(overlayed_feature);
 
-        let slot_state = overlayed_feature.slot.clone().map(SlotState::from);
+        let slot_state = match (&feature_slot, overlayed_feature.slot.clone()) {
+            (None, Some(slot)) => Some(SlotState::unsaved(slot)),
+            (_, slot) => slot.map(SlotState::from),
+        };
 
         FeatureState {
             feature: original_feature,
@@ -322,7 +1048,15 @@ impl FeatureState {
                 .children
                 .clone()
                 .into_iter()
-                .map(|f| FeatureState::from(f, feature_templates))
+                .map(|f| {
+                    let saved = child_names.contains(&f.name);
+                    let child = FeatureState::from(f, feature_templates);
+                    if saved {
+                        child
+                    } else {
+                        child.unsaved()
+                    }
+                })
                 .collect(),
             slot_state,
             effects_state: EffectsState::from(overlayed_feature.effects.clone()),
@@ -332,6 +1066,12 @@ impl FeatureState {
                 .into_iter()
                 .map(RollState::from)
                 .collect(),
+            concentrate_button: button::State::default(),
+            conversion_buttons: vec![],
+            active: false,
+            activate_button: button::State::default(),
+            table_button: button::State::default(),
+            generated: None,
         }
     }
 
@@ -346,6 +1086,21 @@ impl FeatureState {
                     }
                 })])
             }
+            FeatureMessage::Concentrate(_)
+            | FeatureMessage::Convert(_)
+            | FeatureMessage::Activate(_)
+            | FeatureMessage::RollTable(_, _)
+            | FeatureMessage::Roll(_, _) => false,
+            FeatureMessage::ToggleRollBreakdown(path, roll_name) => {
+                self.apply_all(&vec![(path, &|feature_state: &mut FeatureState| {
+                    for roll_state in feature_state.rolls_state.iter_mut() {
+                        if roll_state.name() == roll_name {
+                            roll_state.toggle_breakdown();
+                        }
+                    }
+                    false
+                })])
+            }
         }
     }
 
@@ -388,6 +1143,7 @@ impl FeatureState {
                     children,
                     effects_state,
                     rolls_state,
+                    ..
                 } = self;
                 for child in children {
                     dirty_children = child.apply_all(&apply_to_children) || dirty_children;
@@ -411,6 +1167,7 @@ impl FeatureState {
             children,
             effects_state,
             rolls_state,
+            ..
         } = self;
 
         let mut path = path.clone();
@@ -441,7 +1198,7 @@ impl FeatureState {
     pub fn view<'a, 'b, 'c, T, F>(
         &'a mut self,
         parent_path: FeaturePath,
-        ability_scores: &'b AbilityScores,
+        ability_scores: &'b ModifiedAbilityScores,
         classes: &'c Classes,
         f: &'a F,
     ) -> Column<'a, T>
@@ -456,6 +1213,12 @@ impl FeatureState {
             children,
             effects_state,
             rolls_state,
+            concentrate_button,
+            conversion_buttons,
+            active,
+            activate_button,
+            table_button,
+            ..
         } = self;
 
         let this_path = parent_path.with_child(feature.name.clone());
@@ -476,11 +1239,12 @@ impl FeatureState {
             description,
             slot,
             children,
-            show_reset_chidren,
+            show_reset_children,
             child_display_orientation,
             effects,
             rolls,
             templates,
+            ..
         } = feature;
         let slot_path = this_path.clone();
         let mut header_row: Row<'a, T> = Row::new()
@@ -489,6 +1253,38 @@ impl FeatureState {
 
         header_row = header_row.push(FeatureState::slot_view(slot_state, &slot_path, f));
 
+        if overlayed_feature.requires_concentration() {
+            header_row = header_row.push(
+                Button::new(concentrate_button, Text::new("Concentrate").size(16))
+                    .on_press(f(FeatureMessage::Concentrate(this_path.clone())))
+                    .padding(8),
+            );
+        }
+
+        if let Some(cost) = &overlayed_feature.cost {
+            let text = if *active {
+                "Cancel".to_string()
+            } else {
+                format!("Use ({})", cost)
+            };
+            header_row = header_row.push(
+                Button::new(activate_button, Text::new(text).size(16))
+                    .on_press(f(FeatureMessage::Activate(this_path.clone())))
+                    .padding(8),
+            );
+        }
+
+        if let Some(table) = &overlayed_feature.table {
+            header_row = header_row.push(
+                Button::new(table_button, Text::new(format!("Roll {}", table)).size(16))
+                    .on_press(f(FeatureMessage::RollTable(
+                        this_path.clone(),
+                        table.clone(),
+                    )))
+                    .padding(8),
+            );
+        }
+
         let mut column = Column::new().push(header_row);
 
         // column = column.push(Row::new().push(Text::new(format!("With {}", templates.join(", ")))));
@@ -501,6 +1297,14 @@ impl FeatureState {
             column = column.push(effects_state.view().padding(2))
         }
 
+        if !overlayed_feature.conversions.is_empty() {
+            column = column.push(FeatureState::conversions_view(
+                &overlayed_feature.conversions,
+                conversion_buttons,
+                f,
+            ))
+        }
+
         let display_orientation = child_display_orientation
             .as_ref()
             .unwrap_or(&DisplayOrientation::Rows)
@@ -509,8 +1313,10 @@ impl FeatureState {
         if (!rolls_state.is_empty()) {
             column = column.push(FeatureState::rolls_view(
                 rolls_state,
+                &this_path,
                 ability_scores,
                 classes,
+                f,
             ))
         }
 
@@ -530,22 +1336,64 @@ impl FeatureState {
         column.width(Length::FillPortion(1))
     }
 
-    fn rolls_view<'a, 'b, 'c, T>(
+    fn rolls_view<'a, 'b, 'c, T, F>(
         rolls_states: &'a mut Vec<RollState>,
-        ability_scores: &'b AbilityScores,
+        feature_path: &FeaturePath,
+        ability_scores: &'b ModifiedAbilityScores,
         classes: &'c Classes,
+        f: &'a F,
     ) -> Column<'a, T>
     where
         T: Debug + Clone + 'a,
+        F: Fn(FeatureMessage) -> T + 'a,
     {
         let mut column = Column::new();
         for roll_state in rolls_states {
-            column = column.push(Row::new().push(roll_state.view(ability_scores, classes)))
+            let toggle = f(FeatureMessage::ToggleRollBreakdown(
+                feature_path.clone(),
+                roll_state.name().to_string(),
+            ));
+            let roll = f(FeatureMessage::Roll(
+                feature_path.clone(),
+                roll_state.name().to_string(),
+            ));
+            column =
+                column.push(Row::new().push(roll_state.view(ability_scores, classes, toggle, roll)))
         }
 
         column
     }
 
+    fn conversions_view<'a, 'b, T, F>(
+        conversions: &Vec<Conversion>,
+        conversion_buttons: &'a mut Vec<button::State>,
+        f: &'b F,
+    ) -> Column<'a, T>
+    where
+        T: Debug + Clone + 'a,
+        F: Fn(FeatureMessage) -> T + 'b,
+    {
+        conversion_buttons.resize_with(conversions.len(), button::State::default);
+        let mut column = Column::new().spacing(4);
+        let mut row = Row::new().spacing(8);
+        for (index, (conversion, button)) in conversions
+            .iter()
+            .zip(conversion_buttons.iter_mut())
+            .enumerate()
+        {
+            if index > 0 && index % CONVERSIONS_PER_ROW == 0 {
+                column = column.push(row);
+                row = Row::new().spacing(8);
+            }
+            row = row.push(
+                Button::new(button, Text::new(conversion.name()).size(16))
+                    .on_press(f(FeatureMessage::Convert(conversion.clone())))
+                    .padding(8),
+            );
+        }
+        column.push(row)
+    }
+
     fn slot_view<'a, 'b, T, F>(
         slot_state: &'a mut Option<SlotState>,
         slot_path: &FeaturePath,
@@ -584,6 +1432,7 @@ mod test {
         feature.effects = vec![Effect::Roll {
             bonus: RollBonus::Proficiency,
             scope: scope,
+            name: None,
         }];
 
         (feature.name.clone(), feature)
@@ -597,6 +1446,7 @@ mod test {
         feature.effects = vec![Effect::Roll {
             bonus: RollBonus::Proficiency,
             scope: scope,
+            name: None,
         }];
         (feature.name.clone(), feature)
     }
@@ -609,6 +1459,7 @@ mod test {
         feature.effects = vec![Effect::Roll {
             bonus: RollBonus::Proficiency,
             scope: scope,
+            name: None,
         }];
         (feature.name.clone(), feature)
     }
@@ -654,6 +1505,7 @@ mod test {
         feature.effects = vec![Effect::Roll {
             bonus: RollBonus::Proficiency,
             scope: scope,
+            name: None,
         }];
 
         (feature.name.clone(), feature)
@@ -762,4 +1614,154 @@ mod test {
             serde_json::to_string(&proficiencies).unwrap_or("".to_string())
         );
     }
+
+    #[test]
+    fn conversions_change_both_slots_or_neither() {
+        use crate::core::conversion::Conversion;
+        use crate::core::feature::{FeatureMessage, FeaturesState};
+        use crate::core::slot::Slot;
+
+        let with_slot = |name: &str, current: isize, max: isize| Feature {
+            slot: Some(Slot::new(current, Some(max))),
+            ..Feature::named(name)
+        };
+        let points = vec!["Font of Magic".to_string(), "Sorcery Points".to_string()];
+        let level_2 = vec!["Spell Slots".to_string(), "Level 2".to_string()];
+        let mut features = FeaturesState::from(
+            vec![
+                Feature::named("Font of Magic").with_children(vec![with_slot(
+                    "Sorcery Points",
+                    4,
+                    5,
+                )]),
+                Feature::named("Spell Slots").with_children(vec![with_slot("Level 2", 0, 3)]),
+            ],
+            &HashMap::new(),
+        );
+        let create_slot: Conversion = serde_json::from_str(
+            r#"{ "name": "Create Level 2 Slot",
+                "spend": { "path": ["Font of Magic", "Sorcery Points"], "amount": 3 },
+                "gain": { "path": ["Spell Slots", "Level 2"], "amount": 1 } }"#,
+        )
+        .unwrap();
+        let current = |features: &FeaturesState, path: &Vec<String>| {
+            features.find_slot(path).map(|slot| slot.current())
+        };
+
+        assert!(features.update(FeatureMessage::Convert(create_slot.clone())));
+        assert_eq!(current(&features, &points), Some(1));
+        assert_eq!(current(&features, &level_2), Some(1));
+
+        assert!(features.notice.is_none());
+
+        assert!(!features.update(FeatureMessage::Convert(create_slot)));
+        assert_eq!(current(&features, &points), Some(1));
+        assert_eq!(current(&features, &level_2), Some(1));
+        assert_eq!(
+            features.notice.as_deref(),
+            Some("Cannot Create Level 2 Slot: needs 3 Font of Magic > Sorcery Points, 1 left")
+        );
+    }
+
+    #[test]
+    fn activated_features_pay_their_cost_for_their_effects() {
+        use crate::core::feature::{FeatureMessage, FeaturesState};
+        use crate::core::roll::Advantage;
+        use crate::core::slot::Slot;
+
+        let heightened = Feature {
+            cost: serde_json::from_str(
+                r#"{ "path": ["Font of Magic", "Sorcery Points"], "amount": 3 }"#,
+            )
+            .unwrap(),
+            effects: vec![Effect::Roll {
+                bonus: RollBonus::Advantage(Advantage::Advantage),
+                scope: RollScope::default(),
+                name: None,
+            }],
+            ..Feature::named("Heightened Spell")
+        };
+        let points = Feature {
+            slot: Some(Slot::new(4, Some(5))),
+            ..Feature::named("Sorcery Points")
+        };
+        let mut features = FeaturesState::from(
+            vec![Feature::named("Font of Magic").with_children(vec![points, heightened])],
+            &HashMap::new(),
+        );
+        let path = FeaturePath::of(vec![
+            "Font of Magic".to_string(),
+            "Heightened Spell".to_string(),
+        ]);
+        let points = vec!["Font of Magic".to_string(), "Sorcery Points".to_string()];
+        let current = |features: &FeaturesState| features.find_slot(&points).unwrap().current();
+
+        assert!(features.effects(None).is_empty());
+        assert!(features.update(FeatureMessage::Activate(path.clone())));
+        assert_eq!(current(&features), 1);
+        assert_eq!(features.effects(None).len(), 1);
+        assert_eq!(features.active(), vec!["Heightened Spell".to_string()]);
+
+        assert!(features.end_activations());
+        assert!(features.effects(None).is_empty());
+        assert!(!features.update(FeatureMessage::Activate(path.clone())));
+        assert_eq!(current(&features), 1);
+    }
+
+    #[test]
+    fn costs_by_spell_level_are_paid_when_the_spell_is_cast() {
+        use crate::core::feature::{FeatureMessage, FeaturesState};
+        use crate::core::slot::Slot;
+
+        let twinned = Feature {
+            cost: serde_json::from_str(
+                r#"{ "path": ["Sorcery Points"], "amount": 1, "per_spell_level": true }"#,
+            )
+            .unwrap(),
+            ..Feature::named("Twinned Spell")
+        };
+        let points = Feature {
+            slot: Some(Slot::new(2, Some(5))),
+            ..Feature::named("Sorcery Points")
+        };
+        let mut features = FeaturesState::from(vec![points, twinned], &HashMap::new());
+        let path = FeaturePath::of(vec!["Twinned Spell".to_string()]);
+        let points = vec!["Sorcery Points".to_string()];
+
+        assert!(features.update(FeatureMessage::Activate(path)));
+        assert_eq!(features.find_slot(&points).unwrap().current(), 2);
+        let costs = features.spell_costs();
+        assert_eq!(costs.len(), 1);
+        assert_eq!(costs[0].1.for_spell_level(0).amount(), 1);
+        assert_eq!(
+            features.refuse_spend(&costs[0].1.for_spell_level(3), "cast Fireball"),
+            Some("Cannot cast Fireball: needs 3 Sorcery Points, 2 left".to_string())
+        );
+        assert!(features
+            .spend(&costs[0].1.for_spell_level(2), "cast Shatter")
+            .is_ok());
+        assert_eq!(features.find_slot(&points).unwrap().current(), 0);
+    }
+
+    #[test]
+    fn generated_features_keep_apart_from_the_characters_own() {
+        use crate::core::feature::FeaturesState;
+
+        let mut features = FeaturesState::from(vec![Feature::named("Spells")], &HashMap::new());
+        let generated = Feature::named("Spells").with_children(vec![Feature::named("Shield")]);
+        features.set_generated("Spellbook", generated.clone(), &HashMap::new());
+        features.set_generated("Spellbook", generated, &HashMap::new());
+        let names = |features: &FeaturesState| {
+            features
+                .feature_state
+                .iter()
+                .map(|state| state.feature.name.clone())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(names(&features), vec!["Spells", "Spells (Spellbook)"]);
+        assert_eq!(features.persistable().len(), 1);
+
+        features.remove_generated("Spellbook");
+        assert_eq!(names(&features), vec!["Spells"]);
+    }
 }
@@ -59,10 +59,26 @@ impl FeaturePath {
         }
     }
 
+    pub fn segments(&self) -> &[String] {
+        &self.path
+    }
+
     pub fn is_empty(&self) -> bool {
         self.path.is_empty()
     }
 
+    pub fn first(&self) -> Option<String> {
+        self.path.first().cloned()
+    }
+
+    pub fn last(&self) -> Option<String> {
+        self.path.last().cloned()
+    }
+
+    pub fn breadcrumb(&self) -> String {
+        self.path.join(" › ")
+    }
+
     pub fn with_child(&self, child: String) -> FeaturePath {
         let mut path = self.path.clone();
         path.push(child);
@@ -0,0 +1,396 @@
+use crate::character::class::Classes;
+use crate::core::ability_score::{Ability, ModifiedAbilityScores};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Debug, Display, Formatter};
+
+/// A number in character or template data: either a literal, or an expression such as
+/// `"CHA mod"`, `"Sorcerer level"`, `"prof"` or `"max(1, floor(level / 2))"`.
+///
+/// Expressions support decimal numbers such as `0.5`, `+ - * /`, parentheses and the functions
+/// `min`, `max`, `floor` and `ceil`. The final result is rounded down.
+#[derive(Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum Formula {
+    Constant(isize),
+    Expression(String),
+}
+
+/// What a formula can refer to.
+pub struct FormulaContext<'a> {
+    ability_scores: &'a ModifiedAbilityScores,
+    classes: &'a Classes,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum FormulaError {
+    UnexpectedEnd,
+    UnexpectedToken(String),
+    UnknownVariable(String),
+    UnknownFunction(String),
+    WrongArgumentCount { function: String, arguments: usize },
+    DivisionByZero,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Word(String),
+    Symbol(char),
+}
+
+impl Formula {
+    /// Explains `error` from evaluating this formula, for showing in place of its value.
+    pub fn describe(&self, error: &FormulaError) -> String {
+        format!("cannot evaluate '{}' ({})", self, error)
+    }
+
+    /// The value of a literal formula, without needing a context.
+    pub fn constant(&self) -> Option<isize> {
+        match self {
+            Formula::Constant(value) => Some(*value),
+            Formula::Expression(_) => None,
+        }
+    }
+
+    pub fn value(&self, context: &FormulaContext) -> Result<isize, FormulaError> {
+        match self {
+            Formula::Constant(value) => Ok(*value),
+            Formula::Expression(expression) => {
+                let tokens = tokenize(expression)?;
+                let mut parser = Parser {
+                    tokens,
+                    position: 0,
+                    context,
+                };
+                let value = parser.expression()?;
+                match parser.peek() {
+                    None => Ok(value.floor() as isize),
+                    Some(token) => Err(FormulaError::UnexpectedToken(token.to_string())),
+                }
+            }
+        }
+    }
+}
+
+impl From<isize> for Formula {
+    fn from(value: isize) -> Formula {
+        Formula::Constant(value)
+    }
+}
+
+impl Display for Formula {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Formula::Constant(value) => write!(f, "{}", value),
+            Formula::Expression(expression) => write!(f, "{}", expression),
+        }
+    }
+}
+
+/// Formulas show up inside effect descriptions, so debug output is kept to the formula itself.
+impl Debug for Formula {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "{}", value),
+            Token::Word(word) => write!(f, "{}", word),
+            Token::Symbol(symbol) => write!(f, "{}", symbol),
+        }
+    }
+}
+
+impl Display for FormulaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FormulaError::UnexpectedEnd => write!(f, "formula ended unexpectedly"),
+            FormulaError::UnexpectedToken(token) => write!(f, "unexpected '{}'", token),
+            FormulaError::UnknownVariable(name) => write!(f, "unknown value '{}'", name),
+            FormulaError::UnknownFunction(name) => write!(f, "unknown function '{}'", name),
+            FormulaError::WrongArgumentCount {
+                function,
+                arguments,
+            } => write!(f, "{} does not take {} arguments", function, arguments),
+            FormulaError::DivisionByZero => write!(f, "division by zero"),
+        }
+    }
+}
+
+impl<'a> FormulaContext<'a> {
+    pub fn new(
+        ability_scores: &'a ModifiedAbilityScores,
+        classes: &'a Classes,
+    ) -> FormulaContext<'a> {
+        FormulaContext {
+            ability_scores,
+            classes,
+        }
+    }
+
+    fn variable(&self, name: &str) -> Result<f64, FormulaError> {
+        let words = name
+            .split_whitespace()
+            .map(|w| w.to_lowercase())
+            .collect::<Vec<String>>();
+        let words = words.iter().map(|w| w.as_str()).collect::<Vec<&str>>();
+
+        let value = match words.as_slice() {
+            ["level"] => self.classes.total_level(),
+            ["prof"] | ["proficiency"] => self.classes.proficiency_bonus(),
+            [ability, "mod"] => match parse_ability(ability) {
+                Some(ability) => self.ability_scores.get(ability).score().modifier(),
+                None => return Err(FormulaError::UnknownVariable(name.to_string())),
+            },
+            [ability] => match parse_ability(ability) {
+                Some(ability) => self.ability_scores.get(ability).score().value(),
+                None => return Err(FormulaError::UnknownVariable(name.to_string())),
+            },
+            [class @ .., "level"] => self.classes.level(&class.join(" ")),
+            _ => return Err(FormulaError::UnknownVariable(name.to_string())),
+        };
+        Ok(value as f64)
+    }
+}
+
+fn parse_ability(name: &str) -> Option<Ability> {
+    match name {
+        "str" | "strength" => Some(Ability::Strength),
+        "dex" | "dexterity" => Some(Ability::Dexterity),
+        "con" | "constitution" => Some(Ability::Constitution),
+        "int" | "intelligence" => Some(Ability::Intelligence),
+        "wis" | "wisdom" => Some(Ability::Wisdom),
+        "cha" | "charisma" => Some(Ability::Charisma),
+        _ => None,
+    }
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, FormulaError> {
+    let mut tokens = vec![];
+    let mut chars = expression.chars().peekable();
+    while let Some(c) = chars.peek().cloned() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut number = String::new();
+            while let Some(c) = chars
+                .peek()
+                .cloned()
+                .filter(|c| c.is_ascii_digit() || *c == '.')
+            {
+                number.push(c);
+                chars.next();
+            }
+            let value = number
+                .parse::<f64>()
+                .map_err(|_| FormulaError::UnexpectedToken(number.clone()))?;
+            tokens.push(Token::Number(value));
+        } else if c.is_alphabetic() {
+            let mut word = String::new();
+            while let Some(c) = chars
+                .peek()
+                .cloned()
+                .filter(|c| c.is_alphanumeric() || *c == '_' || *c == '\'')
+            {
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Word(word));
+        } else if "+-*/(),".contains(c) {
+            tokens.push(Token::Symbol(c));
+            chars.next();
+        } else {
+            return Err(FormulaError::UnexpectedToken(c.to_string()));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a, 'b> {
+    tokens: Vec<Token>,
+    position: usize,
+    context: &'a FormulaContext<'b>,
+}
+
+impl<'a, 'b> Parser<'a, 'b> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position = self.position + 1;
+        token
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), FormulaError> {
+        match self.next() {
+            Some(Token::Symbol(s)) if s == symbol => Ok(()),
+            Some(token) => Err(FormulaError::UnexpectedToken(token.to_string())),
+            None => Err(FormulaError::UnexpectedEnd),
+        }
+    }
+
+    fn expression(&mut self) -> Result<f64, FormulaError> {
+        let mut value = self.term()?;
+        loop {
+            match self.peek() {
+                Some(Token::Symbol('+')) => {
+                    self.next();
+                    value = value + self.term()?;
+                }
+                Some(Token::Symbol('-')) => {
+                    self.next();
+                    value = value - self.term()?;
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<f64, FormulaError> {
+        let mut value = self.factor()?;
+        loop {
+            match self.peek() {
+                Some(Token::Symbol('*')) => {
+                    self.next();
+                    value = value * self.factor()?;
+                }
+                Some(Token::Symbol('/')) => {
+                    self.next();
+                    let divisor = self.factor()?;
+                    if divisor == 0.0 {
+                        return Err(FormulaError::DivisionByZero);
+                    }
+                    value = value / divisor;
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    fn factor(&mut self) -> Result<f64, FormulaError> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(value),
+            Some(Token::Symbol('-')) => Ok(-self.factor()?),
+            Some(Token::Symbol('(')) => {
+                let value = self.expression()?;
+                self.expect(')')?;
+                Ok(value)
+            }
+            Some(Token::Word(word)) => {
+                if let Some(Token::Symbol('(')) = self.peek() {
+                    self.next();
+                    return self.function(word);
+                }
+                let mut name = word;
+                while let Some(Token::Word(word)) = self.peek().cloned() {
+                    self.next();
+                    name = format!("{} {}", name, word);
+                }
+                self.context.variable(&name)
+            }
+            Some(token) => Err(FormulaError::UnexpectedToken(token.to_string())),
+            None => Err(FormulaError::UnexpectedEnd),
+        }
+    }
+
+    fn function(&mut self, name: String) -> Result<f64, FormulaError> {
+        let mut arguments = vec![self.expression()?];
+        while let Some(Token::Symbol(',')) = self.peek() {
+            self.next();
+            arguments.push(self.expression()?);
+        }
+        self.expect(')')?;
+
+        let wrong_count = || FormulaError::WrongArgumentCount {
+            function: name.clone(),
+            arguments: arguments.len(),
+        };
+        match name.to_lowercase().as_str() {
+            "min" => arguments.iter().cloned().fold(None, |min: Option<f64>, v| {
+                Some(min.map(|m| m.min(v)).unwrap_or(v))
+            }),
+            "max" => arguments.iter().cloned().fold(None, |max: Option<f64>, v| {
+                Some(max.map(|m| m.max(v)).unwrap_or(v))
+            }),
+            "floor" if arguments.len() == 1 => Some(arguments[0].floor()),
+            "ceil" if arguments.len() == 1 => Some(arguments[0].ceil()),
+            "floor" | "ceil" => return Err(wrong_count()),
+            _ => return Err(FormulaError::UnknownFunction(name.clone())),
+        }
+        .ok_or_else(wrong_count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::character::class::Class;
+    use crate::core::ability_score::{AbilityScore, AbilityScores};
+
+    fn evaluate(formula: &str) -> Result<isize, FormulaError> {
+        let ability_scores = AbilityScores::default()
+            .with(Ability::Charisma, AbilityScore::of(20))
+            .with(Ability::Dexterity, AbilityScore::of(9))
+            .to_state()
+            .modified();
+        let classes = Classes::from(vec![
+            Class::new("Sorcerer".to_string(), 15),
+            Class::new("Warlock".to_string(), 2),
+        ]);
+        let context = FormulaContext::new(&ability_scores, &classes);
+        Formula::Expression(formula.to_string()).value(&context)
+    }
+
+    #[test]
+    fn evaluates_character_values() {
+        assert_eq!(evaluate("CHA mod"), Ok(5));
+        assert_eq!(evaluate("charisma"), Ok(20));
+        assert_eq!(evaluate("level"), Ok(17));
+        assert_eq!(evaluate("Sorcerer level"), Ok(15));
+        assert_eq!(evaluate("Wizard level"), Ok(0));
+        assert_eq!(evaluate("prof"), Ok(6));
+        assert_eq!(evaluate("8 + prof + CHA mod"), Ok(19));
+        assert_eq!(evaluate("max(1, DEX mod)"), Ok(1));
+        assert_eq!(evaluate("min(Warlock level, 5) * 2"), Ok(4));
+        assert_eq!(evaluate("Sorcerer level / 2"), Ok(7));
+        assert_eq!(evaluate("ceil(Sorcerer level / 2)"), Ok(8));
+        assert_eq!(evaluate("-(DEX mod)"), Ok(1));
+        assert_eq!(evaluate("0.5 * level"), Ok(8));
+        assert_eq!(evaluate("ceil(Sorcerer level * 1.5)"), Ok(23));
+    }
+
+    #[test]
+    fn reports_invalid_formulas() {
+        assert_eq!(
+            evaluate("LCK mod"),
+            Err(FormulaError::UnknownVariable("LCK mod".to_string()))
+        );
+        assert_eq!(
+            evaluate("round(level)"),
+            Err(FormulaError::UnknownFunction("round".to_string()))
+        );
+        assert_eq!(evaluate("level +"), Err(FormulaError::UnexpectedEnd));
+        assert_eq!(evaluate("level / 0"), Err(FormulaError::DivisionByZero));
+        assert_eq!(
+            evaluate("1.2.3 * level"),
+            Err(FormulaError::UnexpectedToken("1.2.3".to_string()))
+        );
+    }
+
+    #[test]
+    fn reads_numbers_and_strings() {
+        let formulas: Vec<Formula> = serde_json::from_str(r#"[3, "CHA mod"]"#).unwrap();
+        assert_eq!(
+            formulas,
+            vec![
+                Formula::Constant(3),
+                Formula::Expression("CHA mod".to_string())
+            ]
+        );
+    }
+}
@@ -1,7 +1,11 @@
 pub mod ability_score;
+pub mod conversion;
 pub mod effect;
 pub mod feature;
 pub mod feature_path;
+pub mod formula;
 pub mod overlay;
+pub mod provenance;
 pub mod roll;
 pub mod slot;
+pub mod stat;
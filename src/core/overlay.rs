@@ -6,35 +6,34 @@ pub trait Overlay {
     fn overlay(&self, overlay: &Self) -> Self;
 }
 
+/// Overlays items sharing an `overlay_by` key. The result keeps the template's order, followed
+/// by items only present in the overlay, so that it never depends on hash ordering.
 pub fn overlay_all<T>(template: &Vec<T>, overlay: &Vec<T>) -> Vec<T>
 where
     T: Debug + Clone + Overlay,
 {
-    let mut templates_by_name = HashMap::new();
-    for item in template.iter() {
-        templates_by_name.insert(item.overlay_by(), item);
-    }
-
     let mut overlays_by_name = HashMap::new();
     for item in overlay {
         overlays_by_name.insert(item.overlay_by(), item);
     }
 
-    let mut overlay_keys = overlays_by_name.keys().collect::<HashSet<&String>>();
-    overlay_keys.extend(templates_by_name.keys().collect::<HashSet<&String>>());
-
+    let mut seen = HashSet::new();
     let mut result = vec![];
 
-    for key in overlay_keys {
-        match (templates_by_name.get(key), overlays_by_name.get(key)) {
-            (Some(&template), Some(&overlay)) => result.push(template.overlay(overlay)),
-            (Some(&item), None) => {
-                result.push(item.clone());
-            }
-            (None, Some(&item)) => {
-                result.push(item.clone());
-            }
-            (None, None) => {}
+    for item in template.iter() {
+        let key = item.overlay_by();
+        if !seen.insert(key.clone()) {
+            continue;
+        }
+        match overlays_by_name.get(&key) {
+            Some(&overlay) => result.push(item.overlay(overlay)),
+            None => result.push(item.clone()),
+        }
+    }
+
+    for item in overlay.iter() {
+        if seen.insert(item.overlay_by()) {
+            result.push(overlays_by_name[&item.overlay_by()].clone());
         }
     }
 
@@ -0,0 +1,180 @@
+use crate::core::ability_score::Ability;
+use crate::core::feature_path::FeaturePath;
+use crate::core::roll::{Advantage, Dice};
+use crate::util::format_modifier;
+use std::fmt::{Display, Formatter};
+
+/// Where a part of a derived value came from.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Source {
+    Base,
+    Roll(String),
+    Ability {
+        ability: Ability,
+        modified_by: Vec<String>,
+    },
+    Proficiency(Option<FeaturePath>),
+    Feature {
+        path: FeaturePath,
+        name: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Amount {
+    Value(isize),
+    Modifier(isize),
+    Dice(Vec<Dice>),
+    Advantage(Advantage),
+    Reroll(Vec<isize>),
+    Set(isize),
+    Become(isize),
+    /// A formula that could not be evaluated, and so adds nothing.
+    Error(String),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Contribution {
+    amount: Amount,
+    source: Source,
+}
+
+impl Source {
+    pub fn feature(path: FeaturePath, name: Option<String>) -> Source {
+        Source::Feature { path, name }
+    }
+
+    /// A short human readable label, used when an ability score lists what modified it.
+    pub fn label(&self) -> String {
+        match self {
+            Source::Base => "base".to_string(),
+            Source::Roll(name) => name.clone(),
+            Source::Ability {
+                ability,
+                modified_by,
+            } => {
+                if modified_by.is_empty() {
+                    ability.abbreviation().to_string()
+                } else {
+                    format!("{} ({})", ability.abbreviation(), modified_by.join(", "))
+                }
+            }
+            Source::Proficiency(None) => "Proficiency".to_string(),
+            Source::Proficiency(Some(path)) => format!("Proficiency ({})", path.breadcrumb()),
+            Source::Feature { path, name } => name
+                .clone()
+                .or_else(|| path.last())
+                .unwrap_or_else(|| path.to_string()),
+        }
+    }
+}
+
+impl Amount {
+    /// The amount added to a plain number; dice, advantage and rerolls add nothing.
+    pub fn value(&self) -> isize {
+        match self {
+            Amount::Value(value) | Amount::Modifier(value) => *value,
+            Amount::Set(value) | Amount::Become(value) => *value,
+            _ => 0,
+        }
+    }
+}
+
+impl Contribution {
+    pub fn new(amount: Amount, source: Source) -> Contribution {
+        Contribution { amount, source }
+    }
+
+    pub fn amount(&self) -> &Amount {
+        &self.amount
+    }
+
+    pub fn source(&self) -> &Source {
+        &self.source
+    }
+}
+
+impl Display for Amount {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Amount::Value(value) => write!(f, "{}", value),
+            Amount::Modifier(0) => write!(f, "+0"),
+            Amount::Modifier(modifier) => write!(f, "{}", format_modifier(*modifier)),
+            Amount::Dice(dice) => write!(
+                f,
+                "+{}",
+                dice.iter()
+                    .map(|d| d.to_string())
+                    .collect::<Vec<String>>()
+                    .join("+")
+            ),
+            Amount::Advantage(advantage) => write!(f, "{}", advantage),
+            Amount::Reroll(values) => write!(
+                f,
+                "Reroll {}",
+                values
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Amount::Set(value) => write!(f, "set to {}", value),
+            Amount::Become(value) => write!(f, "becomes {}", value),
+            Amount::Error(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Display for Contribution {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.amount {
+            Amount::Set(_) | Amount::Become(_) => {
+                write!(f, "{} ({})", self.amount, self.source.label())
+            }
+            Amount::Error(_) => write!(f, "{}: {}", self.source.label(), self.amount),
+            _ => write!(f, "{} {}", self.amount, self.source.label()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn describes_each_contribution() {
+        let path = FeaturePath::of(vec![
+            "Proficiencies".to_string(),
+            "Saving Throw Proficiencies".to_string(),
+        ]);
+        let contributions = vec![
+            Contribution::new(
+                Amount::Modifier(4),
+                Source::Ability {
+                    ability: Ability::Charisma,
+                    modified_by: vec!["Amulet".to_string()],
+                },
+            ),
+            Contribution::new(Amount::Modifier(3), Source::Proficiency(Some(path))),
+            Contribution::new(
+                Amount::Modifier(1),
+                Source::feature(
+                    FeaturePath::of(vec![
+                        "Equipment".to_string(),
+                        "Wand of the War Mage".to_string(),
+                    ]),
+                    None,
+                ),
+            ),
+        ];
+
+        assert_eq!(
+            contributions
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<String>>()
+                .join(", "),
+            "+4 CHA (Amulet), +3 Proficiency (Proficiencies › Saving Throw Proficiencies), +1 Wand of the War Mage"
+        );
+    }
+}
@@ -0,0 +1,262 @@
+use crate::core::feature_path::FeaturePath;
+use crate::core::roll::rollable::RollOutcome;
+use crate::core::roll::Dice;
+use crate::core::slot::SlotAmount;
+use iced::{button, Align, Button, Column, Row, Text};
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+const DISPLAYED_ENTRIES: usize = 10;
+
+#[derive(Debug, Clone, Default)]
+pub struct RollLog {
+    entries: Vec<RollLogEntry>,
+    /// Why the last amendment was refused.
+    notice: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RollLogEntry {
+    label: String,
+    outcome: RollOutcome,
+    note: Option<String>,
+    amendments: Vec<Amendment>,
+    amend_buttons: Vec<button::State>,
+}
+
+/// Extra dice a feature adds to (or takes from) a roll after it is made, such as Bend Luck.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PostRoll {
+    dice: Vec<Dice>,
+    /// Whether the dice can be subtracted from the roll instead of added.
+    #[serde(default)]
+    subtract: bool,
+    cost: Option<SlotAmount>,
+}
+
+/// A feature that can amend logged rolls.
+#[derive(Debug, Clone)]
+pub struct PostRollModifier {
+    pub path: FeaturePath,
+    pub name: String,
+    pub post_roll: PostRoll,
+}
+
+#[derive(Debug, Clone)]
+pub struct Amendment {
+    source: String,
+    outcome: RollOutcome,
+    subtract: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum RollLogMessage {
+    /// Amend a logged roll, by its position in the log, with the modifier at the path.
+    Amend {
+        entry: usize,
+        modifier: FeaturePath,
+        subtract: bool,
+    },
+}
+
+impl PostRoll {
+    pub fn dice(&self) -> Vec<Dice> {
+        self.dice.clone()
+    }
+
+    pub fn cost(&self) -> Option<&SlotAmount> {
+        self.cost.as_ref()
+    }
+}
+
+impl Amendment {
+    pub fn new(source: String, outcome: RollOutcome, subtract: bool) -> Amendment {
+        Amendment {
+            source,
+            outcome,
+            subtract,
+        }
+    }
+
+    fn value(&self) -> isize {
+        if self.subtract {
+            -self.outcome.total()
+        } else {
+            self.outcome.total()
+        }
+    }
+}
+
+impl RollLogEntry {
+    pub fn new(label: String, outcome: RollOutcome, note: Option<String>) -> RollLogEntry {
+        RollLogEntry {
+            label,
+            outcome,
+            note,
+            amendments: vec![],
+            amend_buttons: vec![],
+        }
+    }
+
+    /// The original total with every amendment applied.
+    pub fn total(&self) -> isize {
+        self.outcome.total()
+            + self
+                .amendments
+                .iter()
+                .map(|amendment| amendment.value())
+                .sum::<isize>()
+    }
+
+    fn text(&self) -> String {
+        let mut text = format!("{}: {}", self.label, self.outcome);
+        if !self.amendments.is_empty() {
+            for amendment in self.amendments.iter() {
+                let sign = if amendment.subtract { "-" } else { "+" };
+                text = format!(
+                    "{} {} {} ({})",
+                    text, sign, amendment.outcome, amendment.source
+                );
+            }
+            text = format!("{} = {}", text, self.total());
+        }
+        match &self.note {
+            Some(note) => format!("{} - {}", text, note),
+            None => text,
+        }
+    }
+}
+
+impl RollLog {
+    pub fn record(&mut self, entry: RollLogEntry) {
+        self.entries.push(entry);
+    }
+
+    /// How many rolls have been logged.
+    pub fn count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The entry at `entry` as the log shows it.
+    pub fn text_of(&self, entry: usize) -> Option<String> {
+        self.entries.get(entry).map(|entry| entry.text())
+    }
+
+    /// Shows why an amendment was refused, or clears it with `None`.
+    pub fn notify(&mut self, notice: Option<String>) {
+        self.notice = notice;
+    }
+
+    /// Adds an amendment to the entry at `entry`, keeping the original outcome.
+    pub fn amend(&mut self, entry: usize, amendment: Amendment) -> bool {
+        match self.entries.get_mut(entry) {
+            Some(entry) => {
+                entry.amendments.push(amendment);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn view<'a, T, F>(
+        &'a mut self,
+        modifiers: &Vec<PostRollModifier>,
+        f: &'a F,
+    ) -> Column<'a, T>
+    where
+        T: Debug + Clone + 'a,
+        F: Fn(RollLogMessage) -> T,
+    {
+        let mut column = Column::new()
+            .spacing(2)
+            .push(Row::new().push(Text::new("Rolls").size(24)));
+        if let Some(notice) = &self.notice {
+            column = column.push(Text::new(notice.clone()).size(16));
+        }
+
+        let amend_options = modifiers
+            .iter()
+            .flat_map(|modifier| {
+                let dice = modifier
+                    .post_roll
+                    .dice
+                    .iter()
+                    .map(|d| d.to_string())
+                    .collect::<Vec<String>>()
+                    .join("+");
+                let mut options = vec![(
+                    modifier.path.clone(),
+                    false,
+                    format!("+{} {}", dice, modifier.name),
+                )];
+                if modifier.post_roll.subtract {
+                    options.push((
+                        modifier.path.clone(),
+                        true,
+                        format!("-{} {}", dice, modifier.name),
+                    ));
+                }
+                options
+            })
+            .collect::<Vec<(FeaturePath, bool, String)>>();
+
+        for (index, entry) in self
+            .entries
+            .iter_mut()
+            .enumerate()
+            .rev()
+            .take(DISPLAYED_ENTRIES)
+        {
+            let mut row = Row::new()
+                .spacing(8)
+                .align_items(Align::Center)
+                .push(Text::new(entry.text()).size(16));
+
+            entry
+                .amend_buttons
+                .resize_with(amend_options.len(), button::State::default);
+            for ((path, subtract, label), amend_button) in
+                amend_options.iter().zip(entry.amend_buttons.iter_mut())
+            {
+                row = row.push(
+                    Button::new(amend_button, Text::new(label.clone()).size(12))
+                        .on_press(f(RollLogMessage::Amend {
+                            entry: index,
+                            modifier: path.clone(),
+                            subtract: *subtract,
+                        }))
+                        .padding(4),
+                );
+            }
+            column = column.push(row);
+        }
+
+        column
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::roll::rollable::Rollable;
+    use rand::rngs::mock::StepRng;
+    use std::collections::HashSet;
+
+    #[test]
+    fn amendments_keep_the_original_outcome() {
+        let roll = |bonus: isize| {
+            Rollable::from(vec![], HashSet::new(), vec![], bonus).roll(&mut StepRng::new(0, 0))
+        };
+        let mut log = RollLog::default();
+        log.record(RollLogEntry::new("Attack".to_string(), roll(12), None));
+
+        assert!(log.amend(0, Amendment::new("Bend Luck".to_string(), roll(3), true)));
+        assert!(!log.amend(1, Amendment::new("Bend Luck".to_string(), roll(3), true)));
+        assert_eq!(log.entries[0].outcome.total(), 12);
+        assert_eq!(log.entries[0].total(), 9);
+        assert_eq!(
+            log.entries[0].text(),
+            "Attack: 12 ([]+12) - 3 ([]+3) (Bend Luck) = 9"
+        );
+    }
+}
@@ -1,16 +1,19 @@
 use crate::character::class::{Class, Classes};
-use crate::core::ability_score::{Ability, AbilityScores, ModifiedAbilityScores};
-use crate::core::effect::Effect;
+use crate::core::ability_score::{Ability, ModifiedAbilityScores};
+use crate::core::effect::{stack, ActiveEffect, Effect, EffectBonus};
 use crate::core::feature::Feature;
 use crate::core::feature_path::FeaturePath;
+use crate::core::formula::{Formula, FormulaContext};
 use crate::core::overlay::Overlay;
+use crate::core::provenance::{Amount, Contribution, Source};
 use crate::core::roll::rollable::Rollable;
-use iced::{Column, Element, Length, Row, Text};
+use iced::{button, Button, Column, Element, Length, Row, Text};
 use serde::export::fmt::Debug;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Formatter};
 
+pub mod log;
 pub mod rollable;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
@@ -23,6 +26,13 @@ impl Dice {
     pub fn new(count: isize, sides: isize) -> Dice {
         Dice { count, sides }
     }
+
+    pub fn times(&self, multiplier: isize) -> Dice {
+        Dice {
+            count: self.count * multiplier,
+            sides: self.sides,
+        }
+    }
 }
 
 impl Display for Dice {
@@ -149,6 +159,10 @@ impl Roll {
     pub fn tags(&mut self, tags: HashMap<String, Vec<String>>) {
         self.tags = Roll::merge_tags(&self.tags, &tags);
     }
+
+    pub fn bonus(&mut self, bonus: RollBonus) {
+        self.bonuses.push(bonus);
+    }
 }
 
 fn isNoneOr<'a, 'b, T>(option: &'a Option<T>, compare_to: &'b T) -> bool
@@ -173,11 +187,20 @@ where
 pub enum RollBonus {
     Reroll(HashSet<isize>),
     Advantage(Advantage),
-    Modifier(isize),
+    Modifier(Formula),
     Roll(Roll),
     Proficiency,
 }
 
+impl RollBonus {
+    fn potency(&self, context: &FormulaContext) -> isize {
+        match self {
+            RollBonus::Modifier(modifier) => modifier.value(context).unwrap_or(0),
+            _ => 0,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 pub enum Advantage {
     Advantage,
@@ -322,58 +345,94 @@ impl Display for RollScope {
 #[derive(Debug, Clone)]
 pub struct RollState {
     roll: Roll,
-    external_bonuses: Vec<RollBonus>,
+    external_bonuses: Vec<EffectBonus<RollBonus>>,
+    show_breakdown: bool,
+    breakdown_button: button::State,
+    roll_button: button::State,
 }
 
+/// Proficiency is only ever added once, and advantage and disadvantage don't stack: any
+/// number of each cancel out. Same-named external bonuses keep only the most potent one.
 fn rollable<'a, 'b, 'c>(
     roll: &'a Roll,
-    external_bonuses: &'a Vec<RollBonus>,
-    ability_scores: &'b AbilityScores,
+    external_bonuses: &'a Vec<EffectBonus<RollBonus>>,
+    ability_scores: &'b ModifiedAbilityScores,
     classes: &'c Classes,
 ) -> Rollable {
     let Roll {
         name,
-        tags,
         ability,
-        range,
         dice,
         bonuses,
+        ..
     } = roll;
 
-    let mut dice = dice.clone();
-
-    let mut bonuses = bonuses.clone();
-    if (!external_bonuses.is_empty()) {
-        bonuses.extend(external_bonuses.clone());
-    }
-
-    let mut reroll = HashSet::new();
-    let mut advantage_count = 0;
-    let mut modifier = 0;
-
+    let context = FormulaContext::new(ability_scores, classes);
+    let mut sourced_bonuses = bonuses
+        .iter()
+        .map(|b| (b.clone(), Source::Roll(name.clone())))
+        .collect::<Vec<(RollBonus, Source)>>();
+    sourced_bonuses.extend(
+        stack(external_bonuses, |b| b.potency(&context))
+            .into_iter()
+            .map(|b| (b.bonus.clone(), b.provenance())),
+    );
+
+    let mut result = Rollable::from(dice.clone(), HashSet::new(), vec![], 0);
+    let mut proficiency: Option<Source> = None;
     let mut children = vec![];
-    for bonus in bonuses {
+    for (bonus, source) in sourced_bonuses {
         match bonus {
-            RollBonus::Reroll(more) => reroll.extend(more),
-            RollBonus::Advantage(advantage) => match advantage {
-                Advantage::Advantage => advantage_count = advantage_count + 1,
-                Advantage::Disadvantage => advantage_count = advantage_count - 1,
-            },
-            RollBonus::Modifier(more) => modifier = more + modifier,
-            RollBonus::Roll(roll) => children.push(roll),
-            RollBonus::Proficiency => modifier = modifier + classes.proficiency_bonus(),
+            RollBonus::Reroll(more) => {
+                let mut values = more.iter().cloned().collect::<Vec<isize>>();
+                values.sort();
+                result.merge(Rollable::from(vec![], more, vec![], 0));
+                result.contribute(Contribution::new(Amount::Reroll(values), source));
+            }
+            RollBonus::Advantage(advantage) => {
+                result.merge(Rollable::from(
+                    vec![],
+                    HashSet::new(),
+                    vec![advantage.clone()],
+                    0,
+                ));
+                result.contribute(Contribution::new(Amount::Advantage(advantage), source));
+            }
+            RollBonus::Modifier(more) => result.contribute(Contribution::new(
+                match more.value(&context) {
+                    Ok(modifier) => Amount::Modifier(modifier),
+                    Err(e) => Amount::Error(more.describe(&e)),
+                },
+                source,
+            )),
+            RollBonus::Roll(roll) => children.push((roll, source)),
+            RollBonus::Proficiency => {
+                if proficiency.is_none() {
+                    proficiency = Some(source)
+                }
+            }
         }
     }
+    if let Some(source) = proficiency {
+        let path = match source {
+            Source::Feature { path, .. } => Some(path),
+            _ => None,
+        };
+        result.contribute(Contribution::new(
+            Amount::Modifier(classes.proficiency_bonus()),
+            Source::Proficiency(path),
+        ));
+    }
 
-    let mut result = Rollable::from(dice, reroll, advantage_count, modifier);
-
-    for roll in children {
-        let other = rollable(&roll, &vec![], ability_scores, classes);
+    children.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+    for (roll, source) in children {
+        let mut other = rollable(&roll, &vec![], ability_scores, classes);
+        other.contribute(Contribution::new(Amount::Dice(roll.dice.clone()), source));
         result.merge(other);
     }
 
     match ability {
-        Some(ability) => result.add_bonus(ability_scores.get(ability.clone()).modifier()),
+        Some(ability) => result.contribute(ability_scores.modifier_contribution(ability.clone())),
         None => {}
     }
     result
@@ -388,14 +447,35 @@ impl RollState {
         RollState {
             roll: roll,
             external_bonuses: vec![],
+            show_breakdown: false,
+            breakdown_button: button::State::default(),
+            roll_button: button::State::default(),
         }
     }
-    pub fn apply<'a, 'b>(&'a mut self, effect: &'b Effect) {
-        match effect {
-            Effect::Roll { bonus, scope } => {
+
+    pub fn reset(&mut self) {
+        self.external_bonuses = vec![];
+    }
+
+    pub fn name(&self) -> &str {
+        self.roll.name.as_str()
+    }
+
+    pub fn toggle_breakdown(&mut self) {
+        self.show_breakdown = !self.show_breakdown;
+    }
+
+    pub fn rollable(&self, ability_scores: &ModifiedAbilityScores, classes: &Classes) -> Rollable {
+        rollable(&self.roll, &self.external_bonuses, ability_scores, classes)
+    }
+    pub fn apply<'a, 'b>(&'a mut self, active_effect: &'b ActiveEffect) {
+        match active_effect.effect() {
+            Effect::Roll { bonus, scope, name } => {
+                let effect_name = name;
                 let RollState {
                     roll,
                     external_bonuses,
+                    ..
                 } = self;
                 let Roll {
                     name,
@@ -446,7 +526,11 @@ impl RollState {
                     && isNoneOrOpt(range, roll_range);
 
                 if (is_matching) {
-                    external_bonuses.push(bonus.clone())
+                    external_bonuses.push(EffectBonus::new(
+                        effect_name.clone(),
+                        active_effect.source().clone(),
+                        bonus.clone(),
+                    ))
                 }
             }
             _ => {}
@@ -455,8 +539,10 @@ impl RollState {
 
     pub fn view<'a, 'b, 'c, T>(
         &'a mut self,
-        ability_scores: &'b AbilityScores,
+        ability_scores: &'b ModifiedAbilityScores,
         classes: &'c Classes,
+        on_toggle_breakdown: T,
+        on_roll: T,
     ) -> Column<'a, T>
     where
         T: Debug + Clone + 'a,
@@ -464,6 +550,9 @@ impl RollState {
         let RollState {
             roll,
             external_bonuses,
+            show_breakdown,
+            breakdown_button,
+            roll_button,
         } = self;
 
         let rollable = rollable(roll, external_bonuses, ability_scores, classes);
@@ -509,9 +598,27 @@ impl RollState {
                 .width(Length::FillPortion(1)),
         );
 
-        let mut column = Column::new().push(row);
+        let mut column = Column::new().push(
+            Row::new()
+                .spacing(8)
+                .push(
+                    Button::new(breakdown_button, row)
+                        .on_press(on_toggle_breakdown)
+                        .padding(0)
+                        .width(Length::FillPortion(1)),
+                )
+                .push(
+                    Button::new(roll_button, Text::new("Roll").size(16))
+                        .on_press(on_roll)
+                        .padding(4),
+                ),
+        );
         // column = column.push(Text::new(tags_text).size(12));
 
+        if *show_breakdown {
+            column = column.push(Text::new(rollable.breakdown()).size(12));
+        }
+
         column
     }
 }
@@ -1,49 +1,71 @@
+use crate::core::provenance::{Amount, Contribution};
 use crate::core::roll::Advantage;
-use crate::core::roll::{Dice, Roll};
+use crate::core::roll::Dice;
 use crate::util::format_modifier;
 use iced::{Element, HorizontalAlignment, Length, Text, VerticalAlignment};
+use rand::Rng;
 use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
 
 pub struct Rollable {
     dice: Vec<Dice>,
-    advantage_counter: isize,
+    advantages: Vec<Advantage>,
     reroll: HashSet<isize>,
     bonus: isize,
+    contributions: Vec<Contribution>,
 }
 
 impl Rollable {
     pub fn from(
         dice: Vec<Dice>,
         reroll: HashSet<isize>,
-        advantage_counter: isize,
+        advantages: Vec<Advantage>,
         bonus: isize,
     ) -> Rollable {
         Rollable {
             dice,
-            advantage_counter,
+            advantages,
             reroll,
             bonus,
+            contributions: vec![],
         }
     }
 
     pub fn merge(&mut self, other: Rollable) {
         self.dice.extend(other.dice);
         self.reroll.extend(other.reroll);
-        self.advantage_counter = other.advantage_counter + self.advantage_counter;
+        self.advantages.extend(other.advantages);
         self.bonus = self.bonus + other.bonus;
+        self.contributions.extend(other.contributions);
     }
 
     pub fn add_bonus(&mut self, bonus: isize) {
         self.bonus = self.bonus + bonus;
     }
 
+    /// Records where part of this roll came from; modifiers are also added to the bonus.
+    pub fn contribute(&mut self, contribution: Contribution) {
+        if let Amount::Modifier(modifier) = contribution.amount() {
+            self.add_bonus(*modifier);
+        }
+        self.contributions.push(contribution);
+    }
+
+    pub fn breakdown(&self) -> String {
+        self.contributions
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
     pub fn advantage(&self) -> Option<Advantage> {
-        if self.advantage_counter == 0 {
-            None
-        } else if self.advantage_counter > 0 {
-            Some(Advantage::Advantage)
-        } else {
-            Some(Advantage::Disadvantage)
+        let advantage = self.advantages.contains(&Advantage::Advantage);
+        let disadvantage = self.advantages.contains(&Advantage::Disadvantage);
+        match (advantage, disadvantage) {
+            (true, false) => Some(Advantage::Advantage),
+            (false, true) => Some(Advantage::Disadvantage),
+            _ => None,
         }
     }
 
@@ -55,6 +77,41 @@ impl Rollable {
         self.bonus
     }
 
+    pub fn roll<R: Rng>(&self, rng: &mut R) -> RollOutcome {
+        let advantage = self.advantage();
+        let mut rolls = vec![];
+        for dice in self.dice.iter() {
+            for _ in 0..dice.count {
+                let first = self.roll_die(dice.sides, rng);
+                let kept = match (&advantage, dice.sides) {
+                    (Some(Advantage::Advantage), 20) => first.max(self.roll_die(dice.sides, rng)),
+                    (Some(Advantage::Disadvantage), 20) => {
+                        first.min(self.roll_die(dice.sides, rng))
+                    }
+                    _ => first,
+                };
+                rolls.push(kept);
+            }
+        }
+
+        RollOutcome {
+            rolls,
+            bonus: self.bonus,
+        }
+    }
+
+    fn roll_die<R: Rng>(&self, sides: isize, rng: &mut R) -> isize {
+        if sides < 1 {
+            return 0;
+        }
+        let value = rng.gen_range(1, sides + 1);
+        if self.reroll.contains(&value) {
+            rng.gen_range(1, sides + 1)
+        } else {
+            value
+        }
+    }
+
     pub fn view<'a, T>(&self) -> Element<'a, T> {
         let dice: Option<String> = Some(
             self.dice()
@@ -92,3 +149,60 @@ impl Rollable {
             .into()
     }
 }
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RollOutcome {
+    rolls: Vec<isize>,
+    bonus: isize,
+}
+
+impl RollOutcome {
+    pub fn total(&self) -> isize {
+        self.rolls.iter().sum::<isize>() + self.bonus
+    }
+}
+
+impl Display for RollOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let rolls = self
+            .rolls
+            .iter()
+            .map(|r| r.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        write!(
+            f,
+            "{} ([{}]{})",
+            self.total(),
+            rolls,
+            format_modifier(self.bonus)
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::mock::StepRng;
+
+    #[test]
+    fn roll_adds_bonus_to_every_die() {
+        let rollable = Rollable::from(vec![Dice::new(2, 6)], HashSet::new(), vec![], 3);
+        let outcome = rollable.roll(&mut StepRng::new(0, 0));
+
+        assert_eq!(outcome.rolls.len(), 2);
+        assert!(outcome.rolls.iter().all(|r| *r >= 1 && *r <= 6));
+        assert_eq!(outcome.total(), outcome.rolls.iter().sum::<isize>() + 3);
+    }
+
+    #[test]
+    fn advantage_and_disadvantage_cancel_out() {
+        let advantages = vec![
+            Advantage::Advantage,
+            Advantage::Advantage,
+            Advantage::Disadvantage,
+        ];
+        let rollable = Rollable::from(vec![Dice::new(1, 20)], HashSet::new(), advantages, 0);
+        assert_eq!(rollable.advantage(), None);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::core::feature_path::FeaturePath;
+use crate::core::formula::{Formula, FormulaContext};
 use iced::{button, Button, Column, Element, Length, Row, Text};
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
@@ -6,12 +7,84 @@ use std::fmt::Debug;
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Slot {
     current: isize,
-    max: Option<isize>,
+    max: Option<Formula>,
+    #[serde(default)]
+    recovery: Option<Recovery>,
+}
+
+/// The kind of rest that refills a slot. Slots without one recover on a long rest.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub enum Recovery {
+    ShortRest,
+    LongRest,
+}
+
+/// An amount of the slot on the feature at `path`, from the top level feature down.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SlotAmount {
+    path: Vec<String>,
+    amount: isize,
+    /// Whether `amount` is charged for each level of the spell it is used with, cantrips
+    /// counting as level 1, once that spell is cast.
+    #[serde(default)]
+    per_spell_level: bool,
 }
 
 impl Slot {
     pub fn new(current: isize, max: Option<isize>) -> Slot {
-        Slot { current, max }
+        Slot {
+            current,
+            max: max.map(Formula::from),
+            recovery: None,
+        }
+    }
+
+    pub fn recovers_on(self, recovery: Recovery) -> Slot {
+        Slot {
+            recovery: Some(recovery),
+            ..self
+        }
+    }
+}
+
+impl SlotAmount {
+    pub fn path(&self) -> &Vec<String> {
+        &self.path
+    }
+
+    pub fn amount(&self) -> isize {
+        self.amount
+    }
+
+    pub fn is_per_spell_level(&self) -> bool {
+        self.per_spell_level
+    }
+
+    /// The amount charged when used with a spell of `level`.
+    pub fn for_spell_level(&self, level: isize) -> SlotAmount {
+        let amount = if self.per_spell_level {
+            self.amount * level.max(1)
+        } else {
+            self.amount
+        };
+        SlotAmount {
+            path: self.path.clone(),
+            amount,
+            per_spell_level: false,
+        }
+    }
+}
+
+impl std::fmt::Display for SlotAmount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.path.last() {
+            Some(name) => write!(f, "{} {}", self.amount, name)?,
+            None => write!(f, "{}", self.amount)?,
+        }
+        if self.per_spell_level {
+            write!(f, " per spell level")?;
+        }
+        Ok(())
     }
 }
 
@@ -19,6 +92,12 @@ impl Slot {
 pub struct SlotState {
     control: SlotControl,
     slot: Slot,
+    max: Option<isize>,
+    /// Why the maximum's formula could not be evaluated.
+    error: Option<String>,
+    /// Whether to fill the slot once its maximum is known, as for a slot from a template that
+    /// the character has never saved.
+    fill: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -29,29 +108,108 @@ pub enum SlotCommand {
 
 impl SlotState {
     pub fn persistable(&self) -> Slot {
-        let SlotState { control, slot } = self;
+        let SlotState { slot, .. } = self;
         slot.clone()
     }
 
     pub fn from(slot: Slot) -> SlotState {
         SlotState {
             control: SlotControl::default(),
+            max: slot.max.as_ref().and_then(Formula::constant),
             slot,
+            error: None,
+            fill: false,
         }
     }
 
+    /// A slot the character has never saved, which starts out full.
+    pub fn unsaved(slot: Slot) -> SlotState {
+        let mut state = SlotState::from(slot);
+        state.fill = true;
+        state.fill_up();
+        state
+    }
+
+    fn fill_up(&mut self) {
+        if let (true, Some(max)) = (self.fill, self.max) {
+            self.slot.current = max;
+            self.fill = false;
+        }
+    }
+
+    /// Re-evaluates the slot maximum, which may depend on levels or ability scores.
+    pub fn evaluate(&mut self, context: &FormulaContext) {
+        self.error = None;
+        self.max = match self.slot.max.as_ref().map(|max| (max, max.value(context))) {
+            Some((_, Ok(value))) => Some(value),
+            Some((max, Err(e))) => {
+                self.error = Some(max.describe(&e));
+                None
+            }
+            None => None,
+        };
+        self.fill_up();
+    }
+
+    /// Overrides the maximum with one derived elsewhere, such as the spell slot table, lowering
+    /// the current amount if it is now above it.
+    pub fn set_max(&mut self, max: isize) -> bool {
+        self.max = Some(max);
+        self.error = None;
+        self.fill_up();
+        let current = self.slot.current.min(max);
+        let lowered = current != self.slot.current;
+        self.slot.current = current;
+        lowered
+    }
+
+    /// Refills the slot if `rest` is enough for it to recover.
+    pub fn rest(&mut self, rest: &Recovery) -> bool {
+        match (rest, self.slot.recovery.as_ref()) {
+            (Recovery::ShortRest, Some(Recovery::ShortRest)) | (Recovery::LongRest, _) => {
+                self.reset()
+            }
+            _ => false,
+        }
+    }
+
+    pub fn current(&self) -> isize {
+        self.slot.current
+    }
+
+    pub fn max(&self) -> Option<isize> {
+        self.max
+    }
+
+    /// Uses one charge of the slot. Refused, leaving the slot unchanged, when none are left.
     pub fn use_slot(&mut self) -> bool {
-        let SlotState { control, slot } = self;
-        slot.current = slot.current - 1;
-        true
+        let SlotState { slot, .. } = self;
+        if slot.current <= 0 {
+            false
+        } else {
+            slot.current = slot.current - 1;
+            true
+        }
+    }
+
+    /// Adds (or with a negative amount, removes) charges, never going below none or above the
+    /// maximum when it is known.
+    pub fn adjust(&mut self, amount: isize) -> bool {
+        let mut current = (self.slot.current + amount).max(0);
+        if let Some(max) = self.max {
+            current = current.min(max.max(self.slot.current));
+        }
+        let changed = current != self.slot.current;
+        self.slot.current = current;
+        changed
     }
 
     pub fn reset(&mut self) -> bool {
-        let SlotState { control, slot } = self;
-        if (slot.max == Some(slot.current)) {
+        let SlotState { slot, max, .. } = self;
+        if (*max == Some(slot.current)) {
             false
         } else {
-            slot.current = slot.max.unwrap_or(0);
+            slot.current = max.unwrap_or(0);
             true
         }
     }
@@ -69,9 +227,15 @@ impl SlotState {
         T: Debug + Clone + 'a,
         F: Fn(SlotCommand) -> T + 'b,
     {
-        let SlotState { slot, control } = self;
+        let SlotState {
+            slot,
+            control,
+            max,
+            error,
+            ..
+        } = self;
 
-        let Slot { current, max } = slot;
+        let current = slot.current;
         let mut row = Row::new().spacing(20);
 
         row = match max {
@@ -97,7 +261,11 @@ impl SlotState {
             .padding(8);
         row = row.push(button);
 
-        Column::new().push(row)
+        let mut column = Column::new().push(row);
+        if let Some(error) = error {
+            column = column.push(Text::new(format!("Maximum: {}", error)).size(12));
+        }
+        column
     }
 }
 
@@ -111,3 +279,19 @@ pub struct SlotControl {
 pub trait FromSlotCommand<T> {
     fn from(command: SlotCommand) -> T;
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn adjustments_stay_within_the_slot() {
+        let mut slot = SlotState::from(Slot::new(2, Some(3)));
+        assert!(slot.adjust(5));
+        assert_eq!(slot.current(), 3);
+        assert!(!slot.adjust(1));
+        assert!(slot.adjust(-5));
+        assert_eq!(slot.current(), 0);
+        assert!(!slot.adjust(-1));
+    }
+}
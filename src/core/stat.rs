@@ -0,0 +1,462 @@
+use crate::core::effect::{stack, ActiveEffect, Effect, EffectBonus};
+use crate::core::formula::{Formula, FormulaContext};
+use crate::core::provenance::{Amount, Contribution, Source};
+use iced::{button, Button, Column, HorizontalAlignment, Length, Row, Text, VerticalAlignment};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Debug, Display, Formatter};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(tag = "type")]
+pub enum ValueBonus<T = isize> {
+    Modifier {
+        modifier: T,
+    },
+    /// Raises the value to `value` if it is lower, the way the Amulet of Health does.
+    Become {
+        value: T,
+    },
+    /// Replaces the value with `value`, even if that lowers it.
+    Set {
+        value: T,
+    },
+    /// Raises the highest value modifiers can bring this to, the way Primal Champion raises
+    /// the Strength maximum to 24.
+    Maximum {
+        value: T,
+    },
+}
+
+impl ValueBonus<Formula> {
+    /// The bonus with its formula evaluated, or why it can't be.
+    pub fn evaluate(&self, context: &FormulaContext) -> Result<ValueBonus, String> {
+        let value = |formula: &Formula| formula.value(context).map_err(|e| formula.describe(&e));
+        Ok(match self {
+            ValueBonus::Modifier { modifier } => ValueBonus::Modifier {
+                modifier: value(modifier)?,
+            },
+            ValueBonus::Become { value: formula } => ValueBonus::Become {
+                value: value(formula)?,
+            },
+            ValueBonus::Set { value: formula } => ValueBonus::Set {
+                value: value(formula)?,
+            },
+            ValueBonus::Maximum { value: formula } => ValueBonus::Maximum {
+                value: value(formula)?,
+            },
+        })
+    }
+}
+
+impl ValueBonus {
+    fn potency(&self) -> isize {
+        match self {
+            ValueBonus::Modifier { modifier } => *modifier,
+            ValueBonus::Become { value } => *value,
+            ValueBonus::Set { value } => *value,
+            ValueBonus::Maximum { value } => *value,
+        }
+    }
+}
+
+/// The modifier pipeline shared by ability scores and every other derived value.
+///
+/// Bonuses are applied in a fixed order so the result does not depend on feature order:
+/// the highest `Set` replaces the base value, `Modifier`s are added on top, and finally the
+/// value is raised to the highest `Become`.
+pub fn modify(
+    base: Vec<Contribution>,
+    bonuses: &[EffectBonus<ValueBonus>],
+) -> (isize, Vec<Contribution>) {
+    modify_up_to(base, bonuses, None)
+}
+
+/// Like `modify`, except that modifiers cannot take the value past `maximum`, or past the
+/// highest `Maximum` bonus if that is higher. The positive modifiers together are reduced to
+/// fit under the maximum before any negative ones apply, so the result doesn't depend on their
+/// order, and a value already past the maximum is never lowered by it.
+pub fn modify_up_to(
+    base: Vec<Contribution>,
+    bonuses: &[EffectBonus<ValueBonus>],
+    maximum: Option<isize>,
+) -> (isize, Vec<Contribution>) {
+    let mut maximum = maximum;
+    let mut set_value: Option<(isize, Source)> = None;
+    let mut become_value: Option<(isize, Source)> = None;
+    let mut modifiers = vec![];
+
+    for value_modifier in stack(bonuses, ValueBonus::potency) {
+        let source = value_modifier.provenance();
+        match value_modifier.bonus {
+            ValueBonus::Set { value } => {
+                if set_value.as_ref().map(|(v, _)| value > *v).unwrap_or(true) {
+                    set_value = Some((value, source));
+                }
+            }
+            ValueBonus::Become { value } => {
+                if become_value
+                    .as_ref()
+                    .map(|(v, _)| value > *v)
+                    .unwrap_or(true)
+                {
+                    become_value = Some((value, source));
+                }
+            }
+            ValueBonus::Modifier { modifier } => modifiers.push((modifier, source)),
+            ValueBonus::Maximum { value } => {
+                if maximum.map(|m| value > m).unwrap_or(true) {
+                    maximum = Some(value);
+                }
+            }
+        }
+    }
+
+    let mut value = base.iter().map(|c| c.amount().value()).sum::<isize>();
+    let mut contributions = base;
+    if let Some((set, source)) = set_value {
+        value = set;
+        contributions.push(Contribution::new(Amount::Set(set), source));
+    }
+    let mut room = maximum.map(|maximum| (maximum - value).max(0));
+    let mut lowered_by = 0;
+    for (modifier, source) in modifiers {
+        let modifier = match room.as_mut() {
+            Some(room) if modifier > 0 => {
+                let fits = modifier.min(*room);
+                *room -= fits;
+                fits
+            }
+            _ => modifier,
+        };
+        if modifier > 0 {
+            value += modifier;
+        } else {
+            lowered_by += modifier;
+        }
+        contributions.push(Contribution::new(Amount::Modifier(modifier), source));
+    }
+    value += lowered_by;
+    match become_value {
+        Some((raised_to, source)) if raised_to > value => {
+            value = raised_to;
+            contributions.push(Contribution::new(Amount::Become(raised_to), source));
+        }
+        _ => {}
+    }
+
+    (value, contributions)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub enum Movement {
+    Walk,
+    Fly,
+    Swim,
+    Climb,
+    Burrow,
+}
+
+/// A derived scalar value that effects can target.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub enum Stat {
+    MaxHitPoints,
+    Speed(Movement),
+    Initiative,
+    SpellSaveDc,
+    SpellAttack,
+    ArmorClass,
+    CarryingCapacity,
+}
+
+impl Display for Movement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Movement::Walk => write!(f, "Walking"),
+            Movement::Fly => write!(f, "Flying"),
+            Movement::Swim => write!(f, "Swimming"),
+            Movement::Climb => write!(f, "Climbing"),
+            Movement::Burrow => write!(f, "Burrowing"),
+        }
+    }
+}
+
+impl Display for Stat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Stat::MaxHitPoints => write!(f, "Max Hit Points"),
+            Stat::Speed(movement) => write!(f, "{} Speed", movement),
+            Stat::Initiative => write!(f, "Initiative"),
+            Stat::SpellSaveDc => write!(f, "Spell Save DC"),
+            Stat::SpellAttack => write!(f, "Spell Attack"),
+            Stat::ArmorClass => write!(f, "Armor Class"),
+            Stat::CarryingCapacity => write!(f, "Carrying Capacity"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum StatMessage {
+    ToggleBreakdown(Stat),
+}
+
+#[derive(Debug, Clone)]
+pub struct ModifiedValue {
+    value: isize,
+    contributions: Vec<Contribution>,
+}
+
+impl ModifiedValue {
+    pub fn value(&self) -> isize {
+        self.value
+    }
+
+    /// Whether a bonus to it could not be evaluated.
+    pub fn has_error(&self) -> bool {
+        self.contributions
+            .iter()
+            .any(|c| matches!(c.amount(), Amount::Error(_)))
+    }
+
+    pub fn breakdown(&self) -> String {
+        self.contributions
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StatsState {
+    bonuses: Vec<(Stat, EffectBonus<ValueBonus>)>,
+    /// Bonuses whose formula could not be evaluated, shown in place of their amount.
+    errors: Vec<(Stat, Contribution)>,
+    shown_breakdowns: Vec<Stat>,
+    breakdown_buttons: Vec<button::State>,
+}
+
+impl StatsState {
+    pub fn apply_all(&mut self, effects: &Vec<ActiveEffect>, context: &FormulaContext) {
+        self.bonuses = vec![];
+        self.errors = vec![];
+        for active_effect in effects {
+            if let Effect::Stat { stat, bonus, name } = active_effect.effect() {
+                let source = active_effect.source().clone();
+                match bonus.evaluate(context) {
+                    Ok(bonus) => self
+                        .bonuses
+                        .push((stat.clone(), EffectBonus::new(name.clone(), source, bonus))),
+                    Err(error) => self.errors.push((
+                        stat.clone(),
+                        Contribution::new(
+                            Amount::Error(error),
+                            Source::feature(source, name.clone()),
+                        ),
+                    )),
+                }
+            }
+        }
+    }
+
+    /// Computes `stat` from the contributions making up its base value.
+    pub fn value(&self, stat: &Stat, base: Vec<Contribution>) -> ModifiedValue {
+        let bonuses = self
+            .bonuses
+            .iter()
+            .filter(|(s, _)| s == stat)
+            .map(|(_, bonus)| bonus.clone())
+            .collect::<Vec<EffectBonus<ValueBonus>>>();
+        let (value, mut contributions) = modify(base, &bonuses);
+        contributions.extend(
+            self.errors
+                .iter()
+                .filter(|(s, _)| s == stat)
+                .map(|(_, error)| error.clone()),
+        );
+        ModifiedValue {
+            value,
+            contributions,
+        }
+    }
+
+    pub fn has_bonus(&self, stat: &Stat) -> bool {
+        self.bonuses.iter().any(|(s, _)| s == stat) || self.errors.iter().any(|(s, _)| s == stat)
+    }
+
+    pub fn update(&mut self, message: StatMessage) {
+        match message {
+            StatMessage::ToggleBreakdown(stat) => {
+                if self.shown_breakdowns.contains(&stat) {
+                    self.shown_breakdowns.retain(|s| s != &stat);
+                } else {
+                    self.shown_breakdowns.push(stat);
+                }
+            }
+        }
+    }
+
+    pub fn view<'a, T, F>(
+        &'a mut self,
+        values: Vec<(Stat, ModifiedValue)>,
+        f: &'a F,
+    ) -> Column<'a, T>
+    where
+        T: Debug + Clone + 'a,
+        F: Fn(StatMessage) -> T,
+    {
+        let StatsState {
+            shown_breakdowns,
+            breakdown_buttons,
+            ..
+        } = self;
+        breakdown_buttons.resize_with(values.len(), button::State::default);
+
+        let mut column = Column::new().push(Row::new().push(Text::new("Stats").size(24)));
+        for ((stat, value), breakdown_button) in
+            values.into_iter().zip(breakdown_buttons.iter_mut())
+        {
+            let row = Row::new()
+                .width(Length::Fill)
+                .spacing(4)
+                .push(
+                    Text::new(stat.to_string())
+                        .size(16)
+                        .horizontal_alignment(HorizontalAlignment::Left)
+                        .vertical_alignment(VerticalAlignment::Bottom)
+                        .width(Length::FillPortion(1)),
+                )
+                .push(
+                    Text::new(value.value().to_string())
+                        .size(16)
+                        .horizontal_alignment(HorizontalAlignment::Left)
+                        .vertical_alignment(VerticalAlignment::Bottom)
+                        .width(Length::FillPortion(1)),
+                );
+
+            column = column.push(
+                Button::new(breakdown_button, row)
+                    .on_press(f(StatMessage::ToggleBreakdown(stat.clone())))
+                    .padding(0),
+            );
+            if shown_breakdowns.contains(&stat) || value.has_error() {
+                column = column.push(Text::new(value.breakdown()).size(12));
+            }
+        }
+
+        column
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::character::class::{Class, Classes};
+    use crate::core::ability_score::{Ability, AbilityScore, AbilityScores};
+    use crate::core::feature_path::FeaturePath;
+
+    #[test]
+    fn stats_share_the_modifier_pipeline() {
+        let alert = Effect::Stat {
+            stat: Stat::Initiative,
+            bonus: ValueBonus::Modifier {
+                modifier: Formula::Constant(5),
+            },
+            name: None,
+        };
+        let mobile = Effect::Stat {
+            stat: Stat::Speed(Movement::Walk),
+            bonus: ValueBonus::Modifier {
+                modifier: Formula::Expression("Monk level * 5".to_string()),
+            },
+            name: None,
+        };
+        let source = FeaturePath::of(vec!["Feats".to_string()]);
+        let ability_scores = AbilityScores::default()
+            .with(Ability::Dexterity, AbilityScore::of(14))
+            .to_state()
+            .modified();
+        let classes = Classes::from(vec![Class::new("Monk".to_string(), 2)]);
+        let mut stats = StatsState::default();
+        stats.apply_all(
+            &vec![
+                ActiveEffect::new(alert, source.clone()),
+                ActiveEffect::new(mobile, source),
+            ],
+            &FormulaContext::new(&ability_scores, &classes),
+        );
+
+        let base = |value: isize| vec![Contribution::new(Amount::Value(value), Source::Base)];
+        assert_eq!(stats.value(&Stat::Initiative, base(2)).value(), 7);
+        assert_eq!(
+            stats.value(&Stat::Speed(Movement::Walk), base(30)).value(),
+            40
+        );
+        assert_eq!(stats.value(&Stat::Speed(Movement::Fly), base(0)).value(), 0);
+        assert_eq!(stats.value(&Stat::ArmorClass, base(12)).value(), 12);
+
+        let typo = Effect::Stat {
+            stat: Stat::ArmorClass,
+            bonus: ValueBonus::Modifier {
+                modifier: Formula::Expression("DEX mdo".to_string()),
+            },
+            name: Some("Shield of Typos".to_string()),
+        };
+        stats.apply_all(
+            &vec![ActiveEffect::new(typo, FeaturePath::empty())],
+            &FormulaContext::new(&ability_scores, &classes),
+        );
+        let armor_class = stats.value(&Stat::ArmorClass, base(12));
+        assert_eq!(armor_class.value(), 12);
+        assert!(armor_class.has_error());
+        assert_eq!(
+            armor_class.breakdown(),
+            "12 base, Shield of Typos: cannot evaluate 'DEX mdo' (unknown value 'DEX mdo')"
+        );
+    }
+
+    #[test]
+    fn capped_modifiers_do_not_depend_on_order() {
+        let modifiers = [2, -1, 2];
+        let orders = [
+            [0, 1, 2],
+            [0, 2, 1],
+            [1, 0, 2],
+            [1, 2, 0],
+            [2, 0, 1],
+            [2, 1, 0],
+        ];
+        for order in orders.iter() {
+            let bonuses: Vec<EffectBonus<ValueBonus>> = order
+                .iter()
+                .map(|&i| {
+                    EffectBonus::new(
+                        None,
+                        FeaturePath::of(vec![i.to_string()]),
+                        ValueBonus::Modifier {
+                            modifier: modifiers[i],
+                        },
+                    )
+                })
+                .collect();
+            let base = vec![Contribution::new(Amount::Value(18), Source::Base)];
+            let (value, _) = modify_up_to(base, &bonuses, Some(20));
+            assert_eq!(value, 19, "Modifiers applied in the order {:?}", order);
+        }
+    }
+
+    #[test]
+    fn same_named_penalties_stack_apart_from_bonuses() {
+        let named = |modifier: isize| {
+            EffectBonus::new(
+                Some("Curse".to_string()),
+                FeaturePath::of(vec![modifier.to_string()]),
+                ValueBonus::Modifier { modifier },
+            )
+        };
+        let base = || vec![Contribution::new(Amount::Value(10), Source::Base)];
+        assert_eq!(modify(base(), &[named(-3), named(2)]).0, 9);
+        assert_eq!(
+            modify(base(), &[named(2), named(1), named(-1), named(-3)]).0,
+            9
+        );
+    }
+}
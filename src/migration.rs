@@ -0,0 +1,146 @@
+use crate::store::{ParseError, Store, StoreError};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::fmt::{Display, Formatter};
+
+/// The schema this version reads and writes. Documents without a `schema_version` are version 0.
+pub const SCHEMA_VERSION: u64 = 1;
+
+/// Each step upgrades a document from the version at its index to the next one.
+const MIGRATIONS: [fn(&mut Value); SCHEMA_VERSION as usize] = [rename_show_reset_children];
+
+/// A document written by a newer version, which this one can't read without losing data.
+#[derive(Debug, Clone, Eq, PartialOrd, PartialEq)]
+pub struct SchemaError {
+    pub key: String,
+    pub version: u64,
+}
+
+impl Display for SchemaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} has schema version {}, newer than the {} this version reads",
+            self.key, self.version, SCHEMA_VERSION
+        )
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialOrd, PartialEq)]
+pub enum ReadError {
+    Parse(ParseError),
+    Schema(SchemaError),
+}
+
+/// A document read at the current schema version.
+#[derive(Debug, Clone)]
+pub struct Read<T> {
+    pub value: T,
+    /// The version it was upgraded from, if it was older.
+    pub upgraded_from: Option<u64>,
+    /// The upgraded document, as it should be written back.
+    pub document: Value,
+}
+
+/// Parses a stored document, upgrading it in memory first if it is older than `SCHEMA_VERSION`.
+pub fn read<T: DeserializeOwned>(key: &str, content: &str) -> Result<Read<T>, ReadError> {
+    let parse_error = |e: serde_json::Error| ReadError::Parse(ParseError::of(key, &e));
+    let mut document: Value = serde_json::from_str(content).map_err(parse_error)?;
+    let version = schema_version(&document);
+    if version > SCHEMA_VERSION {
+        return Err(ReadError::Schema(SchemaError {
+            key: key.to_string(),
+            version,
+        }));
+    }
+    if version == SCHEMA_VERSION {
+        // Parsing the text again keeps the line and column of any error.
+        let value = serde_json::from_str(content).map_err(parse_error)?;
+        return Ok(Read {
+            value,
+            upgraded_from: None,
+            document,
+        });
+    }
+
+    for migration in MIGRATIONS.iter().skip(version as usize) {
+        migration(&mut document);
+    }
+    if let Value::Object(fields) = &mut document {
+        fields.insert("schema_version".to_string(), Value::from(SCHEMA_VERSION));
+    }
+    let value = serde_json::from_value(document.clone()).map_err(parse_error)?;
+    Ok(Read {
+        value,
+        upgraded_from: Some(version),
+        document,
+    })
+}
+
+pub fn schema_version(document: &Value) -> u64 {
+    document
+        .get("schema_version")
+        .and_then(|version| version.as_u64())
+        .unwrap_or(0)
+}
+
+/// Writes an upgraded document over `key`, once the original is kept as `migrated/<key>.v<version>`.
+pub async fn write_back(
+    store: &Store,
+    key: String,
+    original: String,
+    version: u64,
+    upgraded: String,
+) -> Result<(), StoreError> {
+    let kept = format!("migrated/{}.v{}", key.trim_start_matches('/'), version);
+    store.save_without_backup(kept, original).await?;
+    store.save_without_backup(key, upgraded).await
+}
+
+/// Version 0 to 1: `show_reset_chidren` is spelled `show_reset_children`.
+fn rename_show_reset_children(document: &mut Value) {
+    match document {
+        Value::Object(fields) => {
+            if let Some(show) = fields.remove("show_reset_chidren") {
+                fields.entry("show_reset_children").or_insert(show);
+            }
+            fields.values_mut().for_each(rename_show_reset_children);
+        }
+        Value::Array(values) => values.iter_mut().for_each(rename_show_reset_children),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn older_documents_are_upgraded_and_newer_ones_refused() {
+        let upgraded: Read<Value> = read(
+            "old.json",
+            r#"{ "features": [{ "name": "Sorcery Points", "show_reset_chidren": true }] }"#,
+        )
+        .unwrap();
+        assert_eq!(upgraded.upgraded_from, Some(0));
+        assert_eq!(upgraded.value["schema_version"], SCHEMA_VERSION);
+        assert_eq!(upgraded.value["features"][0]["show_reset_children"], true);
+        assert_eq!(
+            upgraded.value["features"][0].get("show_reset_chidren"),
+            None
+        );
+
+        let current = format!("{{ \"schema_version\": {} }}", SCHEMA_VERSION);
+        let current: Read<Value> = read("current.json", &current).unwrap();
+        assert_eq!(current.upgraded_from, None);
+
+        let newer = format!("{{ \"schema_version\": {} }}", SCHEMA_VERSION + 1);
+        assert_eq!(
+            read::<Value>("newer.json", &newer).err(),
+            Some(ReadError::Schema(SchemaError {
+                key: "newer.json".to_string(),
+                version: SCHEMA_VERSION + 1,
+            }))
+        );
+    }
+}
@@ -1,25 +1,54 @@
+use crate::resources::spell::Spells;
+use crate::resources::table::Tables;
 use crate::resources::template::Templates;
+use std::fmt::{Display, Formatter};
 
 mod persistence;
+pub mod progression;
+pub mod spell;
+pub mod table;
 pub mod template;
 
 #[derive(Debug, Clone, Default)]
 pub struct Resources {
     templates: Templates,
+    spells: Spells,
+    tables: Tables,
 }
 
 impl Resources {
     pub fn templates(&self) -> &Templates {
         &self.templates
     }
+
+    pub fn spells(&self) -> &Spells {
+        &self.spells
+    }
+
+    pub fn tables(&self) -> &Tables {
+        &self.tables
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialOrd, PartialEq)]
 pub enum ResourceError {
     Store(crate::store::StoreError),
+    Parse(crate::store::ParseError),
+    Schema(crate::migration::SchemaError),
     Serialize(String),
 }
 
+impl Display for ResourceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResourceError::Store(e) => write!(f, "{:?}", e),
+            ResourceError::Parse(e) => write!(f, "{}", e),
+            ResourceError::Schema(e) => write!(f, "{}", e),
+            ResourceError::Serialize(e) => write!(f, "{}", e),
+        }
+    }
+}
+
 pub async fn load(storage_root: String) -> Result<Resources, ResourceError> {
     let persistence = persistence::ResourcePersistence::load(
         persistence::ResourcePersistenceConfig::of(storage_root),
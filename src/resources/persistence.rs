@@ -1,9 +1,14 @@
+use crate::migration::{self, Read, ReadError};
+use crate::resources::spell::Spells;
+use crate::resources::table::Tables;
 use crate::resources::template::Templates;
 use crate::resources::{ResourceError, Resources};
-use crate::store::Store;
+use crate::store::{ParseError, Store, StoreError};
 
 pub struct ResourcePersistence {
     templates: Templates,
+    spells: Spells,
+    tables: Tables,
 }
 pub struct ResourcePersistenceConfig {
     storage_root: String,
@@ -19,8 +24,12 @@ impl ResourcePersistenceConfig {
 }
 
 impl ResourcePersistence {
-    pub fn from(templates: Templates) -> ResourcePersistence {
-        ResourcePersistence { templates }
+    pub fn from(templates: Templates, spells: Spells, tables: Tables) -> ResourcePersistence {
+        ResourcePersistence {
+            templates,
+            spells,
+            tables,
+        }
     }
 
     pub async fn load(
@@ -29,21 +38,68 @@ impl ResourcePersistence {
         let store = config.store()?;
 
         let template_key = "template.json".to_string();
-        let templates: Templates = store
-            .load(template_key)
+        let content = store
+            .load(template_key.clone())
             .await
-            .map_err(ResourceError::Store)
-            .and_then(|content| {
-                serde_json::from_str(content.as_str())
-                    .map_err(|e| ResourceError::Serialize(e.to_string()))
+            .map_err(ResourceError::Store)?;
+        let read: Read<Templates> =
+            migration::read(&template_key, &content).map_err(|e| match e {
+                ReadError::Parse(e) => ResourceError::Parse(e),
+                ReadError::Schema(e) => ResourceError::Schema(e),
             })?;
+        if let Some(version) = read.upgraded_from {
+            let upgraded = to_json(&read.document)?;
+            migration::write_back(&store, template_key, content, version, upgraded)
+                .await
+                .map_err(ResourceError::Store)?;
+        }
+        let templates = read.value;
+
+        // The spell catalog is optional; characters without spells don't need one.
+        let spells_key = "spells.json".to_string();
+        let spells: Spells = match store.load(spells_key.clone()).await {
+            Ok(content) => parse(&spells_key, &content)?,
+            Err(StoreError::NotFound(_)) => Spells::default(),
+            Err(e) => return Err(ResourceError::Store(e)),
+        };
 
-        Ok(ResourcePersistence { templates })
+        // Random tables are optional too.
+        let tables_key = "tables.json".to_string();
+        let tables: Tables = match store.load(tables_key.clone()).await {
+            Ok(content) => parse(&tables_key, &content)?,
+            Err(StoreError::NotFound(_)) => Tables::default(),
+            Err(e) => return Err(ResourceError::Store(e)),
+        };
+
+        Ok(ResourcePersistence {
+            templates,
+            spells,
+            tables,
+        })
     }
 
     pub fn resources(self) -> Resources {
         Resources {
             templates: self.templates,
+            spells: self.spells,
+            tables: self.tables,
         }
     }
 }
+
+fn parse<T: serde::de::DeserializeOwned>(key: &str, content: &str) -> Result<T, ResourceError> {
+    serde_json::from_str(content).map_err(|e| ResourceError::Parse(ParseError::of(key, &e)))
+}
+
+/// Templates are edited by hand, so they are written with the same four space indent.
+fn to_json(document: &serde_json::Value) -> Result<String, ResourceError> {
+    use serde::Serialize;
+
+    let mut json = vec![];
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+    let mut serializer = serde_json::Serializer::with_formatter(&mut json, formatter);
+    document
+        .serialize(&mut serializer)
+        .map_err(|e| ResourceError::Serialize(e.to_string()))?;
+    String::from_utf8(json).map_err(|e| ResourceError::Serialize(e.to_string()))
+}
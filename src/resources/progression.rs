@@ -0,0 +1,176 @@
+use crate::character::class::CasterType;
+use crate::core::ability_score::Ability;
+use crate::resources::template::Grants;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How a class grows as it gains levels, used by the level up wizard.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassProgression {
+    hit_die: isize,
+    #[serde(default)]
+    caster: Option<CasterType>,
+    #[serde(default)]
+    spellcasting_ability: Option<Ability>,
+    /// What taking the class grants, on top of the features of each level.
+    #[serde(default)]
+    grants: Grants,
+    /// The subset of `grants` given when the class is not the character's first.
+    #[serde(default)]
+    multiclass: Grants,
+    /// Skills chosen when the class is the character's first.
+    #[serde(default)]
+    skills: SkillChoice,
+    /// Levels granting an ability score improvement or a feat.
+    #[serde(default)]
+    asi_levels: Vec<isize>,
+    /// The level at which a subclass is chosen, if the class has subclasses.
+    subclass_level: Option<isize>,
+    #[serde(default)]
+    subclasses: HashMap<String, SubclassProgression>,
+    #[serde(default)]
+    levels: Vec<ProgressionLevel>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SkillChoice {
+    choose: usize,
+    from: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubclassProgression {
+    #[serde(default)]
+    grants: Grants,
+    #[serde(default)]
+    levels: Vec<ProgressionLevel>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProgressionLevel {
+    level: isize,
+    /// Names of the feature templates gained at this level.
+    #[serde(default)]
+    features: Vec<String>,
+    /// How many new spells are learned at this level.
+    #[serde(default)]
+    spells: usize,
+}
+
+impl ClassProgression {
+    pub fn hit_die(&self) -> isize {
+        self.hit_die
+    }
+
+    /// Hit points gained by taking the average instead of rolling.
+    pub fn average_hit_points(&self) -> isize {
+        self.hit_die / 2 + 1
+    }
+
+    pub fn caster(&self) -> Option<CasterType> {
+        self.caster.clone()
+    }
+
+    pub fn spellcasting_ability(&self) -> Option<Ability> {
+        self.spellcasting_ability.clone()
+    }
+
+    /// How many skills to choose, and the skills to choose from.
+    pub fn skill_choice(&self) -> (usize, &Vec<String>) {
+        (self.skills.choose, &self.skills.from)
+    }
+
+    pub fn improves_abilities_at(&self, level: isize) -> bool {
+        self.asi_levels.contains(&level)
+    }
+
+    pub fn chooses_subclass_at(&self, level: isize) -> bool {
+        self.subclass_level == Some(level)
+    }
+
+    pub fn subclasses(&self) -> Vec<String> {
+        let mut subclasses = self.subclasses.keys().cloned().collect::<Vec<String>>();
+        subclasses.sort();
+        subclasses
+    }
+
+    /// Features gained at `level`, including those of the subclass.
+    pub fn features_at(&self, level: isize, subclass: Option<&str>) -> Vec<String> {
+        let subclass_levels = subclass
+            .and_then(|subclass| self.subclasses.get(subclass))
+            .into_iter()
+            .flat_map(|subclass| subclass.levels.iter());
+        self.levels
+            .iter()
+            .chain(subclass_levels)
+            .filter(|l| l.level == level)
+            .flat_map(|l| l.features.clone())
+            .collect()
+    }
+
+    /// What the class grants a character with `level` levels in it.
+    pub fn grants(&self, level: isize) -> Grants {
+        self.grants
+            .clone()
+            .with_features(features_up_to(&self.levels, level))
+    }
+
+    /// What the class grants when taken after another class.
+    pub fn multiclass_grants(&self, level: isize) -> Grants {
+        self.multiclass
+            .clone()
+            .with_features(features_up_to(&self.levels, level))
+    }
+
+    pub fn subclass_grants(&self, subclass: &str, level: isize) -> Option<Grants> {
+        self.subclasses.get(subclass).map(|subclass| {
+            subclass
+                .grants
+                .clone()
+                .with_features(features_up_to(&subclass.levels, level))
+        })
+    }
+
+    pub fn spells_at(&self, level: isize) -> usize {
+        self.levels
+            .iter()
+            .filter(|l| l.level == level)
+            .map(|l| l.spells)
+            .sum()
+    }
+}
+
+fn features_up_to(levels: &Vec<ProgressionLevel>, level: isize) -> Vec<String> {
+    levels
+        .iter()
+        .filter(|l| l.level <= level)
+        .flat_map(|l| l.features.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn subclass_features_are_gained_with_class_features() {
+        let progression: ClassProgression = serde_json::from_str(
+            r#"{ "hit_die": 6, "asi_levels": [4, 8], "subclass_level": 1,
+                "subclasses": { "Wild Magic": { "levels": [{ "level": 6, "features": ["Bend Luck"] }] } },
+                "levels": [
+                    { "level": 1, "features": ["Spellcasting"], "spells": 2 },
+                    { "level": 6, "spells": 1 }
+                ] }"#,
+        )
+        .unwrap();
+
+        assert_eq!(progression.average_hit_points(), 4);
+        assert!(progression.improves_abilities_at(8));
+        assert_eq!(progression.features_at(6, None), Vec::<String>::new());
+        assert_eq!(
+            progression.features_at(6, Some("Wild Magic")),
+            vec!["Bend Luck"]
+        );
+        assert_eq!(progression.spells_at(1), 2);
+    }
+}
@@ -0,0 +1,282 @@
+use crate::character::spellcasting::Spellcasting;
+use crate::core::ability_score::Ability;
+use crate::core::feature::Feature;
+use crate::core::formula::Formula;
+use crate::core::roll::{Dice, Roll, RollBonus};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub const SPELL_ATTACK_TAGS: [&str; 2] = ["Spellcasting", "Attack"];
+pub const SPELL_DAMAGE_TAGS: [&str; 2] = ["Spellcasting", "Damage"];
+/// Tags the target's saving throw against the spell, such as for Heightened Spell.
+pub const SPELL_SAVE_TAGS: [&str; 2] = ["Spellcasting", "Save"];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Spells {
+    #[serde(default)]
+    spells: HashMap<String, Spell>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Spell {
+    /// 0 for cantrips.
+    level: isize,
+    school: String,
+    casting_time: String,
+    range: String,
+    #[serde(default)]
+    components: Components,
+    duration: String,
+    #[serde(default)]
+    concentration: bool,
+    description: Option<String>,
+    /// Whether casting the spell makes a spell attack roll.
+    #[serde(default)]
+    attack: bool,
+    /// The saving throw targets make against the caster's spell save DC.
+    save: Option<Ability>,
+    #[serde(default)]
+    damage: Vec<Dice>,
+    damage_type: Option<String>,
+    /// Extra damage dice for each slot level above the spell's level.
+    #[serde(default)]
+    upcast: Vec<Dice>,
+    /// The classes with this spell on their spell list.
+    #[serde(default)]
+    classes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Components {
+    #[serde(default)]
+    verbal: bool,
+    #[serde(default)]
+    somatic: bool,
+    material: Option<String>,
+}
+
+impl Spells {
+    pub fn get(&self, name: &str) -> Option<&Spell> {
+        self.spells.get(name)
+    }
+
+    /// Spell names ordered by level, then name.
+    pub fn names(&self) -> Vec<String> {
+        let mut spells = self
+            .spells
+            .iter()
+            .map(|(name, spell)| (spell.level, name.clone()))
+            .collect::<Vec<(isize, String)>>();
+        spells.sort();
+        spells.into_iter().map(|(_, name)| name).collect()
+    }
+}
+
+impl Spell {
+    pub fn level(&self) -> isize {
+        self.level
+    }
+
+    /// Whether the named class has this spell on its list. Spells without class lists are
+    /// treated as being on every list.
+    pub fn on_list_of(&self, class: &str) -> bool {
+        self.classes.is_empty() || self.classes.iter().any(|c| c.eq_ignore_ascii_case(class))
+    }
+
+    pub fn damage_roll_name(&self) -> String {
+        self.damage_type
+            .clone()
+            .map(|t| format!("{} Damage", t))
+            .unwrap_or("Damage".to_string())
+    }
+
+    /// The target's saving throw, before its own modifier, if the spell calls for one.
+    pub fn save_roll_name(&self) -> Option<String> {
+        self.save.as_ref().map(|save| format!("{} Save", save))
+    }
+
+    /// The extra damage dice from casting with a slot of `slot_level`.
+    pub fn upcast_dice(&self, slot_level: isize) -> Vec<Dice> {
+        let levels_above = slot_level - self.level;
+        if self.level == 0 || levels_above <= 0 {
+            vec![]
+        } else {
+            self.upcast.iter().map(|d| d.times(levels_above)).collect()
+        }
+    }
+
+    pub fn summary(&self, spellcasting: Option<&Spellcasting>) -> String {
+        let level = if self.level == 0 {
+            format!("{} cantrip", self.school)
+        } else {
+            format!("Level {} {}", self.level, self.school)
+        };
+        let duration = if self.concentration {
+            format!("Concentration, {}", self.duration)
+        } else {
+            self.duration.clone()
+        };
+        let mut parts = vec![
+            level,
+            self.casting_time.clone(),
+            self.range.clone(),
+            self.components.to_string(),
+            duration,
+        ];
+        match (&self.save, spellcasting) {
+            (Some(save), Some(spellcasting)) => {
+                parts.push(format!("DC {} {} save", spellcasting.save_dc(), save))
+            }
+            (Some(save), None) => parts.push(format!("{} save", save)),
+            (None, _) => {}
+        }
+        parts.join(" · ")
+    }
+
+    /// The spell as a feature, cast with the spell attack and save DC of `spellcasting`, with its
+    /// attack and damage rolls tagged so that spellcasting effects apply to them.
+    pub fn to_feature(&self, name: &str, spellcasting: Option<&Spellcasting>) -> Feature {
+        let tags = |kind: [&str; 2]| {
+            let mut tags = HashMap::new();
+            tags.insert(
+                "type".to_string(),
+                kind.iter().map(|t| t.to_string()).collect(),
+            );
+            tags.insert("spell".to_string(), vec![name.to_string()]);
+            tags
+        };
+
+        let mut rolls = vec![];
+        if self.attack {
+            let mut roll = Roll::default();
+            roll.name("Attack".to_string());
+            roll.tags(tags(SPELL_ATTACK_TAGS));
+            roll.dice(vec![Dice::new(1, 20)]);
+            if let Some(spellcasting) = spellcasting {
+                roll.bonus(RollBonus::Modifier(Formula::Constant(
+                    spellcasting.attack(),
+                )));
+            }
+            rolls.push(roll);
+        }
+        if !self.damage.is_empty() {
+            let mut roll = Roll::default();
+            roll.name(self.damage_roll_name());
+            roll.tags(tags(SPELL_DAMAGE_TAGS));
+            roll.dice(self.damage.clone());
+            rolls.push(roll);
+        }
+        if let Some(save) = self.save_roll_name() {
+            let mut roll = Roll::default();
+            roll.name(save);
+            roll.tags(tags(SPELL_SAVE_TAGS));
+            roll.dice(vec![Dice::new(1, 20)]);
+            rolls.push(roll);
+        }
+
+        let description = match &self.description {
+            Some(description) => format!("{}\n{}", self.summary(spellcasting), description),
+            None => self.summary(spellcasting),
+        };
+
+        Feature::named(name)
+            .with_description(description)
+            .with_rolls(rolls)
+            .with_concentration(self.concentration)
+    }
+}
+
+impl std::fmt::Display for Components {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut components = vec![];
+        if self.verbal {
+            components.push("V".to_string());
+        }
+        if self.somatic {
+            components.push("S".to_string());
+        }
+        if let Some(material) = &self.material {
+            components.push(format!("M ({})", material));
+        }
+        write!(f, "{}", components.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::character::class::{Class, Classes};
+    use crate::core::ability_score::{AbilityScore, AbilityScores};
+    use crate::core::effect::{ActiveEffect, Effect};
+    use crate::core::feature::FeaturesState;
+    use crate::core::feature_path::FeaturePath;
+    use crate::core::provenance::{Amount, Contribution, Source};
+    use crate::core::roll::RollScope;
+    use crate::core::stat::{Stat, StatsState};
+
+    #[test]
+    fn spell_attacks_pick_up_spellcasting_effects() {
+        let spells: Spells = serde_json::from_str(
+            r#"{ "spells": { "Fire Bolt": {
+                "level": 0, "school": "Evocation", "casting_time": "1 action",
+                "range": "120 feet", "duration": "Instantaneous", "description": null,
+                "attack": true, "save": null, "damage": [{ "count": 1, "sides": 10 }],
+                "damage_type": "Fire"
+            } } }"#,
+        )
+        .unwrap();
+        let stats = StatsState::default();
+        let modifier = |m: isize| vec![Contribution::new(Amount::Modifier(m), Source::Base)];
+        // +4 charisma, +2 proficiency
+        let sorcerer = Spellcasting::new(
+            "Sorcerer".to_string(),
+            Ability::Charisma,
+            stats.value(&Stat::SpellSaveDc, modifier(14)),
+            stats.value(&Stat::SpellAttack, modifier(6)),
+        );
+        let fire_bolt = spells
+            .get("Fire Bolt")
+            .unwrap()
+            .to_feature("Fire Bolt", Some(&sorcerer));
+        let mut features = FeaturesState::from(vec![fire_bolt], &HashMap::new());
+
+        let mut scope = RollScope::default();
+        scope.tag(
+            "type".to_string(),
+            SPELL_ATTACK_TAGS.iter().map(|t| t.to_string()).collect(),
+        );
+        let wand = Effect::Roll {
+            bonus: RollBonus::Modifier(Formula::Constant(2)),
+            scope,
+            name: None,
+        };
+        features.apply_effects(&vec![ActiveEffect::new(wand, FeaturePath::empty())]);
+
+        let ability_scores = AbilityScores::default()
+            .with(Ability::Charisma, AbilityScore::of(18))
+            .to_state()
+            .modified();
+        let classes = Classes::from(vec![Class::new("Sorcerer".to_string(), 1)]);
+        let fire_bolt = FeaturePath::of(vec!["Fire Bolt".to_string()]);
+        let attack = features.find_roll(&fire_bolt, "Attack").unwrap();
+        let damage = features.find_roll(&fire_bolt, "Fire Damage").unwrap();
+
+        // +6 spell attack, +2 wand
+        assert_eq!(attack.rollable(&ability_scores, &classes).bonus(), 8);
+        assert_eq!(damage.rollable(&ability_scores, &classes).bonus(), 0);
+    }
+
+    #[test]
+    fn upcasting_adds_dice_per_slot_level() {
+        let fireball: Spell = serde_json::from_str(
+            r#"{ "level": 3, "school": "Evocation", "casting_time": "1 action",
+                "range": "150 feet", "duration": "Instantaneous", "description": null,
+                "save": "Dexterity", "damage": [{ "count": 8, "sides": 6 }],
+                "damage_type": "Fire", "upcast": [{ "count": 1, "sides": 6 }] }"#,
+        )
+        .unwrap();
+
+        assert_eq!(fireball.upcast_dice(3), vec![]);
+        assert_eq!(fireball.upcast_dice(5), vec![Dice::new(2, 6)]);
+    }
+}
@@ -0,0 +1,103 @@
+use crate::core::effect::Effect;
+use crate::core::roll::Dice;
+use crate::core::slot::Recovery;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Tables {
+    #[serde(default)]
+    tables: HashMap<String, Table>,
+}
+
+/// A random table, such as the wild magic surge table, rolled with `dice`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Table {
+    dice: Vec<Dice>,
+    entries: Vec<TableEntry>,
+    /// Paths of slots refilled whenever the table is rolled on, whatever the result.
+    #[serde(default)]
+    resets: Vec<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableEntry {
+    /// The lowest and highest rolls giving this entry, inclusive.
+    min: isize,
+    max: isize,
+    text: String,
+    /// Effects applied to the character when this entry is rolled.
+    #[serde(default)]
+    effects: Vec<Effect>,
+    /// The rest that ends the effects. Without one they last until ended by hand.
+    until: Option<Recovery>,
+    /// Paths of slots refilled when this entry is rolled.
+    #[serde(default)]
+    resets: Vec<Vec<String>>,
+}
+
+impl Tables {
+    pub fn get(&self, name: &str) -> Option<&Table> {
+        self.tables.get(name)
+    }
+}
+
+impl Table {
+    pub fn dice(&self) -> Vec<Dice> {
+        self.dice.clone()
+    }
+
+    pub fn entry(&self, roll: isize) -> Option<&TableEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.min <= roll && roll <= entry.max)
+    }
+
+    /// Slots refilled by rolling `roll`, both those reset by any roll and by the entry.
+    pub fn resets(&self, roll: isize) -> Vec<Vec<String>> {
+        let mut resets = self.resets.clone();
+        if let Some(entry) = self.entry(roll) {
+            resets.extend(entry.resets.clone());
+        }
+        resets
+    }
+}
+
+impl TableEntry {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn effects(&self) -> &Vec<Effect> {
+        &self.effects
+    }
+
+    pub fn until(&self) -> Option<Recovery> {
+        self.until.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rolls_find_the_entry_covering_them() {
+        let table: Table = serde_json::from_str(
+            r#"{ "dice": [{ "count": 1, "sides": 100 }],
+                "resets": [["Wild Magic", "Tides of Chaos"]],
+                "entries": [
+                    { "min": 1, "max": 2, "text": "Roll again", "until": null },
+                    { "min": 3, "max": 100, "text": "You glow", "until": "LongRest",
+                      "resets": [["Font of Magic", "Sorcery Points"]] }
+                ] }"#,
+        )
+        .unwrap();
+
+        assert_eq!(table.entry(2).map(|e| e.text()), Some("Roll again"));
+        assert_eq!(table.entry(100).map(|e| e.text()), Some("You glow"));
+        assert!(table.entry(101).is_none());
+        assert_eq!(table.resets(1).len(), 1);
+        assert_eq!(table.resets(50).len(), 2);
+    }
+}
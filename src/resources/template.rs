@@ -1,19 +1,311 @@
+use crate::character::class::Class;
+use crate::character::description::Size;
+use crate::core::ability_score::Ability;
+use crate::core::effect::Effect;
 use crate::core::feature::Feature;
+use crate::resources::progression::ClassProgression;
+use serde::export::Formatter;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt::Display;
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Templates {
     #[serde(default)]
     features: HashMap<String, Feature>,
+    #[serde(default)]
+    classes: HashMap<String, ClassProgression>,
+    #[serde(default)]
+    races: HashMap<String, Grants>,
+    #[serde(default)]
+    backgrounds: HashMap<String, Grants>,
+    #[serde(default)]
+    feats: HashMap<String, Grants>,
+    /// Starting equipment, granting each item as a feature.
+    #[serde(default)]
+    equipment_packs: HashMap<String, Grants>,
 }
 
-impl Templates {
-    pub fn new(features: HashMap<String, Feature>) -> Templates {
-        Templates { features }
+/// The kinds of template a character can take. Classes and subclasses are taken through the
+/// character's classes, the others by name.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum TemplateKind {
+    Class,
+    Subclass,
+    Race,
+    Background,
+    Feat,
+}
+
+/// What a template gives the character taking it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Grants {
+    /// Names of feature templates, including proficiencies such as "Stealth Proficiency".
+    #[serde(default)]
+    features: Vec<String>,
+    #[serde(default)]
+    ability_bonuses: Vec<AbilityBonus>,
+    /// Effects beyond ability bonuses, such as a feat raising a stat.
+    #[serde(default)]
+    effects: Vec<Effect>,
+    speed: Option<isize>,
+    size: Option<Size>,
+    #[serde(default)]
+    languages: Vec<String>,
+    #[serde(default)]
+    tools: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbilityBonus {
+    pub ability: Ability,
+    pub bonus: isize,
+}
+
+/// A template taken by a character, with everything it grants at the character's level.
+#[derive(Debug, Clone)]
+pub struct Granted {
+    pub kind: TemplateKind,
+    pub name: String,
+    pub grants: Grants,
+}
+
+impl Display for TemplateKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Grants {
+    pub fn features(&self) -> &Vec<String> {
+        &self.features
+    }
+
+    pub fn ability_bonuses(&self) -> &Vec<AbilityBonus> {
+        &self.ability_bonuses
+    }
+
+    pub fn effects(&self) -> &Vec<Effect> {
+        &self.effects
+    }
+
+    pub fn speed(&self) -> Option<isize> {
+        self.speed
+    }
+
+    pub fn size(&self) -> Option<Size> {
+        self.size.clone()
+    }
+
+    pub fn languages(&self) -> &Vec<String> {
+        &self.languages
+    }
+
+    pub fn tools(&self) -> &Vec<String> {
+        &self.tools
     }
 
+    pub fn with_features(self, features: Vec<String>) -> Grants {
+        let mut grants = self;
+        grants.features.extend(features);
+        grants
+    }
+}
+
+impl Templates {
     pub fn features(&self) -> &HashMap<String, Feature> {
         &self.features
     }
+
+    pub fn class(&self, name: &str) -> Option<&ClassProgression> {
+        self.classes.get(name)
+    }
+
+    /// Names of the classes with a progression, alphabetically.
+    pub fn class_names(&self) -> Vec<String> {
+        sorted(self.classes.keys())
+    }
+
+    pub fn race_names(&self) -> Vec<String> {
+        sorted(self.races.keys())
+    }
+
+    pub fn background_names(&self) -> Vec<String> {
+        sorted(self.backgrounds.keys())
+    }
+
+    pub fn background(&self, name: &str) -> Option<&Grants> {
+        self.backgrounds.get(name)
+    }
+
+    pub fn feat_names(&self) -> Vec<String> {
+        sorted(self.feats.keys())
+    }
+
+    pub fn equipment_pack_names(&self) -> Vec<String> {
+        sorted(self.equipment_packs.keys())
+    }
+
+    pub fn equipment_pack(&self, name: &str) -> Option<&Grants> {
+        self.equipment_packs.get(name)
+    }
+
+    /// The templates a character takes through its classes, race, background and feats. Classes
+    /// and subclasses grant the features of every level the character has reached, and only
+    /// the first class grants its starting proficiencies. Names without a template are skipped.
+    pub fn granted(
+        &self,
+        classes: &[Class],
+        race: Option<&str>,
+        background: Option<&str>,
+        feats: &[String],
+    ) -> Vec<Granted> {
+        let mut granted = vec![];
+        for (index, class) in classes.iter().enumerate() {
+            let progression = match self.classes.get(class.name()) {
+                Some(progression) => progression,
+                None => continue,
+            };
+            granted.push(Granted {
+                kind: TemplateKind::Class,
+                name: class.name().to_string(),
+                grants: if index == 0 {
+                    progression.grants(class.level())
+                } else {
+                    progression.multiclass_grants(class.level())
+                },
+            });
+            if let Some(subclass) = class.subclass() {
+                if let Some(grants) = progression.subclass_grants(subclass, class.level()) {
+                    granted.push(Granted {
+                        kind: TemplateKind::Subclass,
+                        name: subclass.to_string(),
+                        grants,
+                    });
+                }
+            }
+        }
+
+        let named = vec![
+            (TemplateKind::Race, &self.races, race.into_iter().collect()),
+            (
+                TemplateKind::Background,
+                &self.backgrounds,
+                background.into_iter().collect(),
+            ),
+            (
+                TemplateKind::Feat,
+                &self.feats,
+                feats
+                    .iter()
+                    .map(|feat| feat.as_str())
+                    .collect::<Vec<&str>>(),
+            ),
+        ];
+        for (kind, templates, names) in named {
+            for name in names {
+                if let Some(grants) = templates.get(name) {
+                    granted.push(Granted {
+                        kind: kind.clone(),
+                        name: name.to_string(),
+                        grants: grants.clone(),
+                    });
+                }
+            }
+        }
+        granted
+    }
+}
+
+fn sorted<'a>(names: impl Iterator<Item = &'a String>) -> Vec<String> {
+    let mut names = names.cloned().collect::<Vec<String>>();
+    names.sort();
+    names
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn characters_take_templates_of_every_kind() {
+        let templates: Templates = serde_json::from_str(
+            r#"{
+                "classes": { "Sorcerer": { "hit_die": 6,
+                    "grants": { "features": ["Charisma Saving Throw Proficiency"] },
+                    "subclasses": { "Wild Magic": {
+                        "grants": { "features": ["Wild Magic Surge"] },
+                        "levels": [{ "level": 6, "features": ["Bend Luck"] }] } },
+                    "levels": [{ "level": 2, "features": ["Font of Magic"] }] } },
+                "races": { "Half-Elf": { "speed": 30, "languages": ["Elvish"],
+                    "ability_bonuses": [{ "ability": "Charisma", "bonus": 2 }] } },
+                "feats": { "Alert": { "features": ["Alert"], "effects": [{ "type": "Stat",
+                    "bonus": { "type": "Modifier", "modifier": 5 }, "stat": "Initiative" }] } }
+            }"#,
+        )
+        .unwrap();
+        let classes = vec![
+            Class::new("Sorcerer".to_string(), 2).with_subclass(Some("Wild Magic".to_string()))
+        ];
+
+        let granted = templates.granted(&classes, Some("Half-Elf"), Some("Sage"), &[]);
+        let summary = granted
+            .iter()
+            .map(|g| (g.kind.clone(), g.name.clone(), g.grants.features().clone()))
+            .collect::<Vec<(TemplateKind, String, Vec<String>)>>();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    TemplateKind::Class,
+                    "Sorcerer".to_string(),
+                    vec![
+                        "Charisma Saving Throw Proficiency".to_string(),
+                        "Font of Magic".to_string()
+                    ]
+                ),
+                (
+                    TemplateKind::Subclass,
+                    "Wild Magic".to_string(),
+                    vec!["Wild Magic Surge".to_string()]
+                ),
+                (TemplateKind::Race, "Half-Elf".to_string(), vec![]),
+            ]
+        );
+        assert_eq!(granted[2].grants.speed(), Some(30));
+
+        let feats = templates.granted(&[], None, None, &["Alert".to_string()]);
+        assert_eq!(feats[0].kind, TemplateKind::Feat);
+        assert_eq!(feats[0].grants.effects().len(), 1);
+    }
+
+    #[test]
+    fn later_classes_grant_only_multiclass_proficiencies() {
+        let templates: Templates = serde_json::from_str(
+            r#"{ "classes": {
+                "Sorcerer": { "hit_die": 6,
+                    "grants": { "features": ["Charisma Saving Throw Proficiency"] },
+                    "levels": [{ "level": 1, "features": ["Spellcasting"] }] },
+                "Fighter": { "hit_die": 10,
+                    "grants": { "features": ["Strength Saving Throw Proficiency",
+                        "Light Armor Proficiency"] },
+                    "multiclass": { "features": ["Light Armor Proficiency"] },
+                    "levels": [{ "level": 1, "features": ["Second Wind"] }] } } }"#,
+        )
+        .unwrap();
+        let classes = vec![
+            Class::new("Sorcerer".to_string(), 3),
+            Class::new("Fighter".to_string(), 1),
+        ];
+
+        let granted = templates.granted(&classes, None, None, &[]);
+        assert_eq!(
+            granted[0].grants.features(),
+            &vec!["Charisma Saving Throw Proficiency", "Spellcasting"]
+        );
+        assert_eq!(
+            granted[1].grants.features(),
+            &vec!["Light Armor Proficiency", "Second Wind"]
+        );
+    }
 }
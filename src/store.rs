@@ -1,8 +1,81 @@
+use std::fmt::{Display, Formatter};
+
 #[derive(Debug, Clone, Eq, PartialOrd, PartialEq)]
 pub enum StoreError {
     FileError(String),
     DirectoryError(String),
     WriteError(String),
+    NotFound(String),
+}
+
+/// A stored document that can't be read as what it should hold, and where it goes wrong.
+#[derive(Debug, Clone, Eq, PartialOrd, PartialEq)]
+pub struct ParseError {
+    pub key: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn of(key: &str, error: &serde_json::Error) -> ParseError {
+        let location = format!(" at line {} column {}", error.line(), error.column());
+        ParseError {
+            key: key.to_string(),
+            line: error.line(),
+            column: error.column(),
+            message: error.to_string().trim_end_matches(&location).to_string(),
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}, line {} column {}: {}",
+            self.key, self.line, self.column, self.message
+        )
+    }
+}
+
+/// How many earlier versions of each document are kept.
+pub const BACKUPS: usize = 20;
+
+/// An earlier version of a document, kept under `backups/<key>/<milliseconds>`, or
+/// `<milliseconds>-<sequence>` when several are replaced within the same millisecond.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Backup {
+    pub key: String,
+    /// Milliseconds since the epoch at which it was replaced.
+    pub saved_at: u128,
+    pub sequence: usize,
+}
+
+impl Backup {
+    fn prefix(key: &str) -> String {
+        format!("backups/{}", key.trim_start_matches('/'))
+    }
+
+    fn name(saved_at: u128, sequence: usize) -> String {
+        match sequence {
+            0 => saved_at.to_string(),
+            _ => format!("{}-{}", saved_at, sequence),
+        }
+    }
+
+    fn parse(key: String) -> Option<Backup> {
+        let name = key.rsplit('/').next()?;
+        let (saved_at, sequence) = match name.split_once('-') {
+            Some((saved_at, sequence)) => (saved_at.parse().ok()?, sequence.parse().ok()?),
+            None => (name.parse().ok()?, 0),
+        };
+        Some(Backup {
+            key,
+            saved_at,
+            sequence,
+        })
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialOrd, PartialEq)]
@@ -35,7 +108,10 @@ impl Store {
 
         let mut file = async_std::fs::File::open(self.path_for(key))
             .await
-            .map_err(|e| StoreError::FileError(e.to_string()))?;
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => StoreError::NotFound(e.to_string()),
+                _ => StoreError::FileError(e.to_string()),
+            })?;
 
         file.read_to_string(&mut contents)
             .await
@@ -44,16 +120,25 @@ impl Store {
         Ok(contents)
     }
 
+    /// Writes `content` to a temporary file, syncs it and renames it over `key`, so that a
+    /// crash leaves either the old document or the new one. The old one is kept as a backup.
     pub async fn save(&self, key: String, content: String) -> Result<(), StoreError> {
+        self.write(key, content, true).await
+    }
+
+    /// Saves like `save` without keeping the old document, for files that aren't versioned.
+    pub async fn save_without_backup(
+        &self,
+        key: String,
+        content: String,
+    ) -> Result<(), StoreError> {
+        self.write(key, content, false).await
+    }
+
+    async fn write(&self, key: String, content: String, back_up: bool) -> Result<(), StoreError> {
         use async_std::prelude::*;
 
-        let path = self.path_for(key).split('/').into_iter().fold(
-            std::path::PathBuf::new(),
-            |mut path, part| {
-                path.push(part);
-                path
-            },
-        );
+        let path = std::path::PathBuf::from(self.path_for(key.clone()));
 
         if let Some(dir) = path.parent() {
             async_std::fs::create_dir_all(dir)
@@ -61,43 +146,298 @@ impl Store {
                 .map_err(|e| StoreError::DirectoryError(e.to_string()))?;
         }
 
+        let temporary = std::path::PathBuf::from(format!("{}.tmp", path.display()));
         {
-            let mut file = async_std::fs::OpenOptions::new()
-                .append(false)
-                // .read(true)
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .open(path)
+            let mut file = async_std::fs::File::create(&temporary)
                 .await
                 .map_err(|e| StoreError::FileError(e.to_string()))?;
 
             file.write_all(content.as_bytes())
                 .await
                 .map_err(|e| StoreError::WriteError(e.to_string()))?;
+            file.sync_all()
+                .await
+                .map_err(|e| StoreError::WriteError(e.to_string()))?;
+        }
+
+        if back_up && async_std::path::Path::new(&path).is_file().await {
+            self.back_up(key.clone()).await?;
+        }
+
+        async_std::fs::rename(&temporary, &path)
+            .await
+            .map_err(|e| StoreError::FileError(e.to_string()))?;
+        if let Some(dir) = path.parent() {
+            // Syncing the directory makes the rename itself durable; not every platform allows it.
+            if let Ok(dir) = async_std::fs::File::open(dir).await {
+                let _ = dir.sync_all().await;
+            }
+        }
+
+        match back_up {
+            true => self.prune_backups(key).await,
+            false => Ok(()),
+        }
+    }
+
+    /// Adds `content` to the end of the file at `key`, creating it, and syncs it. Nothing
+    /// already written is touched, which suits logs that are only ever added to.
+    pub async fn append(&self, key: String, content: String) -> Result<(), StoreError> {
+        use async_std::prelude::*;
+
+        let path = self.path_for(key);
+        if let Some(dir) = std::path::Path::new(&path).parent() {
+            async_std::fs::create_dir_all(dir)
+                .await
+                .map_err(|e| StoreError::DirectoryError(e.to_string()))?;
+        }
+        let mut file = async_std::fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .await
+            .map_err(|e| StoreError::FileError(e.to_string()))?;
+        file.write_all(content.as_bytes())
+            .await
+            .map_err(|e| StoreError::WriteError(e.to_string()))?;
+        file.sync_all()
+            .await
+            .map_err(|e| StoreError::WriteError(e.to_string()))
+    }
+
+    /// Backups of `key`, newest first.
+    pub async fn backups(&self, key: String) -> Result<Vec<Backup>, StoreError> {
+        let mut backups: Vec<Backup> = self
+            .list(Backup::prefix(&key))
+            .await?
+            .into_iter()
+            .filter_map(Backup::parse)
+            .collect();
+        backups.sort_by(|a, b| (b.saved_at, b.sequence).cmp(&(a.saved_at, a.sequence)));
+        Ok(backups)
+    }
+
+    /// Puts a backup back in place of `key`. What `key` held becomes a backup in turn.
+    pub async fn restore(&self, key: String, backup: &Backup) -> Result<(), StoreError> {
+        let content = self.load(backup.key.clone()).await?;
+        self.save(key, content).await
+    }
+
+    async fn back_up(&self, key: String) -> Result<(), StoreError> {
+        let saved_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|time| time.as_millis())
+            .unwrap_or_default();
+        let mut sequence = 0;
+        let mut backup;
+        loop {
+            let name = Backup::name(saved_at, sequence);
+            backup = self.path_for(format!("{}/{}", Backup::prefix(&key), name));
+            if !async_std::path::Path::new(&backup).exists().await {
+                break;
+            }
+            sequence += 1;
+        }
+        if let Some(dir) = std::path::Path::new(&backup).parent() {
+            async_std::fs::create_dir_all(dir)
+                .await
+                .map_err(|e| StoreError::DirectoryError(e.to_string()))?;
         }
+        async_std::fs::copy(self.path_for(key), backup)
+            .await
+            .map(|_| ())
+            .map_err(|e| StoreError::FileError(e.to_string()))
+    }
 
+    async fn prune_backups(&self, key: String) -> Result<(), StoreError> {
+        for backup in self.backups(key).await?.into_iter().skip(BACKUPS) {
+            self.remove(backup.key).await?;
+        }
         Ok(())
     }
+
+    /// Keys of the files directly under `prefix`, sorted. A missing directory has none.
+    pub async fn list(&self, prefix: String) -> Result<Vec<String>, StoreError> {
+        use async_std::prelude::*;
+
+        let mut entries = match async_std::fs::read_dir(self.path_for(prefix.clone())).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(StoreError::DirectoryError(e.to_string())),
+        };
+        let mut keys = vec![];
+        while let Some(entry) = entries.next().await {
+            let entry = entry.map_err(|e| StoreError::DirectoryError(e.to_string()))?;
+            let is_file = entry
+                .file_type()
+                .await
+                .map_err(|e| StoreError::DirectoryError(e.to_string()))?
+                .is_file();
+            if is_file {
+                let name = entry.file_name().to_string_lossy().to_string();
+                keys.push(format!("{}/{}", prefix.trim_end_matches('/'), name));
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
+    /// Moves the file at `from` to `to`, creating its directory.
+    pub async fn rename(&self, from: String, to: String) -> Result<(), StoreError> {
+        let to = self.path_for(to);
+        if let Some(dir) = std::path::Path::new(&to).parent() {
+            async_std::fs::create_dir_all(dir)
+                .await
+                .map_err(|e| StoreError::DirectoryError(e.to_string()))?;
+        }
+        async_std::fs::rename(self.path_for(from), to)
+            .await
+            .map_err(|e| StoreError::FileError(e.to_string()))
+    }
+
+    /// Moves a file that can't be read out of the way, under `quarantine/`, numbered by the
+    /// time it was moved. Returns its new key.
+    pub async fn quarantine(&self, key: String) -> Result<String, StoreError> {
+        let seconds = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+        let quarantined = format!("quarantine/{}.{}", key.trim_start_matches('/'), seconds);
+        self.rename(key, quarantined.clone()).await?;
+        Ok(quarantined)
+    }
+
+    pub async fn remove(&self, key: String) -> Result<(), StoreError> {
+        async_std::fs::remove_file(self.path_for(key))
+            .await
+            .map_err(|e| StoreError::FileError(e.to_string()))
+    }
 }
 
 #[cfg(test)]
-mod test {
-    use super::Store;
+pub mod test {
+    use super::{ParseError, Store, StoreError, BACKUPS};
+
+    /// A directory under the system temp directory, removed when dropped so a failing test
+    /// doesn't leave it behind.
+    pub struct TestDirectory(pub String);
+
+    impl TestDirectory {
+        pub fn new(name: &str) -> TestDirectory {
+            let path = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&path);
+            TestDirectory(path.to_string_lossy().to_string())
+        }
+    }
+
+    impl Drop for TestDirectory {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
     #[actix_rt::test]
     async fn file_persistence() {
         let content = "hello, I am the content you are looking for!".to_string();
         let key = "my-thing".to_string();
-        let test_directory = "store-test".to_string();
-        let store = Store::new(test_directory.clone()).unwrap();
+        let test_directory = TestDirectory::new("store-test");
+        let store = Store::new(test_directory.0.clone()).unwrap();
         let first = store.load(key.clone()).await;
-        assert!(first.is_err());
+        assert!(matches!(first, Err(StoreError::NotFound(_))));
 
         let save = store.save(key.clone(), content.clone()).await;
         assert_eq!(save, Ok(()));
 
         let load = store.load(key.clone()).await;
         assert_eq!(load, Ok(content));
-        std::fs::remove_dir_all(test_directory);
+    }
+
+    #[test]
+    fn parse_errors_say_where_the_document_goes_wrong() {
+        let error = serde_json::from_str::<serde_json::Value>("{\n  \"name\": }").unwrap_err();
+        assert_eq!(
+            ParseError::of("characters/vynne.json", &error).to_string(),
+            "characters/vynne.json, line 2 column 11: expected value"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn files_are_listed_moved_and_removed() {
+        let test_directory = TestDirectory::new("store-list-test");
+        let store = Store::new(test_directory.0.clone()).unwrap();
+        assert_eq!(store.list("things".to_string()).await, Ok(vec![]));
+
+        for key in vec!["things/b", "things/a", "things/nested/c"] {
+            store.save(key.to_string(), "".to_string()).await.unwrap();
+        }
+        assert_eq!(
+            store.list("things".to_string()).await,
+            Ok(vec!["things/a".to_string(), "things/b".to_string()])
+        );
+
+        store
+            .rename("things/a".to_string(), "archive/a".to_string())
+            .await
+            .unwrap();
+        store.remove("things/b".to_string()).await.unwrap();
+        assert_eq!(store.list("things".to_string()).await, Ok(vec![]));
+        assert_eq!(
+            store.list("archive".to_string()).await,
+            Ok(vec!["archive/a".to_string()])
+        );
+    }
+
+    #[actix_rt::test]
+    async fn saves_keep_earlier_versions_to_restore() {
+        let test_directory = TestDirectory::new("store-backup-test");
+        let store = Store::new(test_directory.0.clone()).unwrap();
+        let key = "things/a".to_string();
+        store.save(key.clone(), "first".to_string()).await.unwrap();
+        assert_eq!(store.backups(key.clone()).await, Ok(vec![]));
+
+        for version in 0..BACKUPS + 2 {
+            std::thread::sleep(std::time::Duration::from_millis(2));
+            store.save(key.clone(), version.to_string()).await.unwrap();
+        }
+        assert_eq!(
+            store.list("things".to_string()).await,
+            Ok(vec![key.clone()])
+        );
+        let backups = store.backups(key.clone()).await.unwrap();
+        assert_eq!(backups.len(), BACKUPS);
+        assert_eq!(
+            store.load(backups[0].key.clone()).await,
+            Ok(BACKUPS.to_string())
+        );
+
+        store.restore(key.clone(), &backups[0]).await.unwrap();
+        assert_eq!(store.load(key.clone()).await, Ok(BACKUPS.to_string()));
+        let backups = store.backups(key.clone()).await.unwrap();
+        assert_eq!(
+            store.load(backups[0].key.clone()).await,
+            Ok((BACKUPS + 1).to_string())
+        );
+    }
+
+    #[actix_rt::test]
+    async fn saves_within_a_millisecond_keep_every_backup() {
+        let test_directory = TestDirectory::new("store-sequence-test");
+        let store = Store::new(test_directory.0.clone()).unwrap();
+        let key = "things/a".to_string();
+        for version in 0..4 {
+            store.save(key.clone(), version.to_string()).await.unwrap();
+        }
+        let backups = store.backups(key.clone()).await.unwrap();
+        let mut contents = vec![];
+        for backup in backups {
+            contents.push(store.load(backup.key).await.unwrap());
+        }
+        assert_eq!(contents, vec!["2", "1", "0"]);
+
+        store
+            .save_without_backup(key.clone(), "4".to_string())
+            .await
+            .unwrap();
+        assert_eq!(store.backups(key).await.unwrap().len(), 3);
     }
 }